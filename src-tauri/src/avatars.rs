@@ -0,0 +1,274 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::{AppError, AppResult};
+use crate::image_cache;
+
+// Profile pictures for chatters, keyed by "platform:user_id".
+// YouTube sends the avatar with every message. Twitch doesn't, so unknown chatters are
+// queued and looked up in batches through the Helix users endpoint; the result is sent
+// to the frontend as an `avatar-update` event.
+static AVATARS: Lazy<Mutex<HashMap<String, AvatarEntry>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static PENDING: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+static TWITCH_CREDENTIALS: Lazy<Mutex<Option<(String, String)>>> = Lazy::new(|| Mutex::new(None));
+static CACHE_FILE: OnceCell<PathBuf> = OnceCell::new();
+// Set when avatars changed since the cache file was last written
+static CACHE_DIRTY: AtomicBool = AtomicBool::new(false);
+static APP: OnceCell<AppHandle> = OnceCell::new();
+
+// How long an avatar is trusted before it's looked up again
+const AVATAR_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+// How often queued Twitch lookups are sent and changes are saved to disk
+const LOOKUP_INTERVAL: Duration = Duration::from_millis(500);
+// Helix accepts up to 100 IDs per users request
+const MAX_BATCH: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AvatarEntry {
+    // Empty when the user has no avatar or wasn't found, so they aren't looked up again
+    url: String,
+    fetched_at: i64,
+}
+
+impl AvatarEntry {
+    fn is_fresh(&self) -> bool {
+        let age = chrono::Utc::now().timestamp() - self.fetched_at;
+        age >= 0 && (age as u64) < AVATAR_TTL.as_secs()
+    }
+}
+
+// Payload of the `avatar-update` event
+#[derive(Debug, Clone, Serialize)]
+struct AvatarUpdate {
+    platform: &'static str,
+    user_id: String,
+    avatar: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UsersResponse {
+    data: Vec<HelixUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HelixUser {
+    id: String,
+    profile_image_url: String,
+}
+
+fn cache_key(platform: &str, user_id: &str) -> String {
+    format!("{}:{}", platform, user_id)
+}
+
+// Load avatars saved by a previous run and start the Twitch lookup loop. Called once from setup.
+pub fn start(app: AppHandle, data_dir: Option<PathBuf>) {
+    if APP.set(app.clone()).is_err() {
+        return;
+    }
+
+    if let Some(dir) = data_dir {
+        load_disk_cache(dir);
+    }
+
+    tauri::async_runtime::spawn(async move {
+        let client = reqwest::Client::new();
+        loop {
+            tokio::time::sleep(LOOKUP_INTERVAL).await;
+            if let Err(e) = lookup_pending(&app, &client).await {
+                warn!("Twitch avatar lookup failed: {}", e);
+            }
+            // One write for everything that changed since the last tick, instead of one
+            // per chatter
+            if CACHE_DIRTY.swap(false, Ordering::SeqCst) {
+                save_disk_cache();
+            }
+        }
+    });
+}
+
+// Credentials for the Helix lookups; set once the Twitch listener has a token
+pub fn set_twitch_credentials(client_id: String, token: String) {
+    if let Ok(mut credentials) = TWITCH_CREDENTIALS.lock() {
+        *credentials = Some((client_id, token));
+    }
+}
+
+// Stop Helix lookups until a token for the new profile is set
+pub fn clear_twitch_credentials() {
+    if let Ok(mut credentials) = TWITCH_CREDENTIALS.lock() {
+        *credentials = None;
+    }
+}
+
+// Avatar URL for a chatter if we know it, ready for the webview. Unknown Twitch chatters
+// are queued for lookup.
+pub fn twitch_avatar(user_id: &str) -> Option<String> {
+    let key = cache_key("twitch", user_id);
+    let entry = AVATARS.lock().ok().and_then(|avatars| avatars.get(&key).cloned());
+
+    // Twitch user IDs are numeric; anything else (like mock chatters) can't be looked up
+    let valid_id = !user_id.is_empty() && user_id.bytes().all(|b| b.is_ascii_digit());
+    if valid_id && !entry.as_ref().map(|entry| entry.is_fresh()).unwrap_or(false) {
+        if let Ok(mut pending) = PENDING.lock() {
+            pending.insert(user_id.to_string());
+        }
+    }
+
+    entry.filter(|entry| !entry.url.is_empty())
+        .map(|entry| image_cache::resolve(&entry.url))
+}
+
+// Record the avatar YouTube sent with a message and return it ready for the webview
+pub fn youtube_avatar(channel_id: Option<&str>, profile_image_url: Option<&str>) -> Option<String> {
+    let url = profile_image_url.filter(|url| !url.is_empty())?;
+
+    if let Some(channel_id) = channel_id {
+        let key = cache_key("youtube", channel_id);
+        let changed = AVATARS.lock()
+            .map(|mut avatars| {
+                let changed = avatars.get(&key).map(|entry| entry.url != url || !entry.is_fresh()).unwrap_or(true);
+                if changed {
+                    avatars.insert(key, AvatarEntry {
+                        url: url.to_string(),
+                        fetched_at: chrono::Utc::now().timestamp(),
+                    });
+                }
+                changed
+            })
+            .unwrap_or(false);
+
+        if changed {
+            CACHE_DIRTY.store(true, Ordering::SeqCst);
+        }
+    }
+
+    Some(image_cache::resolve(url))
+}
+
+async fn lookup_pending(app: &AppHandle, client: &reqwest::Client) -> AppResult<()> {
+    let credentials = TWITCH_CREDENTIALS.lock()
+        .map_err(|e| AppError::internal(format!("Failed to lock Twitch credentials: {}", e)))?
+        .clone();
+    // Without a token there's nothing we can do yet; keep the queue for later
+    let Some((client_id, token)) = credentials else {
+        return Ok(());
+    };
+
+    let ids = match PENDING.lock() {
+        Ok(mut pending) => {
+            let ids = pending.iter().take(MAX_BATCH).cloned().collect::<Vec<_>>();
+            for id in &ids {
+                pending.remove(id);
+            }
+            ids
+        },
+        Err(e) => return Err(AppError::internal(format!("Failed to lock avatar queue: {}", e))),
+    };
+
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let users = fetch_twitch_users(client, &client_id, &token, &ids).await?;
+    let now = chrono::Utc::now().timestamp();
+
+    let mut updates = Vec::new();
+    if let Ok(mut avatars) = AVATARS.lock() {
+        for id in &ids {
+            let url = users.iter()
+                .find(|user| user.id == *id)
+                .map(|user| user.profile_image_url.clone())
+                .unwrap_or_default();
+
+            if !url.is_empty() {
+                updates.push(AvatarUpdate {
+                    platform: "twitch",
+                    user_id: id.clone(),
+                    avatar: image_cache::resolve(&url),
+                });
+            }
+            avatars.insert(cache_key("twitch", id), AvatarEntry { url, fetched_at: now });
+        }
+    }
+    CACHE_DIRTY.store(true, Ordering::SeqCst);
+
+    for update in updates {
+        let _ = app.emit_all("avatar-update", update);
+    }
+    Ok(())
+}
+
+async fn fetch_twitch_users(
+    client: &reqwest::Client,
+    client_id: &str,
+    token: &str,
+    ids: &[String],
+) -> AppResult<Vec<HelixUser>> {
+    let mut headers = HeaderMap::new();
+    headers.insert("Client-Id", HeaderValue::from_str(client_id).map_err(|e| AppError::config(format!("Invalid client ID: {}", e)))?);
+    headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|e| AppError::auth("twitch", format!("Invalid token: {}", e)))?);
+
+    let query = ids.iter().map(|id| ("id", id.as_str())).collect::<Vec<_>>();
+
+    let response = client.get("https://api.twitch.tv/helix/users")
+        .headers(headers)
+        .query(&query)
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+
+    let users: UsersResponse = response.json()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    Ok(users.data)
+}
+
+fn load_disk_cache(dir: PathBuf) {
+    let path = dir.join("avatars.json");
+    let _ = CACHE_FILE.set(path.clone());
+
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return;
+    };
+
+    match serde_json::from_str::<HashMap<String, AvatarEntry>>(&contents) {
+        Ok(saved) => {
+            info!("Loaded {} avatars from disk", saved.len());
+            if let Ok(mut avatars) = AVATARS.lock() {
+                avatars.extend(saved);
+            }
+        },
+        Err(e) => warn!("Ignoring unreadable avatar cache {}: {}", path.display(), e),
+    }
+}
+
+fn save_disk_cache() {
+    let Some(path) = CACHE_FILE.get() else {
+        return;
+    };
+
+    let result = AVATARS.lock()
+        .map_err(|e| e.to_string())
+        .and_then(|avatars| serde_json::to_string(&*avatars).map_err(|e| e.to_string()))
+        .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+
+    if let Err(e) = result {
+        warn!("Failed to save avatar cache to {}: {}", path.display(), e);
+    }
+}
@@ -1,14 +1,31 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::sync::Once;
+use std::time::Duration;
+use log::{info, warn};
 use once_cell::sync::Lazy;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 
+use crate::error::{AppError, AppResult};
+use crate::image_cache;
+
 // Global store for channel and global badges
-static BADGES_INITIALIZED: Once = Once::new();
+static BACKGROUND_REFRESH: Once = Once::new();
 static BADGE_CACHE: Lazy<Arc<Mutex<BadgeCache>>> = 
     Lazy::new(|| Arc::new(Mutex::new(BadgeCache::new())));
+// Credentials and channel the background refresh keeps up to date
+static REFRESH_TARGET: Lazy<Mutex<Option<RefreshTarget>>> = Lazy::new(|| Mutex::new(None));
+
+// Badge metadata is saved here so badges show up at startup, even offline. Each profile
+// has its own.
+static CACHE_FILE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+// How old the saved badges can get before they're fetched again
+const BADGE_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+// How often the background task checks whether the badges are stale
+const REFRESH_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 // Structures for the badge API responses
 #[derive(Debug, Deserialize)]
@@ -22,7 +39,7 @@ struct BadgeSet {
     versions: Vec<BadgeVersion>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct BadgeVersion {
     id: String,
     image_url_1x: String,
@@ -32,126 +49,336 @@ struct BadgeVersion {
     description: String,
 }
 
+impl BadgeVersion {
+    fn image_urls(&self) -> [&str; 3] {
+        [&self.image_url_1x, &self.image_url_2x, &self.image_url_4x]
+    }
+}
+
 // Our simplified badge structure that we'll send to the frontend
 #[derive(Debug, Serialize, Clone)]
 pub struct SimpleBadge {
     pub id: String,
     pub version: String,
+    // Image at the preferred scale, plus all scales for high-DPI screens
     pub image_url: String,
+    pub srcset: String,
     pub title: String,
 }
 
+type BadgeSets = HashMap<String, HashMap<String, BadgeVersion>>; // set_id -> id -> BadgeVersion
+
+#[derive(Debug, Clone)]
+struct RefreshTarget {
+    client_id: String,
+    token: String,
+    broadcaster_id: String,
+}
+
+// One channel's badges and when they were fetched (unix seconds)
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ChannelBadges {
+    badges: BadgeSets,
+    fetched_at: i64,
+}
+
 // Badge cache to store all badges
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct BadgeCache {
-    channel_badges: HashMap<String, HashMap<String, BadgeVersion>>, // set_id -> id -> BadgeVersion
-    global_badges: HashMap<String, HashMap<String, BadgeVersion>>,  // set_id -> id -> BadgeVersion
+    // Channel badges per broadcaster ID, so switching channels doesn't mix them up
+    #[serde(default)]
+    channels: HashMap<String, ChannelBadges>,
+    #[serde(default)]
+    global_badges: BadgeSets,
+    #[serde(default)]
+    global_fetched_at: i64,
+}
+
+fn is_within_ttl(fetched_at: i64) -> bool {
+    let age = chrono::Utc::now().timestamp() - fetched_at;
+    age >= 0 && (age as u64) < BADGE_CACHE_TTL.as_secs()
+}
+
+fn to_badge_sets(badges: Vec<BadgeSet>) -> BadgeSets {
+    badges.into_iter()
+        .map(|badge_set| {
+            let versions = badge_set.versions.into_iter()
+                .map(|version| (version.id.clone(), version))
+                .collect();
+            (badge_set.set_id, versions)
+        })
+        .collect()
 }
 
 impl BadgeCache {
     fn new() -> Self {
         BadgeCache {
-            channel_badges: HashMap::new(),
+            channels: HashMap::new(),
             global_badges: HashMap::new(),
+            global_fetched_at: 0,
         }
     }
 
-    // Add channel badges to the cache
-    fn add_channel_badges(&mut self, badges: Vec<BadgeSet>) {
-        for badge_set in badges {
-            let mut version_map = HashMap::new();
-            for version in badge_set.versions {
-                version_map.insert(version.id.clone(), version);
-            }
-            self.channel_badges.insert(badge_set.set_id, version_map);
-        }
+    // Whether both this broadcaster's badges and the global ones are younger than the TTL
+    fn is_fresh(&self, broadcaster_id: &str) -> bool {
+        let channel_fresh = self.channels.get(broadcaster_id)
+            .map(|channel| is_within_ttl(channel.fetched_at))
+            .unwrap_or(false);
+        channel_fresh && is_within_ttl(self.global_fetched_at)
     }
 
-    // Add global badges to the cache
-    fn add_global_badges(&mut self, badges: Vec<BadgeSet>) {
-        for badge_set in badges {
-            let mut version_map = HashMap::new();
-            for version in badge_set.versions {
-                version_map.insert(version.id.clone(), version);
-            }
-            self.global_badges.insert(badge_set.set_id, version_map);
-        }
+    // All image URLs we emit for a channel, so they can be downloaded ahead of time
+    fn image_urls(&self, broadcaster_id: &str) -> Vec<String> {
+        self.channels.get(broadcaster_id)
+            .into_iter()
+            .flat_map(|channel| channel.badges.values())
+            .chain(self.global_badges.values())
+            .flat_map(|versions| versions.values())
+            .map(|version| image_cache::pick(version.image_urls()).to_string())
+            .collect()
     }
 
-    // Get badge info based on set_id and id
-    fn get_badge_info(&self, set_id: &str, id: &str) -> Option<SimpleBadge> {
-        // Try to find in channel badges first
-        if let Some(versions) = self.channel_badges.get(set_id) {
-            if let Some(badge) = versions.get(id) {
-                return Some(SimpleBadge {
-                    id: set_id.to_string(),
-                    version: id.to_string(),
-                    image_url: badge.image_url_1x.clone(),
-                    title: badge.title.clone(),
-                });
-            }
-        }
-        
-        // If not found, try global badges
-        if let Some(versions) = self.global_badges.get(set_id) {
-            if let Some(badge) = versions.get(id) {
-                return Some(SimpleBadge {
-                    id: set_id.to_string(),
-                    version: id.to_string(),
-                    image_url: badge.image_url_1x.clone(),
-                    title: badge.title.clone(),
-                });
-            }
-        }
-        
-        None
+    // Replace a channel's badges. Sets the streamer removed or changed don't linger.
+    fn set_channel_badges(&mut self, broadcaster_id: &str, badges: Vec<BadgeSet>) {
+        self.channels.insert(broadcaster_id.to_string(), ChannelBadges {
+            badges: to_badge_sets(badges),
+            fetched_at: chrono::Utc::now().timestamp(),
+        });
+    }
+
+    // Replace the global badges
+    fn set_global_badges(&mut self, badges: Vec<BadgeSet>) {
+        self.global_badges = to_badge_sets(badges);
+        self.global_fetched_at = chrono::Utc::now().timestamp();
+    }
+
+    // Get badge info based on set_id and id. Versions we don't know about fall back to
+    // the nearest lower version in the same set, e.g. a 14 month subscriber badge that
+    // the channel never uploaded uses the 12 month one.
+    fn get_badge_info(&self, broadcaster_id: &str, set_id: &str, id: &str, info: &str) -> Option<SimpleBadge> {
+        let channel = self.channels.get(broadcaster_id).map(|channel| &channel.badges);
+        let badge = self.find_version(channel, set_id, id)
+            .or_else(|| self.nearest_version(channel, set_id, id))?;
+
+        let image = image_cache::ScaledImage::new(badge.image_urls());
+
+        Some(SimpleBadge {
+            id: set_id.to_string(),
+            version: id.to_string(),
+            image_url: image.url,
+            srcset: image.srcset,
+            title: enrich_title(set_id, &badge.title, info),
+        })
+    }
+
+    // Exact match, channel badges first since they override global ones
+    fn find_version<'a>(&'a self, channel: Option<&'a BadgeSets>, set_id: &str, id: &str) -> Option<&'a BadgeVersion> {
+        channel.and_then(|sets| sets.get(set_id))
+            .and_then(|versions| versions.get(id))
+            .or_else(|| self.global_badges.get(set_id).and_then(|versions| versions.get(id)))
+    }
+
+    fn nearest_version<'a>(&'a self, channel: Option<&'a BadgeSets>, set_id: &str, id: &str) -> Option<&'a BadgeVersion> {
+        channel.and_then(|sets| sets.get(set_id))
+            .and_then(|versions| nearest_lower_version(versions, id))
+            .or_else(|| self.global_badges.get(set_id).and_then(|versions| nearest_lower_version(versions, id)))
+    }
+}
+
+// Subscriber and bits badges use numeric versions (months or bits). Subscriber tiers 2
+// and 3 are numbered from 2000 and 3000, so prefer a version within the same thousand.
+// None if every version is higher.
+fn nearest_lower_version<'a>(versions: &'a HashMap<String, BadgeVersion>, id: &str) -> Option<&'a BadgeVersion> {
+    let target: u64 = id.parse().ok()?;
+
+    let numeric = versions.iter()
+        .filter_map(|(version_id, badge)| version_id.parse::<u64>().ok().map(|n| (n, badge)))
+        .collect::<Vec<_>>();
+
+    let same_tier = numeric.iter()
+        .filter(|(n, _)| *n <= target && n / 1000 == target / 1000)
+        .max_by_key(|(n, _)| *n);
+    let any_lower = numeric.iter()
+        .filter(|(n, _)| *n <= target)
+        .max_by_key(|(n, _)| *n);
+
+    same_tier.or(any_lower).map(|(_, badge)| *badge)
+}
+
+// Add the badge info from the chat message to the title, e.g. "Subscriber, 14 months"
+fn enrich_title(set_id: &str, title: &str, info: &str) -> String {
+    let info = info.trim();
+    if info.is_empty() {
+        return title.to_string();
+    }
+
+    match set_id {
+        "subscriber" | "founder" => match info.parse::<u32>() {
+            Ok(1) => format!("{}, 1 month", title),
+            Ok(months) => format!("{}, {} months", title, months),
+            Err(_) => format!("{} ({})", title, info),
+        },
+        _ => format!("{} ({})", title, info),
     }
 }
 
-// Function to initialize badges
-pub async fn initialize_badges(client_id: &str, token: &str, broadcaster_id: &str) -> Result<(), String> {
-    let mut success = true;
+// Fetch a broadcaster's channel badges and the global badges, replacing what's cached.
+// If one of the requests fails the previously cached badges for it are kept.
+pub async fn initialize_badges(client_id: &str, token: &str, broadcaster_id: &str) -> AppResult<()> {
+    let mut failure = None;
 
     // Prepare headers for API requests
     let mut headers = HeaderMap::new();
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    headers.insert("Client-Id", HeaderValue::from_str(client_id).map_err(|e| e.to_string())?);
-    headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|e| e.to_string())?);
+    headers.insert("Client-Id", HeaderValue::from_str(client_id).map_err(|e| AppError::config(format!("Invalid client ID: {}", e)))?);
+    headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|e| AppError::auth("twitch", format!("Invalid token: {}", e)))?);
 
     let client = reqwest::Client::new();
     
     // Get channel badges
     let channel_badges = match fetch_channel_badges(&client, &headers, broadcaster_id).await {
-        Ok(badges) => badges,
+        Ok(badges) => Some(badges),
         Err(e) => {
-            println!("Error fetching channel badges: {}", e);
-            success = false;
-            Vec::new()
+            warn!("Error fetching channel badges: {}", e);
+            failure = Some(e);
+            None
         }
     };
     
     // Get global badges
     let global_badges = match fetch_global_badges(&client, &headers).await {
-        Ok(badges) => badges,
+        Ok(badges) => Some(badges),
         Err(e) => {
-            println!("Error fetching global badges: {}", e);
-            success = false;
-            Vec::new()
+            warn!("Error fetching global badges: {}", e);
+            failure = Some(e);
+            None
         }
     };
     
     // Store badges in cache
-    if let Ok(mut cache) = BADGE_CACHE.lock() {
-        cache.add_channel_badges(channel_badges);
-        cache.add_global_badges(global_badges);
+    let (image_urls, json) = if let Ok(mut cache) = BADGE_CACHE.lock() {
+        if let Some(badges) = channel_badges {
+            cache.set_channel_badges(broadcaster_id, badges);
+        }
+        if let Some(badges) = global_badges {
+            cache.set_global_badges(badges);
+        }
+        // Serialized under the lock, written after it's released so chat isn't held up
+        // by the disk
+        (cache.image_urls(broadcaster_id), serde_json::to_string(&*cache))
     } else {
-        return Err("Failed to lock badge cache".to_string());
-    }
+        return Err(AppError::internal("Failed to lock badge cache"));
+    };
+    save_disk_cache(json);
+
+    // Download the images in the background so they're available offline next time
+    tauri::async_runtime::spawn(async move {
+        let mut failed = 0;
+        for url in &image_urls {
+            if image_cache::fetch(&client, url).await.is_err() {
+                failed += 1;
+            }
+        }
+        image_cache::flush_index();
+        info!("Cached {} badge images ({} failed)", image_urls.len() - failed, failed);
+    });
     
-    if success {
-        Ok(())
-    } else {
-        Err("Failed to fetch all badges".to_string())
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+// Fetch badges only if the saved ones are missing, stale or for another broadcaster
+pub async fn refresh_if_stale(client_id: &str, token: &str, broadcaster_id: &str) -> AppResult<()> {
+    let fresh = BADGE_CACHE.lock()
+        .map(|cache| cache.is_fresh(broadcaster_id))
+        .unwrap_or(false);
+
+    if fresh {
+        info!("Using cached badges for broadcaster {}", broadcaster_id);
+        return Ok(());
+    }
+
+    initialize_badges(client_id, token, broadcaster_id).await
+}
+
+// Keep the badges for this broadcaster up to date for as long as the app runs.
+// Calling it again switches the refresh over to the new channel or token.
+pub fn start_background_refresh(client_id: String, token: String, broadcaster_id: String) {
+    if let Ok(mut target) = REFRESH_TARGET.lock() {
+        *target = Some(RefreshTarget { client_id, token, broadcaster_id });
+    }
+
+    BACKGROUND_REFRESH.call_once(|| {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                tokio::time::sleep(REFRESH_CHECK_INTERVAL).await;
+
+                let target = REFRESH_TARGET.lock().ok().and_then(|target| target.clone());
+                let Some(target) = target else {
+                    continue;
+                };
+                if let Err(e) = refresh_if_stale(&target.client_id, &target.token, &target.broadcaster_id).await {
+                    warn!("Background badge refresh failed: {}", e);
+                }
+            }
+        });
+    });
+}
+
+// Fetch a broadcaster's badges now, even if the cached ones are fresh, and make it the
+// channel the background refresh follows. Used when switching channels or after the
+// streamer uploads new badges.
+pub async fn refresh_badges(client_id: String, token: String, broadcaster_id: String) -> AppResult<()> {
+    let result = initialize_badges(&client_id, &token, &broadcaster_id).await;
+    start_background_refresh(client_id, token, broadcaster_id);
+    result
+}
+
+// Load badges saved by a previous run. Called at startup and when the profile changes;
+// badges of the previous profile are dropped and the background refresh waits for the
+// new profile's token.
+pub fn load_disk_cache(dir: PathBuf) {
+    let path = dir.join("badges.json");
+    if let Ok(mut cache_file) = CACHE_FILE.lock() {
+        *cache_file = Some(path.clone());
+    }
+    if let Ok(mut cache) = BADGE_CACHE.lock() {
+        *cache = BadgeCache::new();
+    }
+    if let Ok(mut target) = REFRESH_TARGET.lock() {
+        *target = None;
+    }
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => return,
+    };
+
+    match serde_json::from_str::<BadgeCache>(&contents) {
+        Ok(saved) => {
+            info!("Loaded badges for {} channels and {} global badge sets from disk", 
+                saved.channels.len(), saved.global_badges.len());
+            if let Ok(mut cache) = BADGE_CACHE.lock() {
+                *cache = saved;
+            }
+        },
+        Err(e) => warn!("Ignoring unreadable badge cache {}: {}", path.display(), e),
+    }
+}
+
+fn save_disk_cache(json: serde_json::Result<String>) {
+    let Some(path) = CACHE_FILE.lock().ok().and_then(|path| path.clone()) else {
+        return;
+    };
+
+    let result = json
+        .map_err(|e| e.to_string())
+        .and_then(|json| image_cache::write_atomic(&path, json.as_bytes()));
+
+    if let Err(e) = result {
+        warn!("Failed to save badge cache to {}: {}", path.display(), e);
     }
 }
 
@@ -160,22 +387,24 @@ async fn fetch_channel_badges(
     client: &reqwest::Client,
     headers: &HeaderMap,
     broadcaster_id: &str
-) -> Result<Vec<BadgeSet>, String> {
+) -> AppResult<Vec<BadgeSet>> {
     let url = format!("https://api.twitch.tv/helix/chat/badges?broadcaster_id={}", broadcaster_id);
     
     let response = client.get(&url)
         .headers(headers.clone())
         .send()
         .await
-        .map_err(|e| format!("Network error: {}", e))?;
+        .map_err(|e| AppError::from_request("twitch", e))?;
     
-    if !response.status().is_success() {
-        return Err(format!("API error: Status code {}", response.status()));
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
     }
     
     let badge_response: BadgeResponse = response.json()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
+        .map_err(|e| AppError::from_request("twitch", e))?;
     
     Ok(badge_response.data)
 }
@@ -184,42 +413,38 @@ async fn fetch_channel_badges(
 async fn fetch_global_badges(
     client: &reqwest::Client,
     headers: &HeaderMap,
-) -> Result<Vec<BadgeSet>, String> {
+) -> AppResult<Vec<BadgeSet>> {
     let url = "https://api.twitch.tv/helix/chat/badges/global";
     
     let response = client.get(url)
         .headers(headers.clone())
         .send()
         .await
-        .map_err(|e| format!("Network error: {}", e))?;
+        .map_err(|e| AppError::from_request("twitch", e))?;
     
-    if !response.status().is_success() {
-        return Err(format!("API error: Status code {}", response.status()));
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
     }
     
     let badge_response: BadgeResponse = response.json()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
+        .map_err(|e| AppError::from_request("twitch", e))?;
     
     Ok(badge_response.data)
 }
 
 // Public function to convert incoming badges to SimpleBadges with URLs
-pub fn process_message_badges(badges: &[twitch_eventsub::Badge]) -> Vec<SimpleBadge> {
+pub fn process_message_badges(broadcaster_id: &str, badges: &[twitch_eventsub::Badge]) -> Vec<SimpleBadge> {
     let mut result = Vec::new();
     
     if let Ok(cache) = BADGE_CACHE.lock() {
         for badge in badges {
-            if let Some(badge_info) = cache.get_badge_info(&badge.set_id, &badge.id) {
+            // Badges from sets we have no images for at all are left out rather than
+            // shown as broken images
+            if let Some(badge_info) = cache.get_badge_info(broadcaster_id, &badge.set_id, &badge.id, &badge.info) {
                 result.push(badge_info);
-            } else {
-                // Fallback to use the bare minimum we know about the badge
-                result.push(SimpleBadge {
-                    id: badge.id.clone(),
-                    version: "1".to_string(), // Default to version 1
-                    image_url: format!("https://static-cdn.jtvnw.net/badges/v1/{}/{}/1", badge.id, "1"),
-                    title: badge.set_id.clone(),
-                });
             }
         }
     }
@@ -227,15 +452,135 @@ pub fn process_message_badges(badges: &[twitch_eventsub::Badge]) -> Vec<SimpleBa
     result
 }
 
-// Function to check if badges are initialized
-pub fn ensure_badges_initialized(client_id: String, token: String, broadcaster_id: String) {
-    BADGES_INITIALIZED.call_once(|| {
-        // We need to spawn this as a task since it's async and call_once isn't
-        tauri::async_runtime::spawn(async move {
-            match initialize_badges(&client_id, &token, &broadcaster_id).await {
-                Ok(_) => println!("Successfully initialized badges"),
-                Err(e) => println!("Failed to initialize badges: {}", e),
-            }
-        });
-    });
-} 
\ No newline at end of file
+// Roles YouTube reports on a chat message author
+#[derive(Debug, Clone, Default)]
+pub struct YoutubeRoles {
+    pub is_owner: bool,
+    pub is_moderator: bool,
+    pub is_member: bool,
+    pub is_verified: bool,
+    // Only known on membership messages: the level's name and how long they've been a member
+    pub member_level: Option<String>,
+    pub member_months: Option<u32>,
+}
+
+// YouTube has no badge API, so role badges are drawn locally as small SVGs
+fn youtube_badge_image(background: &str, glyph: &str) -> String {
+    let svg = format!(
+        "<svg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 18 18'>\
+<rect width='18' height='18' rx='3' fill='{}'/>\
+<path d='{}' fill='#fff'/></svg>",
+        background, glyph
+    );
+    // Spaces are escaped too, since srcset entries are separated by whitespace
+    let encoded = svg.replace('#', "%23")
+        .replace('<', "%3C")
+        .replace('>', "%3E")
+        .replace(' ', "%20");
+    format!("data:image/svg+xml,{}", encoded)
+}
+
+fn youtube_badge(id: &str, version: &str, title: String, background: &str, glyph: &str) -> SimpleBadge {
+    let image_url = youtube_badge_image(background, glyph);
+    SimpleBadge {
+        id: id.to_string(),
+        version: version.to_string(),
+        srcset: format!("{} 1x", image_url),
+        image_url,
+        title,
+    }
+}
+
+// Glyphs for the synthesized badges
+const GLYPH_OWNER: &str = "M4 12h10v2H4zM4 11l1-6 2.5 3L9 4l1.5 4L13 5l1 6z";
+const GLYPH_MODERATOR: &str = "M11 3l4 4-7 7-3 1 1-3zM3 15l2-2 1 1-2 2z";
+const GLYPH_MEMBER: &str = "M9 15l-5-5a3 3 0 015-3.5A3 3 0 0114 10z";
+const GLYPH_VERIFIED: &str = "M7.5 12.5L4 9l1.4-1.4 2.1 2.1 5.1-5.1L14 6z";
+
+// Role badges for a YouTube chatter, in the order Twitch shows them: broadcaster,
+// moderator, then subscriber-style badges
+pub fn youtube_badges(roles: &YoutubeRoles) -> Vec<SimpleBadge> {
+    let mut result = Vec::new();
+
+    if roles.is_owner {
+        result.push(youtube_badge("owner", "1", "Owner".to_string(), "#ffd600", GLYPH_OWNER));
+    }
+    if roles.is_moderator {
+        result.push(youtube_badge("moderator", "1", "Moderator".to_string(), "#5e84f1", GLYPH_MODERATOR));
+    }
+    if roles.is_member || roles.member_level.is_some() {
+        let base = match &roles.member_level {
+            Some(level) if !level.trim().is_empty() => format!("Member ({})", level.trim()),
+            _ => "Member".to_string(),
+        };
+        let months = roles.member_months.unwrap_or(0);
+        let info = if months > 0 { months.to_string() } else { String::new() };
+
+        // Same wording as the Twitch subscriber badge, e.g. "Member (Gold), 6 months"
+        let title = enrich_title("subscriber", &base, &info);
+        let version = if months > 0 { months.to_string() } else { "1".to_string() };
+        result.push(youtube_badge("member", &version, title, "#0f9d58", GLYPH_MEMBER));
+    }
+    if roles.is_verified {
+        result.push(youtube_badge("verified", "1", "Verified".to_string(), "#606060", GLYPH_VERIFIED));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(ids: &[&str]) -> HashMap<String, BadgeVersion> {
+        ids.iter()
+            .map(|id| (id.to_string(), BadgeVersion {
+                id: id.to_string(),
+                image_url_1x: String::new(),
+                image_url_2x: String::new(),
+                image_url_4x: String::new(),
+                title: format!("Version {}", id),
+                description: String::new(),
+            }))
+            .collect()
+    }
+
+    fn nearest(ids: &[&str], id: &str) -> Option<String> {
+        nearest_lower_version(&versions(ids), id).map(|badge| badge.id.clone())
+    }
+
+    #[test]
+    fn picks_nearest_lower_version() {
+        let subscriber = ["0", "3", "6", "12", "24"];
+
+        assert_eq!(nearest(&subscriber, "14").as_deref(), Some("12"));
+        assert_eq!(nearest(&subscriber, "6").as_deref(), Some("6"));
+        assert_eq!(nearest(&subscriber, "100").as_deref(), Some("24"));
+    }
+
+    #[test]
+    fn prefers_the_same_subscriber_tier() {
+        let subscriber = ["0", "12", "2000", "2003", "3000"];
+
+        assert_eq!(nearest(&subscriber, "2014").as_deref(), Some("2003"));
+        assert_eq!(nearest(&subscriber, "3006").as_deref(), Some("3000"));
+        // Nothing in tier 2 yet, so the highest lower version
+        assert_eq!(nearest(&["0", "12"], "2002").as_deref(), Some("12"));
+    }
+
+    #[test]
+    fn never_picks_a_higher_version() {
+        assert_eq!(nearest(&["3", "6"], "1"), None);
+        assert_eq!(nearest(&["3", "6"], "not-a-number"), None);
+        assert_eq!(nearest(&["premium"], "5"), None);
+    }
+
+    #[test]
+    fn adds_badge_info_to_titles() {
+        assert_eq!(enrich_title("subscriber", "Subscriber", "1"), "Subscriber, 1 month");
+        assert_eq!(enrich_title("founder", "Founder", "14"), "Founder, 14 months");
+        assert_eq!(enrich_title("subscriber", "Subscriber", "abc"), "Subscriber (abc)");
+        assert_eq!(enrich_title("predictions", "Predicted Blue", "blue-1"), "Predicted Blue (blue-1)");
+        assert_eq!(enrich_title("moderator", "Moderator", "  "), "Moderator");
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::{AppError, AppResult};
+
+// Chat messages are funnelled through here instead of going straight to `emit_all`.
+// At normal rates they pass through untouched; under load they're coalesced into
+// `chat-batch` events so the webview IPC isn't flooded with one call per message.
+static STATE: Lazy<Mutex<BatcherState>> = Lazy::new(|| Mutex::new(BatcherState::new(BatchConfig::default())));
+static APP: OnceCell<AppHandle> = OnceCell::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    // Above this many messages per second, messages are batched instead of emitted one by one
+    pub batch_threshold_per_sec: u32,
+    // How often queued messages are flushed to the frontend
+    pub flush_interval_ms: u64,
+    // "Slow chat": only show `max_per_second` messages and queue the rest
+    pub slow_mode: bool,
+    pub max_per_second: u32,
+    // Oldest queued messages are dropped once the queue is this long
+    pub max_queue: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            batch_threshold_per_sec: 20,
+            flush_interval_ms: 100,
+            slow_mode: false,
+            max_per_second: 10,
+            max_queue: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct QueuedEvent {
+    event: &'static str,
+    payload: serde_json::Value,
+}
+
+// Payload of the `chat-batch` event
+#[derive(Debug, Clone, Serialize)]
+struct ChatBatch {
+    messages: Vec<QueuedEvent>,
+    // Messages thrown away because the queue overflowed since the last batch
+    dropped: u64,
+    // Messages still waiting in the queue (slow chat mode)
+    deferred: usize,
+}
+
+enum Flush {
+    Single(QueuedEvent),
+    Batch(ChatBatch),
+}
+
+struct BatcherState {
+    config: BatchConfig,
+    queue: VecDeque<QueuedEvent>,
+    // Arrival times over the last second, used to detect load
+    recent: VecDeque<Instant>,
+    dropped: u64,
+    // Fractional messages slow mode is allowed to release
+    release_budget: f64,
+}
+
+impl BatcherState {
+    fn new(config: BatchConfig) -> Self {
+        BatcherState {
+            config,
+            queue: VecDeque::new(),
+            recent: VecDeque::new(),
+            dropped: 0,
+            release_budget: 0.0,
+        }
+    }
+
+    // Forget arrivals older than a second
+    fn prune(&mut self, now: Instant) {
+        while let Some(oldest) = self.recent.front() {
+            if now.duration_since(*oldest) > Duration::from_secs(1) {
+                self.recent.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // Record a message and queue it, or hand it back to be emitted straight away when
+    // we're below the threshold with nothing waiting, to keep latency down
+    fn arrive(&mut self, event: QueuedEvent, now: Instant) -> Option<QueuedEvent> {
+        self.recent.push_back(now);
+        self.prune(now);
+
+        if !self.config.slow_mode && !self.under_load() && self.queue.is_empty() {
+            return Some(event);
+        }
+        self.push(event);
+        None
+    }
+
+    fn under_load(&self) -> bool {
+        self.recent.len() > self.config.batch_threshold_per_sec as usize
+    }
+
+    fn push(&mut self, event: QueuedEvent) {
+        self.queue.push_back(event);
+        while self.queue.len() > self.config.max_queue.max(1) {
+            self.queue.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    // Take the messages that should be shown this flush
+    fn take_ready(&mut self, interval: Duration) -> Vec<QueuedEvent> {
+        let count = if self.config.slow_mode {
+            let per_second = self.config.max_per_second as f64;
+            // Cap the budget so a quiet period doesn't allow a burst afterwards
+            self.release_budget = (self.release_budget + per_second * interval.as_secs_f64()).min(per_second.max(1.0));
+            let count = (self.release_budget.floor() as usize).min(self.queue.len());
+            self.release_budget -= count as f64;
+            count
+        } else {
+            self.queue.len()
+        };
+
+        self.queue.drain(..count).collect()
+    }
+
+    // What to send to the frontend this flush, if anything
+    fn flush(&mut self, now: Instant, interval: Duration) -> Option<Flush> {
+        // Load is judged on the last second even when no message has arrived since, so
+        // the end of a flood is noticed straight away
+        self.prune(now);
+        let messages = self.take_ready(interval);
+        let dropped = std::mem::take(&mut self.dropped);
+        let deferred = self.queue.len();
+
+        if messages.is_empty() && dropped == 0 {
+            return None;
+        }
+
+        // A lone message with nothing held back doesn't need to be wrapped
+        if messages.len() == 1 && dropped == 0 && deferred == 0 && !self.under_load() {
+            return messages.into_iter().next().map(Flush::Single);
+        }
+        Some(Flush::Batch(ChatBatch { messages, dropped, deferred }))
+    }
+}
+
+// Start the flush loop. Called once from setup.
+pub fn start(app: AppHandle) {
+    if APP.set(app.clone()).is_err() {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let interval = match STATE.lock() {
+                Ok(state) => Duration::from_millis(state.config.flush_interval_ms.max(10)),
+                Err(_) => Duration::from_millis(100),
+            };
+            tokio::time::sleep(interval).await;
+            flush(&app, interval);
+        }
+    });
+}
+
+// Queue or emit a chat event depending on the current load
+pub fn emit(app: &AppHandle, event: &'static str, payload: serde_json::Value) {
+    let queued = QueuedEvent { event, payload };
+
+    // Until the flush loop runs, everything is emitted straight away
+    let queued = match STATE.lock() {
+        Ok(mut state) if APP.get().is_some() => state.arrive(queued, Instant::now()),
+        _ => Some(queued),
+    };
+
+    if let Some(queued) = queued {
+        let _ = app.emit_all(queued.event, queued.payload);
+    }
+}
+
+fn flush(app: &AppHandle, interval: Duration) {
+    let flush = match STATE.lock() {
+        Ok(mut state) => state.flush(Instant::now(), interval),
+        Err(_) => return,
+    };
+
+    match flush {
+        Some(Flush::Single(message)) => {
+            let _ = app.emit_all(message.event, message.payload);
+        },
+        Some(Flush::Batch(batch)) => {
+            let _ = app.emit_all("chat-batch", batch);
+        },
+        None => {},
+    }
+}
+
+pub fn config() -> BatchConfig {
+    STATE.lock().map(|state| state.config.clone()).unwrap_or_default()
+}
+
+pub fn validate(config: &BatchConfig) -> AppResult<()> {
+    if config.slow_mode && config.max_per_second == 0 {
+        return Err(AppError::config("Slow chat needs at least one message per second"));
+    }
+    Ok(())
+}
+
+pub fn set_config(config: BatchConfig) -> AppResult<()> {
+    validate(&config)?;
+
+    let mut state = STATE.lock().map_err(|e| AppError::internal(format!("Failed to lock chat batcher: {}", e)))?;
+    state.config = config;
+    state.release_budget = 0.0;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn message(n: usize) -> QueuedEvent {
+        QueuedEvent { event: "twitch-chat", payload: serde_json::json!(n) }
+    }
+
+    // Messages arriving at `now`; returns how many went out directly
+    fn arrive(state: &mut BatcherState, count: usize, now: Instant) -> usize {
+        (0..count).filter_map(|n| state.arrive(message(n), now)).count()
+    }
+
+    fn batch(flush: Option<Flush>) -> ChatBatch {
+        match flush {
+            Some(Flush::Batch(batch)) => batch,
+            Some(Flush::Single(_)) => panic!("expected a batch, got a single message"),
+            None => panic!("expected a batch, got nothing"),
+        }
+    }
+
+    #[test]
+    fn batches_above_threshold() {
+        let mut state = BatcherState::new(BatchConfig { batch_threshold_per_sec: 5, ..BatchConfig::default() });
+        let now = Instant::now();
+
+        assert_eq!(arrive(&mut state, 8, now), 5);
+        let batch = batch(state.flush(now, INTERVAL));
+        assert_eq!((batch.messages.len(), batch.dropped, batch.deferred), (3, 0, 0));
+        assert!(state.flush(now, INTERVAL).is_none());
+    }
+
+    #[test]
+    fn drops_oldest_when_queue_overflows() {
+        let mut state = BatcherState::new(BatchConfig {
+            batch_threshold_per_sec: 0,
+            max_queue: 5,
+            ..BatchConfig::default()
+        });
+        let now = Instant::now();
+
+        assert_eq!(arrive(&mut state, 9, now), 0);
+        let batch = batch(state.flush(now, INTERVAL));
+        assert_eq!((batch.messages.len(), batch.dropped, batch.deferred), (5, 4, 0));
+        // The newest ones are kept
+        assert_eq!(batch.messages[0].payload, serde_json::json!(4));
+    }
+
+    #[test]
+    fn slow_mode_defers_the_rest() {
+        let mut state = BatcherState::new(BatchConfig {
+            slow_mode: true,
+            max_per_second: 10,
+            ..BatchConfig::default()
+        });
+        let now = Instant::now();
+
+        assert_eq!(arrive(&mut state, 5, now), 0);
+        let batch = batch(state.flush(now, INTERVAL));
+        assert_eq!((batch.messages.len(), batch.dropped, batch.deferred), (1, 0, 4));
+    }
+
+    #[test]
+    fn slow_mode_budget_is_capped_and_accumulates() {
+        let mut state = BatcherState::new(BatchConfig {
+            slow_mode: true,
+            max_per_second: 10,
+            ..BatchConfig::default()
+        });
+        arrive(&mut state, 30, Instant::now());
+
+        // A long quiet spell allows at most a second's worth
+        assert_eq!(state.take_ready(Duration::from_secs(5)).len(), 10);
+        assert_eq!(state.release_budget, 0.0);
+
+        // Half a message per 50ms flush, so every other flush releases one
+        assert_eq!(state.take_ready(Duration::from_millis(50)).len(), 0);
+        assert_eq!(state.take_ready(Duration::from_millis(50)).len(), 1);
+        assert_eq!(state.queue.len(), 19);
+    }
+
+    #[test]
+    fn load_ends_without_new_messages() {
+        let mut state = BatcherState::new(BatchConfig { batch_threshold_per_sec: 2, ..BatchConfig::default() });
+        let start = Instant::now();
+
+        assert_eq!(arrive(&mut state, 3, start), 2);
+        assert!(state.under_load());
+
+        // The flood was over a second ago, so the queued message goes out on its own
+        assert!(matches!(state.flush(start + Duration::from_millis(1500), INTERVAL), Some(Flush::Single(_))));
+        assert!(!state.under_load());
+    }
+}
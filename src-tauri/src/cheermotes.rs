@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+use crate::image_cache::{self, ScaledImage};
+
+// Cheermotes are the images bits are cheered with, e.g. "Cheer100". Each prefix has tiers
+// by amount with their own image and color. Like badges, a channel can add its own on top
+// of the global ones, so they're fetched per broadcaster. They're saved per profile next
+// to the badge cache, so they render straight away on the next start.
+const CHEERMOTES_URL: &str = "https://api.twitch.tv/helix/bits/cheermotes";
+
+// How old a channel's cheermotes can get before they're fetched again
+const CHEERMOTES_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+static CHEERMOTE_CACHE: Lazy<Mutex<HashMap<String, ChannelCheermotes>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static CACHE_FILE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Deserialize)]
+struct CheermoteResponse {
+    data: Vec<CheermoteSet>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CheermoteSet {
+    prefix: String,
+    tiers: Vec<CheermoteTier>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CheermoteTier {
+    min_bits: u32,
+    color: String,
+    images: CheermoteThemes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CheermoteThemes {
+    dark: CheermoteFormats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CheermoteFormats {
+    animated: HashMap<String, String>,
+}
+
+impl CheermoteTier {
+    // The 1x, 2x and 4x animated images for dark backgrounds
+    fn image_urls(&self) -> [&str; 3] {
+        let url = |scale: &str| self.images.dark.animated.get(scale).map(String::as_str).unwrap_or("");
+        [url("1"), url("2"), url("4")]
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ChannelCheermotes {
+    // Lowercase prefix -> tiers sorted by min_bits
+    prefixes: HashMap<String, Vec<CheermoteTier>>,
+    fetched_at: i64,
+}
+
+impl ChannelCheermotes {
+    fn is_fresh(&self) -> bool {
+        let age = chrono::Utc::now().timestamp() - self.fetched_at;
+        age >= 0 && (age as u64) < CHEERMOTES_TTL.as_secs()
+    }
+}
+
+// A cheermote in a message, resolved to the image for its amount
+#[derive(Debug, Clone)]
+pub struct ResolvedCheermote {
+    pub prefix: String,
+    pub bits: u32,
+    pub color: String,
+    pub image: ScaledImage,
+}
+
+// Fetch a broadcaster's cheermotes unless the cached ones are still fresh
+pub async fn refresh_if_stale(client_id: &str, token: &str, broadcaster_id: &str) -> AppResult<()> {
+    let fresh = CHEERMOTE_CACHE.lock()
+        .map(|cache| cache.get(broadcaster_id).map(|channel| channel.is_fresh()).unwrap_or(false))
+        .unwrap_or(false);
+    if fresh {
+        return Ok(());
+    }
+
+    let client = reqwest::Client::new();
+    let response = client.get(CHEERMOTES_URL)
+        .query(&[("broadcaster_id", broadcaster_id)])
+        .bearer_auth(token)
+        .header("Client-Id", client_id)
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+    let cheermotes: CheermoteResponse = response.json().await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    let prefixes = cheermotes.data.into_iter()
+        .map(|set| {
+            let mut tiers = set.tiers;
+            tiers.sort_by_key(|tier| tier.min_bits);
+            (set.prefix.to_lowercase(), tiers)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let image_urls = prefixes.values()
+        .flatten()
+        .map(|tier| image_cache::pick(tier.image_urls()).to_string())
+        .filter(|url| !url.is_empty())
+        .collect::<Vec<_>>();
+
+    info!("Loaded {} cheermotes for broadcaster {}", prefixes.len(), broadcaster_id);
+    let json = match CHEERMOTE_CACHE.lock() {
+        Ok(mut cache) => {
+            cache.insert(broadcaster_id.to_string(), ChannelCheermotes {
+                prefixes,
+                fetched_at: chrono::Utc::now().timestamp(),
+            });
+            Some(serde_json::to_string(&*cache))
+        },
+        Err(_) => None,
+    };
+    // Written after the lock is released, so a slow disk doesn't hold up chat
+    if let Some(json) = json {
+        save_disk_cache(json);
+    }
+
+    // Download the images in the background, the same way badge images are
+    tauri::async_runtime::spawn(async move {
+        let mut failed = 0;
+        for url in &image_urls {
+            if image_cache::fetch(&client, url).await.is_err() {
+                failed += 1;
+            }
+        }
+        image_cache::flush_index();
+        if failed > 0 {
+            warn!("Failed to cache {} of {} cheermote images", failed, image_urls.len());
+        }
+    });
+
+    Ok(())
+}
+
+// Load cheermotes saved by a previous run. Called at startup and when the profile
+// changes; the previous profile's cheermotes are dropped.
+pub fn load_disk_cache(dir: PathBuf) {
+    let path = dir.join("cheermotes.json");
+    if let Ok(mut cache_file) = CACHE_FILE.lock() {
+        *cache_file = Some(path.clone());
+    }
+    if let Ok(mut cache) = CHEERMOTE_CACHE.lock() {
+        cache.clear();
+    }
+
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return;
+    };
+
+    match serde_json::from_str::<HashMap<String, ChannelCheermotes>>(&contents) {
+        Ok(saved) => {
+            info!("Loaded cheermotes for {} channels from disk", saved.len());
+            if let Ok(mut cache) = CHEERMOTE_CACHE.lock() {
+                *cache = saved;
+            }
+        },
+        Err(e) => warn!("Ignoring unreadable cheermote cache {}: {}", path.display(), e),
+    }
+}
+
+fn save_disk_cache(json: serde_json::Result<String>) {
+    let Some(path) = CACHE_FILE.lock().ok().and_then(|path| path.clone()) else {
+        return;
+    };
+
+    let result = json
+        .map_err(|e| e.to_string())
+        .and_then(|json| image_cache::write_atomic(&path, json.as_bytes()));
+
+    if let Err(e) = result {
+        warn!("Failed to save cheermote cache to {}: {}", path.display(), e);
+    }
+}
+
+// Resolve a word like "Cheer100" or "Kappa5000" to its cheermote image. Words that
+// aren't a known prefix followed by an amount give None.
+pub fn resolve(broadcaster_id: &str, word: &str) -> Option<ResolvedCheermote> {
+    let split = word.find(|c: char| c.is_ascii_digit())?;
+    let (prefix, amount) = word.split_at(split);
+    let bits: u32 = amount.parse().ok().filter(|bits| *bits > 0)?;
+
+    let cache = CHEERMOTE_CACHE.lock().ok()?;
+    let tiers = cache.get(broadcaster_id)?.prefixes.get(&prefix.to_lowercase())?;
+    // The highest tier the amount reaches
+    let tier = tiers.iter().rev().find(|tier| tier.min_bits <= bits)?;
+
+    Some(ResolvedCheermote {
+        prefix: prefix.to_string(),
+        bits,
+        color: tier.color.clone(),
+        image: ScaledImage::new(tier.image_urls()),
+    })
+}
@@ -0,0 +1,206 @@
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+
+// Username color settings, shared by both platforms
+static COLOR_SETTINGS: Lazy<Mutex<ColorSettings>> = Lazy::new(|| Mutex::new(ColorSettings::default()));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorSettings {
+    // Background the chat is drawn on, used for the contrast check
+    pub background: String,
+    // Adjust colors (including ones chosen on Twitch) that are hard to read on the background
+    pub correct_contrast: bool,
+    // WCAG contrast ratio to aim for; 4.5 is the AA level for normal text
+    pub min_contrast: f64,
+}
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        ColorSettings {
+            background: "#1e1e1e".to_string(),
+            correct_contrast: false,
+            min_contrast: 4.5,
+        }
+    }
+}
+
+pub fn settings() -> ColorSettings {
+    COLOR_SETTINGS.lock().map(|settings| settings.clone()).unwrap_or_default()
+}
+
+pub fn validate(settings: &ColorSettings) -> AppResult<()> {
+    if parse_hex(&settings.background).is_none() {
+        return Err(AppError::config(format!("Invalid background color: {}", settings.background)));
+    }
+    if !(1.0..=21.0).contains(&settings.min_contrast) {
+        return Err(AppError::config("Minimum contrast must be between 1 and 21"));
+    }
+    Ok(())
+}
+
+pub fn set_settings(settings: ColorSettings) -> AppResult<()> {
+    validate(&settings)?;
+
+    let mut current = COLOR_SETTINGS.lock().map_err(|e| AppError::internal(format!("Failed to lock color settings: {}", e)))?;
+    *current = settings;
+    Ok(())
+}
+
+// Color for a chatter's name. Uses the color they picked on the platform if there is one,
+// otherwise a color derived from their user ID so they keep it on every message.
+pub fn user_color(platform: &str, user_id: &str, provided: Option<&str>) -> String {
+    let color = match provided.filter(|c| parse_hex(c).is_some()) {
+        Some(color) => color.to_string(),
+        None => deterministic_color(platform, user_id),
+    };
+
+    let settings = settings();
+    if settings.correct_contrast {
+        ensure_contrast(&color, &settings.background, settings.min_contrast)
+    } else {
+        color
+    }
+}
+
+// The same (platform, user ID) always gives the same color. Hue, saturation and
+// lightness come straight from the hash, so no RNG whose output could change between
+// crate versions is involved.
+pub fn deterministic_color(platform: &str, user_id: &str) -> String {
+    let hash = fnv1a(&format!("{}:{}", platform, user_id));
+    let hue = (hash % 360) as f64;
+    // Saturated, mid-lightness colors so names stay readable and distinct
+    let saturation = 0.6 + ((hash >> 16) % 31) as f64 / 100.0;
+    let lightness = 0.45 + ((hash >> 32) % 21) as f64 / 100.0;
+    to_hex(hsl_to_rgb(hue, saturation, lightness))
+}
+
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> [u8; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |c: f64| ((c + m) * 255.0).round() as u8;
+    [channel(r), channel(g), channel(b)]
+}
+
+// FNV-1a, used instead of DefaultHasher so colors stay the same between Rust versions
+fn fnv1a(input: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in input.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn parse_hex(color: &str) -> Option<[u8; 3]> {
+    let hex = color.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn to_hex(rgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+// WCAG relative luminance
+fn luminance(rgb: [u8; 3]) -> f64 {
+    let linear = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    0.2126 * linear(rgb[0]) + 0.7152 * linear(rgb[1]) + 0.0722 * linear(rgb[2])
+}
+
+fn contrast_ratio(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (la, lb) = (luminance(a), luminance(b));
+    let (lighter, darker) = if la > lb { (la, lb) } else { (lb, la) };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+// Lighten (on dark backgrounds) or darken (on light ones) a color until it reaches
+// the minimum contrast, keeping its hue
+pub fn ensure_contrast(color: &str, background: &str, min_contrast: f64) -> String {
+    let (Some(rgb), Some(bg)) = (parse_hex(color), parse_hex(background)) else {
+        return color.to_string();
+    };
+
+    if contrast_ratio(rgb, bg) >= min_contrast {
+        return color.to_string();
+    }
+
+    let target = if luminance(bg) < 0.5 { 255.0 } else { 0.0 };
+    let mut best = rgb;
+    for step in 1..=20 {
+        let amount = step as f64 / 20.0;
+        let mix = |c: u8| (c as f64 + (target - c as f64) * amount).round() as u8;
+        best = [mix(rgb[0]), mix(rgb[1]), mix(rgb[2])];
+        if contrast_ratio(best, bg) >= min_contrast {
+            break;
+        }
+    }
+
+    to_hex(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_user_gets_same_color() {
+        let color = deterministic_color("twitch", "12345");
+
+        assert_eq!(color, deterministic_color("twitch", "12345"));
+        assert!(parse_hex(&color).is_some());
+        assert_ne!(color, deterministic_color("youtube", "12345"));
+        assert_ne!(color, deterministic_color("twitch", "12346"));
+    }
+
+    #[test]
+    fn converts_hsl() {
+        assert_eq!(hsl_to_rgb(0.0, 1.0, 0.5), [255, 0, 0]);
+        assert_eq!(hsl_to_rgb(120.0, 1.0, 0.5), [0, 255, 0]);
+        assert_eq!(hsl_to_rgb(240.0, 1.0, 0.5), [0, 0, 255]);
+        assert_eq!(hsl_to_rgb(0.0, 0.0, 1.0), [255, 255, 255]);
+    }
+
+    #[test]
+    fn contrast_correction_reaches_target() {
+        let contrast = |color: &str, background: &str| contrast_ratio(parse_hex(color).unwrap(), parse_hex(background).unwrap());
+
+        for (color, background) in [("#0000ff", "#1e1e1e"), ("#8b0000", "#000000"), ("#ffff00", "#ffffff"), ("#00ff7f", "#f0f0f0")] {
+            for target in [3.0, 4.5, 7.0] {
+                let corrected = ensure_contrast(color, background, target);
+                assert!(
+                    contrast(&corrected, background) >= target,
+                    "{} on {} corrected to {} misses {}", color, background, corrected, target
+                );
+            }
+        }
+
+        // Colors that already pass are left alone
+        assert_eq!(ensure_contrast("#ffffff", "#1e1e1e", 4.5), "#ffffff");
+    }
+}
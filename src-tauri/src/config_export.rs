@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::credentials::{self, Credentials};
+use crate::error::{AppError, AppResult};
+use crate::profiles;
+use crate::secrets;
+use crate::settings::{self, Settings};
+
+// Moving a setup between machines or backing it up: the active profile's settings and,
+// if asked for, its credentials in one JSON file. Credentials in an export are plaintext,
+// so they're left out unless requested.
+const EXPORT_FORMAT: &str = "streamchatbox-config";
+const EXPORT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct ConfigExport {
+    format: String,
+    version: u32,
+    exported_at: String,
+    profile: String,
+    // Kept as raw JSON so an import runs the settings migrations
+    settings: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    credentials: Option<Credentials>,
+}
+
+// What an import changed
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    pub profile: String,
+    pub credentials: bool,
+    // Settings in effect before the import, for working out what has to restart
+    #[serde(skip)]
+    pub previous: Settings,
+}
+
+// Write the active profile's settings, and credentials if `include_secrets`, to `path`
+pub fn export(path: &Path, include_secrets: bool) -> AppResult<PathBuf> {
+    let credentials = if include_secrets {
+        if credentials::is_locked() {
+            return Err(secrets::locked_error());
+        }
+        Some(credentials::get())
+    } else {
+        None
+    };
+
+    let export = ConfigExport {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        profile: profiles::active(),
+        settings: serde_json::to_value(settings::get())
+            .map_err(|e| AppError::internal(format!("Failed to serialize settings: {}", e)))?,
+        credentials,
+    };
+    let contents = serde_json::to_string_pretty(&export)
+        .map_err(|e| AppError::internal(format!("Failed to serialize config: {}", e)))?;
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|e| AppError::config(format!("Failed to create {}: {}", dir.display(), e)))?;
+    }
+    fs::write(path, contents)
+        .map_err(|e| AppError::config(format!("Failed to write {}: {}", path.display(), e)))?;
+    if include_secrets {
+        secrets::restrict_permissions(path);
+        warn!("Exported config to {} including credentials as plaintext", path.display());
+    } else {
+        info!("Exported config to {}", path.display());
+    }
+    Ok(path.to_path_buf())
+}
+
+// Load an export into the active profile. Credentials are only replaced if the export
+// has them, and go in first; if the settings then fail, the old credentials are put back.
+// The profile keeps its own token files.
+pub fn import(path: &Path) -> AppResult<ImportResult> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| AppError::config(format!("Failed to read {}: {}", path.display(), e)))?;
+    let export: ConfigExport = serde_json::from_str(&contents)
+        .map_err(|e| AppError::config(format!("{} is not a config export: {}", path.display(), e)))?;
+
+    if export.format != EXPORT_FORMAT {
+        return Err(AppError::config(format!("{} is not a config export", path.display())));
+    }
+    if export.version > EXPORT_VERSION {
+        return Err(AppError::config("Config was exported by a newer version"));
+    }
+
+    let mut settings = settings::parse(export.settings)?;
+    // Token files are per profile, so the export's would point this profile at another
+    // profile's tokens
+    let current = settings::get().twitch;
+    settings.twitch.token_file = current.token_file;
+    settings.twitch.refresh_token_file = current.refresh_token_file;
+    if export.credentials.is_some() && credentials::is_locked() {
+        return Err(secrets::locked_error());
+    }
+
+    let has_credentials = export.credentials.is_some();
+    let previous_credentials = credentials::get();
+    if let Some(imported) = export.credentials {
+        credentials::update(|credentials| *credentials = imported)?;
+    }
+
+    let previous = match settings::replace(settings) {
+        Ok(previous) => previous,
+        Err(e) => {
+            if has_credentials {
+                if let Err(e) = credentials::update(|credentials| *credentials = previous_credentials) {
+                    warn!("Failed to restore the previous credentials: {}", e);
+                }
+            }
+            return Err(e);
+        },
+    };
+
+    info!("Imported config from {} (exported from profile {})", path.display(), export.profile);
+    Ok(ImportResult {
+        profile: export.profile,
+        credentials: has_credentials,
+        previous,
+    })
+}
@@ -0,0 +1,613 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use twitch_eventsub::{TokenAccess, TwitchKeys};
+
+use crate::error::{AppError, AppResult};
+use crate::logging;
+use crate::secrets::{self, KeySource};
+use crate::settings;
+
+// API keys and tokens for both platforms. Everything that needs a credential reads it
+// from here; the store is saved encrypted (see `secrets`) as `credentials.json` in the
+// app config dir.
+//
+// Older versions spread these over several files: the settings window wrote
+// `.secrets.env` to the config dir, the listeners read `.secrets.env` from the working
+// directory, and Twitch tokens lived in `.user_token.env`/`.refresh_token.env`. Those
+// are imported once on startup.
+const CREDENTIALS_FILE: &str = "credentials.json";
+const LEGACY_SECRETS_FILE: &str = ".secrets.env";
+
+static CREDENTIALS: Lazy<Mutex<Credentials>> = Lazy::new(|| Mutex::new(Credentials::default()));
+// The store of the active profile
+static STORE_FILE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+// Set while a passphrase protected store hasn't been unlocked yet
+static LOCKED: AtomicBool = AtomicBool::new(false);
+// Whether keys from `.secrets.env` files and the environment belong to this profile
+static IMPORT_LEGACY: AtomicBool = AtomicBool::new(true);
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Credentials {
+    pub twitch_client_id: String,
+    pub twitch_client_secret: String,
+    pub twitch_broadcaster_id: String,
+    pub twitch_user_token: String,
+    pub twitch_refresh_token: String,
+    pub youtube_channel_id: String,
+    pub youtube_api_key: String,
+}
+
+impl Credentials {
+    // Keys for the Twitch listener. The stored user token is passed along so the library
+    // doesn't go looking for token files.
+    pub fn twitch_keys(&self) -> AppResult<TwitchKeys> {
+        let client_id = required(&self.twitch_client_id, "Twitch client ID")?;
+        let client_secret = required(&self.twitch_client_secret, "Twitch client secret")?;
+        let broadcaster_id = required(&self.twitch_broadcaster_id, "Twitch broadcaster ID")?;
+
+        Ok(TwitchKeys {
+            authorisation_code: None,
+            access_token: non_empty(&self.twitch_user_token).map(TokenAccess::User),
+            refresh_token: non_empty(&self.twitch_refresh_token),
+            client_id,
+            client_secret,
+            broadcaster_account_id: broadcaster_id.clone(),
+            sender_account_id: Some(broadcaster_id),
+        })
+    }
+
+    // Client ID, broadcaster ID and user token for Helix requests
+    pub fn twitch_api(&self) -> AppResult<(String, String, String)> {
+        let client_id = required(&self.twitch_client_id, "Twitch client ID")?;
+        let broadcaster_id = required(&self.twitch_broadcaster_id, "Twitch broadcaster ID")?;
+        let token = non_empty(&self.twitch_user_token)
+            .ok_or_else(|| AppError::auth("twitch", "No Twitch user token yet, start the Twitch listener to log in"))?;
+
+        Ok((client_id, broadcaster_id, token))
+    }
+
+    // Channel ID and API key for the YouTube listener
+    pub fn youtube(&self) -> AppResult<(String, String)> {
+        Ok((
+            required(&self.youtube_channel_id, "YouTube channel ID")?,
+            required(&self.youtube_api_key, "YouTube API key")?,
+        ))
+    }
+
+    // The keys shown in the API keys window, under the names it has always used
+    pub fn api_keys(&self) -> serde_json::Value {
+        serde_json::json!({
+            "TWITCH_CLIENT_ID": self.twitch_client_id,
+            "TWITCH_CLIENT_SECRET": self.twitch_client_secret,
+            "TWITCH_BROADCASTER_ID": self.twitch_broadcaster_id,
+            "YOUTUBE_CHANNEL_ID": self.youtube_channel_id,
+            "YOUTUBE_API_KEY": self.youtube_api_key,
+        })
+    }
+
+    fn register_secrets(&self) {
+        for secret in [&self.twitch_client_secret, &self.twitch_user_token, &self.twitch_refresh_token, &self.youtube_api_key] {
+            logging::register_secret(secret);
+        }
+    }
+
+    // Fill fields that are still empty from an env style key/value map. Returns whether
+    // anything was taken.
+    fn fill_from(&mut self, values: &HashMap<String, String>) -> bool {
+        let fields: [(&mut String, &[&str]); 7] = [
+            (&mut self.twitch_client_id, &["TWITCH_CLIENT_ID"]),
+            (&mut self.twitch_client_secret, &["TWITCH_CLIENT_SECRET"]),
+            // Badge code used to read TWITCH_CHANNEL_ID for the same value
+            (&mut self.twitch_broadcaster_id, &["TWITCH_BROADCASTER_ID", "TWITCH_CHANNEL_ID"]),
+            (&mut self.twitch_user_token, &["TWITCH_USER_ACCESS_TOKEN", "TWITCH_USER_TOKEN"]),
+            (&mut self.twitch_refresh_token, &["TWITCH_USER_REFRESH_TOKEN"]),
+            (&mut self.youtube_channel_id, &["YOUTUBE_CHANNEL_ID"]),
+            (&mut self.youtube_api_key, &["YOUTUBE_API_KEY"]),
+        ];
+
+        let mut changed = false;
+        for (field, names) in fields {
+            if !field.is_empty() {
+                continue;
+            }
+            if let Some(value) = names.iter().find_map(|name| values.get(*name).and_then(|v| non_empty(v))) {
+                *field = value;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn required(value: &str, name: &str) -> AppResult<String> {
+    if is_locked() {
+        return Err(secrets::locked_error());
+    }
+    non_empty(value).ok_or_else(|| AppError::config(format!("{} is not set, add it in the API keys window", name)))
+}
+
+// Load the store in `config_dir` and import anything the old files still have. Called
+// from setup and again whenever the profile changes. `import_legacy` is only set for the
+// profile that inherits the keys of older versions.
+//
+// A store that can't be read is left as it is and stays locked, so nothing overwrites
+// it; `unlock` tries again. The error is returned for the caller to show.
+pub fn init(config_dir: Option<PathBuf>, import_legacy: bool) -> AppResult<()> {
+    if let Ok(mut credentials) = CREDENTIALS.lock() {
+        *credentials = Credentials::default();
+    }
+    LOCKED.store(false, Ordering::SeqCst);
+    IMPORT_LEGACY.store(import_legacy, Ordering::SeqCst);
+    secrets::forget_key();
+    if let Ok(mut store_file) = STORE_FILE.lock() {
+        *store_file = config_dir.as_ref().map(|dir| dir.join(CREDENTIALS_FILE));
+    }
+
+    let Some(dir) = config_dir else {
+        warn!("No config directory, credentials won't be saved");
+        migrate(None);
+        return Ok(());
+    };
+
+    let path = dir.join(CREDENTIALS_FILE);
+
+    let mut needs_save = false;
+    if let Ok(contents) = fs::read_to_string(&path) {
+        if secrets::key_source(&contents) == Some(KeySource::Passphrase) {
+            // Nothing can be read or imported until the passphrase is entered
+            LOCKED.store(true, Ordering::SeqCst);
+            info!("Credentials are protected by a passphrase and stay locked until it's entered");
+            return Ok(());
+        }
+
+        match read_store(&contents, &dir) {
+            Ok(saved) => {
+                if let Ok(mut credentials) = CREDENTIALS.lock() {
+                    *credentials = saved;
+                }
+                // Written by a version that didn't encrypt the store yet
+                needs_save = !secrets::is_encrypted(&contents);
+            },
+            Err(e) => {
+                LOCKED.store(true, Ordering::SeqCst);
+                return Err(AppError::auth(
+                    "credentials",
+                    format!("{}. {} was left as it is, unlock the credentials to try again", e, path.display()),
+                ));
+            },
+        }
+    }
+
+    let imported = migrate(Some(&dir));
+    if needs_save && !imported {
+        match save() {
+            Ok(_) => info!("Encrypted the credentials store"),
+            Err(e) => warn!("Failed to encrypt the credentials store: {}", e),
+        }
+    }
+
+    get().register_secrets();
+    warn_about_plaintext_files();
+    Ok(())
+}
+
+// Decrypt a store that isn't protected by a passphrase
+fn read_store(contents: &str, dir: &Path) -> AppResult<Credentials> {
+    if secrets::is_encrypted(contents) {
+        secrets::decrypt(contents, dir).and_then(|plaintext| parse(&plaintext))
+    } else {
+        parse(contents.as_bytes())
+    }
+}
+
+fn parse(plaintext: &[u8]) -> AppResult<Credentials> {
+    serde_json::from_slice(plaintext)
+        .map_err(|e| AppError::config(format!("Unreadable credentials: {}", e)))
+}
+
+// Import values the store doesn't have yet. Stored values always win, so this only
+// matters the first time or for keys that were never saved. Returns whether the store
+// was saved.
+fn migrate(config_dir: Option<&Path>) -> bool {
+    let import_legacy = IMPORT_LEGACY.load(Ordering::SeqCst);
+    let mut token_files = vec![twitch_token_files()];
+    if import_legacy {
+        // Before profiles, the token files were in the working directory
+        token_files.push(settings::get().twitch.token_files(Path::new("")));
+    }
+    let sources = legacy_sources(config_dir, Path::new(""), &token_files, import_legacy, std::env::vars().collect());
+
+    let changed = match CREDENTIALS.lock() {
+        Ok(mut credentials) => import(&mut credentials, &sources),
+        Err(_) => false,
+    };
+    if !changed {
+        return false;
+    }
+
+    if let Err(e) = save() {
+        warn!("{}", e);
+        return false;
+    }
+    remove_imported_files(config_dir, &token_files);
+    true
+}
+
+// Where older versions kept credentials, most recent first: the token files, the
+// profile's `.secrets.env`, and for the profile that inherits the old keys, the
+// `.secrets.env` in `working_dir` and the environment. `.example.env` is only a template
+// and is never read.
+fn legacy_sources(
+    config_dir: Option<&Path>,
+    working_dir: &Path,
+    token_files: &[[PathBuf; 2]],
+    import_legacy: bool,
+    env: HashMap<String, String>,
+) -> Vec<(PathBuf, HashMap<String, String>)> {
+    let mut sources = token_files.iter()
+        .map(|files| (files[0].clone(), read_token_files(files)))
+        .collect::<Vec<_>>();
+    if let Some(dir) = config_dir {
+        let path = dir.join(LEGACY_SECRETS_FILE);
+        sources.push((path.clone(), read_env_file(&path)));
+    }
+    if import_legacy {
+        let path = working_dir.join(LEGACY_SECRETS_FILE);
+        sources.push((path.clone(), read_env_file(&path)));
+        sources.push((PathBuf::from("environment"), env));
+    }
+    sources
+}
+
+// Fill empty fields from `sources`, earlier ones first. Returns whether anything was taken.
+fn import(credentials: &mut Credentials, sources: &[(PathBuf, HashMap<String, String>)]) -> bool {
+    let mut changed = false;
+    for (source, values) in sources {
+        if credentials.fill_from(values) {
+            info!("Imported credentials from {}", source.display());
+            changed = true;
+        }
+    }
+    changed
+}
+
+// These were written by the app itself, so they can go once the store has them. A
+// `.secrets.env` in the working directory is the user's; see `remove_plaintext_files`.
+fn remove_imported_files(config_dir: Option<&Path>, token_files: &[[PathBuf; 2]]) {
+    for file in token_files.iter().flatten() {
+        remove_file(file);
+    }
+    if let Some(dir) = config_dir {
+        remove_file(&dir.join(LEGACY_SECRETS_FILE));
+    }
+}
+
+// twitch_eventsub writes tokens it generates or refreshes to these files in the profile's
+// config dir. They're picked up into the store and removed again.
+pub fn twitch_token_files() -> [PathBuf; 2] {
+    settings::get().twitch.token_files(&store_dir().unwrap_or_default())
+}
+
+fn read_token_files([token_file, refresh_file]: &[PathBuf; 2]) -> HashMap<String, String> {
+    HashMap::from([
+        ("TWITCH_USER_ACCESS_TOKEN".to_string(), fs::read_to_string(token_file).unwrap_or_default()),
+        ("TWITCH_USER_REFRESH_TOKEN".to_string(), fs::read_to_string(refresh_file).unwrap_or_default()),
+    ])
+}
+
+fn read_env_file(path: &Path) -> HashMap<String, String> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+
+    contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().trim_matches('"').to_string()))
+        .collect()
+}
+
+// Old plaintext files that still hold credentials
+pub fn plaintext_files() -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::from(LEGACY_SECRETS_FILE)];
+    if let Some(dir) = store_dir() {
+        candidates.push(dir.join(LEGACY_SECRETS_FILE));
+    }
+
+    let mut files = candidates.into_iter()
+        .filter(|path| Credentials::default().fill_from(&read_env_file(path)))
+        .collect::<Vec<_>>();
+
+    files.extend(twitch_token_files().into_iter().filter(|path| path.exists()));
+    files
+}
+
+fn warn_about_plaintext_files() {
+    for path in plaintext_files() {
+        warn!("{} still holds credentials as plaintext; they have been imported, so it can be removed", path.display());
+    }
+}
+
+// Delete the old plaintext files once their contents are safely in the store
+pub fn remove_plaintext_files() -> AppResult<Vec<String>> {
+    if is_locked() {
+        return Err(secrets::locked_error());
+    }
+
+    let files = plaintext_files();
+    // Make sure everything they hold is in the store first
+    migrate(store_dir().as_deref());
+    save()?;
+
+    Ok(files.iter()
+        .filter(|path| remove_file(path))
+        .map(|path| path.display().to_string())
+        .collect())
+}
+
+fn remove_file(path: &Path) -> bool {
+    if !path.exists() {
+        return false;
+    }
+    match fs::remove_file(path) {
+        Ok(_) => {
+            info!("Removed {}", path.display());
+            true
+        },
+        Err(e) => {
+            warn!("Failed to remove {}: {}", path.display(), e);
+            false
+        },
+    }
+}
+
+fn store_file() -> Option<PathBuf> {
+    STORE_FILE.lock().ok()?.clone()
+}
+
+fn store_dir() -> Option<PathBuf> {
+    store_file()?.parent().map(Path::to_path_buf)
+}
+
+pub fn is_locked() -> bool {
+    LOCKED.load(Ordering::SeqCst)
+}
+
+// Decrypt a passphrase protected store, or try again to read one that failed to load.
+// The passphrase is ignored for stores that don't use one.
+pub fn unlock(passphrase: &str) -> AppResult<()> {
+    if !is_locked() {
+        return Ok(());
+    }
+    let path = store_file()
+        .ok_or_else(|| AppError::config("Could not find config directory"))?;
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| AppError::config(format!("Failed to read {}: {}", path.display(), e)))?;
+
+    let saved = if secrets::key_source(&contents) == Some(KeySource::Passphrase) {
+        parse(&secrets::unlock(&contents, passphrase)?)?
+    } else {
+        let dir = path.parent()
+            .ok_or_else(|| AppError::config("Could not find config directory"))?;
+        read_store(&contents, dir)?
+    };
+    if let Ok(mut credentials) = CREDENTIALS.lock() {
+        *credentials = saved;
+    }
+    LOCKED.store(false, Ordering::SeqCst);
+    info!("Unlocked credentials");
+
+    migrate(path.parent());
+    get().register_secrets();
+    warn_about_plaintext_files();
+    Ok(())
+}
+
+// Protect the store with a passphrase, or remove the passphrase with None
+pub fn set_passphrase(passphrase: Option<&str>) -> AppResult<()> {
+    if is_locked() {
+        return Err(secrets::locked_error());
+    }
+    let dir = store_dir()
+        .ok_or_else(|| AppError::config("Could not find config directory"))?;
+
+    secrets::set_passphrase(passphrase, &dir)?;
+    save()?;
+    info!("Credentials are now protected by {:?}", secrets::current_source());
+    Ok(())
+}
+
+// Shown in the settings so the user knows how their keys are kept
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialsStatus {
+    pub locked: bool,
+    // Null until something has been saved
+    pub key_source: Option<KeySource>,
+    pub plaintext_files: Vec<String>,
+}
+
+pub fn status() -> CredentialsStatus {
+    let key_source = secrets::current_source().or_else(|| {
+        let contents = fs::read_to_string(store_file()?).ok()?;
+        secrets::key_source(&contents)
+    });
+
+    CredentialsStatus {
+        locked: is_locked(),
+        key_source,
+        plaintext_files: plaintext_files().iter().map(|path| path.display().to_string()).collect(),
+    }
+}
+
+pub fn get() -> Credentials {
+    CREDENTIALS.lock().map(|credentials| credentials.clone()).unwrap_or_default()
+}
+
+// Change the stored credentials and save them
+pub fn update(change: impl FnOnce(&mut Credentials)) -> AppResult<()> {
+    // A locked store would be overwritten with an empty one
+    if is_locked() {
+        return Err(secrets::locked_error());
+    }
+    {
+        let mut credentials = CREDENTIALS.lock()
+            .map_err(|e| AppError::internal(format!("Failed to lock credentials: {}", e)))?;
+        change(&mut credentials);
+        credentials.register_secrets();
+    }
+    save()
+}
+
+fn save() -> AppResult<()> {
+    if is_locked() {
+        return Err(secrets::locked_error());
+    }
+    let path = store_file()
+        .ok_or_else(|| AppError::config("Could not find config directory"))?;
+    let dir = path.parent()
+        .ok_or_else(|| AppError::config("Could not find config directory"))?;
+
+    let json = CREDENTIALS.lock()
+        .map_err(|e| AppError::internal(format!("Failed to lock credentials: {}", e)))
+        .and_then(|credentials| serde_json::to_vec(&*credentials)
+            .map_err(|e| AppError::internal(format!("Failed to serialize credentials: {}", e))))?;
+    let encrypted = secrets::encrypt(&json, dir)?;
+
+    fs::create_dir_all(dir)
+        .map_err(|e| AppError::config(format!("Failed to create config directory: {}", e)))?;
+    fs::write(&path, encrypted)
+        .map_err(|e| AppError::config(format!("Failed to save credentials to {}: {}", path.display(), e)))?;
+    secrets::restrict_permissions(&path);
+    Ok(())
+}
+
+// Move tokens twitch_eventsub has just written to its token files into the store.
+// Called after the listener starts and periodically while it runs, since the library
+// refreshes expired tokens on its own. Returns whether there was a new token.
+pub fn absorb_twitch_token_files() -> bool {
+    let [token_file, refresh_file] = twitch_token_files();
+    let token = fs::read_to_string(token_file).ok().and_then(|t| non_empty(&t));
+    let refresh = fs::read_to_string(refresh_file).ok().and_then(|t| non_empty(&t));
+    if token.is_none() && refresh.is_none() {
+        return false;
+    }
+
+    let result = update(|credentials| {
+        if let Some(token) = token {
+            credentials.twitch_user_token = token;
+        }
+        if let Some(refresh) = refresh {
+            credentials.twitch_refresh_token = refresh;
+        }
+    });
+
+    match result {
+        Ok(_) => {
+            info!("Saved new Twitch tokens");
+            remove_token_files();
+        },
+        // Still in memory; the files stay so the next attempt can pick them up
+        Err(e) => warn!("Failed to save new Twitch tokens: {}", e),
+    }
+    true
+}
+
+fn remove_token_files() {
+    for file in twitch_token_files() {
+        if file.exists() {
+            if let Err(e) = fs::remove_file(&file) {
+                warn!("Failed to remove {}: {}", file.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory under the system temp dir; removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("streamchatbox-credentials-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn token_files(dir: &Path) -> [PathBuf; 2] {
+        [dir.join(".user_token.env"), dir.join(".refresh_token.env")]
+    }
+
+    #[test]
+    fn imports_each_source_and_removes_its_own_files() {
+        let config = TempDir::new("migrate-config");
+        let working = TempDir::new("migrate-working");
+        let tokens = token_files(&config.0);
+        fs::write(&tokens[0], "user-token-from-file").unwrap();
+        // Before profiles, tokens were written to the working directory
+        let old_tokens = token_files(&working.0);
+        fs::write(&old_tokens[0], "old-user-token").unwrap();
+        fs::write(&old_tokens[1], "refresh-token-from-old-file").unwrap();
+        fs::write(config.0.join(LEGACY_SECRETS_FILE), "TWITCH_CLIENT_ID=config-client\nTWITCH_USER_ACCESS_TOKEN=stale").unwrap();
+        fs::write(working.0.join(LEGACY_SECRETS_FILE), "TWITCH_CLIENT_ID=working-client\nTWITCH_CLIENT_SECRET=working-secret").unwrap();
+        fs::write(working.0.join(".example.env"), "TWITCH_BROADCASTER_ID=your_broadcaster_id").unwrap();
+        let env = HashMap::from([("YOUTUBE_API_KEY".to_string(), "env-key".to_string())]);
+
+        let token_files = [tokens.clone(), old_tokens.clone()];
+        let sources = legacy_sources(Some(&config.0), &working.0, &token_files, true, env);
+        let mut credentials = Credentials::default();
+        assert!(import(&mut credentials, &sources));
+
+        assert_eq!(credentials.twitch_user_token, "user-token-from-file");
+        assert_eq!(credentials.twitch_refresh_token, "refresh-token-from-old-file");
+        assert_eq!(credentials.twitch_client_id, "config-client");
+        assert_eq!(credentials.twitch_client_secret, "working-secret");
+        assert_eq!(credentials.youtube_api_key, "env-key");
+        // `.example.env` is a template, not credentials
+        assert_eq!(credentials.twitch_broadcaster_id, "");
+
+        remove_imported_files(Some(&config.0), &token_files);
+        assert!(token_files.iter().flatten().all(|file| !file.exists()));
+        assert!(!config.0.join(LEGACY_SECRETS_FILE).exists());
+        // The user's own file is left for `remove_plaintext_files`
+        assert!(working.0.join(LEGACY_SECRETS_FILE).exists());
+    }
+
+    #[test]
+    fn other_profiles_skip_the_working_dir_and_environment() {
+        let config = TempDir::new("skip-config");
+        let working = TempDir::new("skip-working");
+        fs::write(working.0.join(LEGACY_SECRETS_FILE), "TWITCH_CLIENT_ID=working-client").unwrap();
+        let env = HashMap::from([("YOUTUBE_API_KEY".to_string(), "env-key".to_string())]);
+
+        let sources = legacy_sources(Some(&config.0), &working.0, &[token_files(&config.0)], false, env);
+        let mut credentials = Credentials::default();
+        assert!(!import(&mut credentials, &sources));
+        assert_eq!(credentials.twitch_client_id, "");
+        assert_eq!(credentials.youtube_api_key, "");
+    }
+}
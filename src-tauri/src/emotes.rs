@@ -0,0 +1,110 @@
+use serde::Serialize;
+use twitch_eventsub::{FragmentType, Message};
+
+use crate::cheermotes;
+use crate::image_cache::ScaledImage;
+
+// A piece of a chat message: plain text, an emote image or a cheermote
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MessageFragment {
+    Text {
+        text: String,
+    },
+    Emote {
+        // The emote code, used as alt text
+        text: String,
+        id: String,
+        url: String,
+        srcset: String,
+    },
+    Cheermote {
+        // The word as typed, e.g. "Cheer100"
+        text: String,
+        prefix: String,
+        bits: u32,
+        color: String,
+        url: String,
+        srcset: String,
+    },
+}
+
+// A cheermote fragment, or plain text if the word isn't a known cheermote
+fn cheermote_fragment(broadcaster_id: &str, text: &str) -> MessageFragment {
+    match cheermotes::resolve(broadcaster_id, text) {
+        Some(cheermote) => MessageFragment::Cheermote {
+            text: text.to_string(),
+            prefix: cheermote.prefix,
+            bits: cheermote.bits,
+            color: cheermote.color,
+            url: cheermote.image.url,
+            srcset: cheermote.image.srcset,
+        },
+        None => MessageFragment::Text {
+            text: text.to_string(),
+        },
+    }
+}
+
+// Twitch emote image in the three sizes the CDN offers (28, 56 and 112px)
+fn emote_image(id: &str, animated: bool) -> ScaledImage {
+    let format = if animated { "animated" } else { "static" };
+    let url = |scale: &str| format!("https://static-cdn.jtvnw.net/emoticons/v2/{}/{}/dark/{}", id, format, scale);
+
+    let (small, medium, large) = (url("1.0"), url("2.0"), url("3.0"));
+    ScaledImage::new([&small, &medium, &large])
+}
+
+// Split a Twitch chat message into text, emote and cheermote fragments
+pub fn twitch_fragments(broadcaster_id: &str, message: &Message) -> Vec<MessageFragment> {
+    message.fragments.iter().map(|fragment| {
+        match (&fragment.kind, &fragment.emote) {
+            (FragmentType::Emote, Some(emote)) => {
+                let animated = emote.format.as_ref()
+                    .map(|formats| formats.iter().any(|f| f == "animated"))
+                    .unwrap_or(false);
+                let image = emote_image(&emote.id, animated);
+
+                MessageFragment::Emote {
+                    text: fragment.text.clone(),
+                    id: emote.id.clone(),
+                    url: image.url,
+                    srcset: image.srcset,
+                }
+            },
+            (FragmentType::CheerMote, _) => cheermote_fragment(broadcaster_id, &fragment.text),
+            _ => MessageFragment::Text {
+                text: fragment.text.clone(),
+            },
+        }
+    }).collect()
+}
+
+// Split the plain text of a channel cheer, which comes without fragments, into text and
+// cheermote fragments
+pub fn cheer_fragments(broadcaster_id: &str, text: &str) -> Vec<MessageFragment> {
+    let mut fragments: Vec<MessageFragment> = Vec::new();
+    for word in text.split_inclusive(' ') {
+        let trimmed = word.trim_end();
+        let fragment = cheermote_fragment(broadcaster_id, trimmed);
+        let spacing = &word[trimmed.len()..];
+
+        match (fragments.last_mut(), fragment) {
+            // Runs of plain words are kept together as one text fragment
+            (Some(MessageFragment::Text { text }), MessageFragment::Text { text: word }) => {
+                text.push_str(&word);
+                text.push_str(spacing);
+            },
+            (_, MessageFragment::Text { text: word }) => {
+                fragments.push(MessageFragment::Text { text: format!("{}{}", word, spacing) });
+            },
+            (_, cheermote) => {
+                fragments.push(cheermote);
+                if !spacing.is_empty() {
+                    fragments.push(MessageFragment::Text { text: spacing.to_string() });
+                }
+            },
+        }
+    }
+    fragments
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+// Crate-wide error type. Commands return it to the frontend as a structured object,
+// e.g. `{ "kind": "quota", "platform": "youtube", "message": "..." }`, and the
+// listeners emit it as an `error` event so the UI can show why chat stopped.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AppError {
+    // Missing, expired or rejected credentials
+    Auth { platform: String, message: String },
+    // The request never got an answer: offline, DNS, timeouts
+    Network { message: String },
+    // The platform is rate limiting us or the API quota is used up
+    Quota { platform: String, message: String },
+    // Missing or invalid settings and files
+    Config { message: String },
+    // The platform answered with an error we don't have a better category for
+    PlatformApi { platform: String, status: Option<u16>, message: String },
+    // Bugs and everything else
+    Internal { message: String },
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn auth(platform: &str, message: impl Into<String>) -> Self {
+        AppError::Auth { platform: platform.to_string(), message: message.into() }
+    }
+
+    pub fn network(message: impl Into<String>) -> Self {
+        AppError::Network { message: message.into() }
+    }
+
+    pub fn quota(platform: &str, message: impl Into<String>) -> Self {
+        AppError::Quota { platform: platform.to_string(), message: message.into() }
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        AppError::Config { message: message.into() }
+    }
+
+    pub fn platform_api(platform: &str, status: Option<u16>, message: impl Into<String>) -> Self {
+        AppError::PlatformApi { platform: platform.to_string(), status, message: message.into() }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        AppError::Internal { message: message.into() }
+    }
+
+    // Classify a failed HTTP response. YouTube reports quota problems as 403 with a
+    // `quotaExceeded`/`rateLimitExceeded` reason in the body.
+    pub fn from_status(platform: &str, status: reqwest::StatusCode, body: &str) -> Self {
+        let message = format!("API error: Status code {}", status);
+        let quota_reason = body.contains("quotaExceeded") || body.contains("rateLimitExceeded");
+
+        match status.as_u16() {
+            429 => AppError::quota(platform, message),
+            403 if quota_reason => AppError::quota(platform, message),
+            401 | 403 => AppError::auth(platform, message),
+            code => AppError::platform_api(platform, Some(code), message),
+        }
+    }
+
+    // Build an error from a reqwest failure that happened before we got a response.
+    // The URL is dropped from the message since YouTube requests carry the API key in it,
+    // and errors end up on screen.
+    pub fn from_request(platform: &str, error: reqwest::Error) -> Self {
+        let error = error.without_url();
+        match error.status() {
+            Some(status) => AppError::from_status(platform, status, ""),
+            None if error.is_decode() => AppError::platform_api(platform, None, format!("Failed to parse response: {}", error)),
+            None => AppError::network(format!("Network error: {}", error)),
+        }
+    }
+
+    // twitch_eventsub doesn't export its error types, so they're classified by the
+    // variant name in their Debug output
+    pub fn from_twitch(error: impl fmt::Debug) -> Self {
+        let message = format!("{:?}", error);
+        let variant = message.split(['(', ' ', '{']).next().unwrap_or_default();
+
+        match variant {
+            "TokenMissingScope"
+            | "TokenMissingSubscription"
+            | "TokenMissingUnimplementedSubscription"
+            | "AuthorisationError"
+            | "NoAccessTokenProvided"
+            | "InvalidAccessToken"
+            | "InvalidOauthToken"
+            | "TokenRequiresRefreshing" => AppError::auth("twitch", message),
+            "WebsocketCreationFailed" | "CurlFailed" => AppError::network(message),
+            "MaximumWebsocketTransmissionsExceeded" => AppError::quota("twitch", message),
+            "NoSubscriptionsRequested" | "ClientIdNotFound" | "ClientSecretNotFound" => AppError::config(message),
+            _ => AppError::platform_api("twitch", None, message),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Auth { platform, message } => write!(f, "{} authentication error: {}", platform, message),
+            AppError::Network { message } => write!(f, "{}", message),
+            AppError::Quota { platform, message } => write!(f, "{} quota exceeded: {}", platform, message),
+            AppError::Config { message } => write!(f, "Configuration error: {}", message),
+            AppError::PlatformApi { platform, message, .. } => write!(f, "{} API error: {}", platform, message),
+            AppError::Internal { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+// Payload of the `error` event
+#[derive(Debug, Clone, Serialize)]
+struct ErrorEvent<'a> {
+    // What was running when it failed, e.g. "youtube-listener"
+    source: &'a str,
+    #[serde(flatten)]
+    error: &'a AppError,
+}
+
+// Log an error and tell the frontend about it
+pub fn emit(app: &AppHandle, source: &str, error: &AppError) {
+    log::error!("[{}] {}", source, error);
+    let _ = app.emit_all("error", ErrorEvent { source, error });
+}
@@ -0,0 +1,243 @@
+use std::sync::Mutex;
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use twitch_eventsub::Subscription;
+
+use crate::error::{AppError, AppResult};
+
+// Which EventSub subscriptions the Twitch listener asked for and what became of them.
+// Subscriptions the token lacks scopes for are rejected before connecting; the rest are
+// confirmed with Twitch once the library reports it has subscribed.
+const SUBSCRIPTIONS_URL: &str = "https://api.twitch.tv/helix/eventsub/subscriptions";
+
+static STATUS: Lazy<Mutex<Vec<SubscriptionStatus>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+// Subscriptions the listener turns into events, with a name for the settings. Only add
+// one here together with its handler in the listener loop.
+const AVAILABLE: &[(Subscription, &str)] = &[
+    (Subscription::ChatMessage, "Chat messages"),
+    (Subscription::ChannelFollow, "Follows"),
+    (Subscription::ChannelRaid, "Raids"),
+    (Subscription::ChannelNewSubscription, "Subscriptions"),
+    (Subscription::ChannelResubscription, "Resubscriptions"),
+    (Subscription::ChannelGiftSubscription, "Gift subscriptions"),
+    (Subscription::ChannelCheer, "Cheers"),
+    (Subscription::ChannelPointsCustomRewardRedeem, "Channel point redemptions"),
+];
+
+// The library joins the scopes a subscription needs with '+'. For these pairs Twitch takes
+// either one, since the manage scope includes reading.
+const SCOPE_ALTERNATIVES: &[(&str, &str)] = &[
+    ("channel:read:redemptions", "channel:manage:redemptions"),
+    ("channel:read:polls", "channel:manage:polls"),
+    ("channel:read:predictions", "channel:manage:predictions"),
+    ("moderator:read:shoutouts", "moderator:manage:shoutouts"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailableSubscription {
+    // EventSub type, as used in the settings
+    pub kind: String,
+    pub name: String,
+    pub required_scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionState {
+    // Requested, waiting for Twitch
+    Pending,
+    Active,
+    Rejected,
+}
+
+// One entry of the `twitch-subscriptions` event
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionStatus {
+    pub kind: String,
+    pub state: SubscriptionState,
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SubscriptionsResponse {
+    data: Vec<EnabledSubscription>,
+}
+
+#[derive(Deserialize)]
+struct EnabledSubscription {
+    #[serde(rename = "type")]
+    kind: String,
+    transport: Transport,
+}
+
+#[derive(Deserialize)]
+struct Transport {
+    method: String,
+}
+
+pub fn available() -> Vec<AvailableSubscription> {
+    AVAILABLE.iter()
+        .map(|(subscription, name)| AvailableSubscription {
+            kind: subscription.tag(),
+            name: name.to_string(),
+            required_scopes: required_scopes(subscription),
+        })
+        .collect()
+}
+
+pub fn is_available(tag: &str) -> bool {
+    AVAILABLE.iter().any(|(subscription, _)| subscription.tag() == tag)
+}
+
+// Whether a token with `token_scopes` satisfies `scope`, directly or through its
+// read/manage counterpart
+pub fn has_scope(token_scopes: &[String], scope: &str) -> bool {
+    let alternative = SCOPE_ALTERNATIVES.iter().find_map(|(read, manage)| match scope {
+        s if s == *read => Some(*manage),
+        s if s == *manage => Some(*read),
+        _ => None,
+    });
+    token_scopes.iter().any(|token_scope| token_scope == scope || Some(token_scope.as_str()) == alternative)
+}
+
+fn required_scopes(subscription: &Subscription) -> Vec<String> {
+    subscription.required_scope()
+        .split('+')
+        .filter(|scope| !scope.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Split the requested subscriptions into those the token's scopes allow and those it
+// doesn't. Without `token_scopes` (no token yet, or it couldn't be checked) everything
+// is let through and left to Twitch.
+pub fn check(requested: &[Subscription], token_scopes: Option<&[String]>) -> (Vec<Subscription>, Vec<SubscriptionStatus>) {
+    let mut accepted = Vec::new();
+    let mut statuses = Vec::new();
+
+    for subscription in requested {
+        let missing = token_scopes
+            .map(|scopes| required_scopes(subscription).into_iter()
+                .filter(|scope| !has_scope(scopes, scope))
+                .collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        if missing.is_empty() {
+            accepted.push(subscription.clone());
+            statuses.push(SubscriptionStatus {
+                kind: subscription.tag(),
+                state: SubscriptionState::Pending,
+                reason: None,
+            });
+        } else {
+            warn!("Not subscribing to {}, the token is missing {}", subscription.tag(), missing.join(", "));
+            statuses.push(SubscriptionStatus {
+                kind: subscription.tag(),
+                state: SubscriptionState::Rejected,
+                reason: Some(format!("Token is missing {}; log in again to allow it", missing.join(", "))),
+            });
+        }
+    }
+
+    (accepted, statuses)
+}
+
+pub fn statuses() -> Vec<SubscriptionStatus> {
+    STATUS.lock().map(|status| status.clone()).unwrap_or_default()
+}
+
+// Remember the statuses and tell the frontend
+pub fn publish(app: &AppHandle, statuses: Vec<SubscriptionStatus>) {
+    if let Ok(mut status) = STATUS.lock() {
+        *status = statuses.clone();
+    }
+    let _ = app.emit_all("twitch-subscriptions", statuses);
+}
+
+// Ask Twitch which websocket subscriptions are enabled and settle the pending ones.
+// Twitch disables websocket subscriptions when their session ends, so enabled ones
+// belong to the current connection.
+pub async fn confirm(app: &AppHandle, client: &Client, client_id: &str, token: &str) -> AppResult<()> {
+    let response = client.get(SUBSCRIPTIONS_URL)
+        .query(&[("status", "enabled")])
+        .bearer_auth(token)
+        .header("Client-Id", client_id)
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+    let enabled: SubscriptionsResponse = response.json().await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    let mut statuses = statuses();
+    for status in statuses.iter_mut().filter(|status| status.state == SubscriptionState::Pending) {
+        let active = enabled.data.iter()
+            .any(|subscription| subscription.kind == status.kind && subscription.transport.method == "websocket");
+        if active {
+            status.state = SubscriptionState::Active;
+        } else {
+            status.state = SubscriptionState::Rejected;
+            status.reason = Some("Twitch refused the subscription".to_string());
+        }
+    }
+
+    let active = statuses.iter().filter(|status| status.state == SubscriptionState::Active).count();
+    info!("{} of {} Twitch subscriptions are active", active, statuses.len());
+    publish(app, statuses);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(scopes: &[&str]) -> Vec<String> {
+        scopes.iter().map(|scope| scope.to_string()).collect()
+    }
+
+    #[test]
+    fn manage_scope_stands_in_for_read_scope() {
+        let token = scopes(&["channel:manage:redemptions"]);
+        let (accepted, statuses) = check(&[Subscription::ChannelPointsCustomRewardRedeem], Some(&token));
+
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(statuses[0].state, SubscriptionState::Pending);
+    }
+
+    #[test]
+    fn read_scope_stands_in_for_manage_scope() {
+        let token = scopes(&["channel:read:redemptions"]);
+        let (accepted, _) = check(&[Subscription::ChannelPointsCustomRewardRedeem], Some(&token));
+
+        assert_eq!(accepted.len(), 1);
+    }
+
+    #[test]
+    fn scopes_that_are_not_alternatives_are_all_required() {
+        // Chat needs reading and writing, the app sends messages too
+        let token = scopes(&["user:read:chat"]);
+        let (accepted, statuses) = check(&[Subscription::ChatMessage], Some(&token));
+
+        assert!(accepted.is_empty());
+        assert_eq!(statuses[0].state, SubscriptionState::Rejected);
+        assert!(statuses[0].reason.as_deref().unwrap_or_default().contains("user:write:chat"));
+    }
+
+    #[test]
+    fn unrelated_scopes_do_not_count() {
+        let token = scopes(&["channel:manage:polls"]);
+        let (accepted, _) = check(&[Subscription::ChannelPointsCustomRewardRedeem], Some(&token));
+
+        assert!(accepted.is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_native_tls::{native_tls, TlsConnector};
+
+// Hype Chat (paid, pinned chat messages). EventSub chat messages don't say whether a
+// message was paid for; Twitch only puts that in the IRC tags. So while the Twitch
+// listener runs, this reads the channel's chat over IRC as an anonymous, read-only user
+// and matches paid messages to the EventSub ones by message ID.
+const IRC_HOST: &str = "irc.chat.twitch.tv";
+const IRC_TLS_PORT: u16 = 6697;
+// Anonymous IRC logins use a "justinfan" nick and need no password or token
+const ANONYMOUS_NICK: &str = "justinfan48213";
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+// How many message IDs are remembered for matching up the two connections
+const MAX_TRACKED: usize = 500;
+
+static TRACKER: Lazy<Mutex<Tracker>> = Lazy::new(|| Mutex::new(Tracker::default()));
+
+// Payload attached to chat messages as `hype_chat`, and of the `twitch-hype-chat` event
+// when the IRC side is the slower one
+#[derive(Debug, Clone, Serialize)]
+pub struct HypeChat {
+    pub message_id: String,
+    // In the currency's main unit, e.g. 5.0 for $5
+    pub amount: f64,
+    pub currency: String,
+    // "ONE" to "TEN", which sets how long the message stays pinned
+    pub level: String,
+}
+
+// Either connection can see a message first: paid messages IRC saw before EventSub, and
+// EventSub message IDs that were already sent to the frontend
+#[derive(Default)]
+struct Tracker {
+    pending: HashMap<String, HypeChat>,
+    pending_order: VecDeque<String>,
+    emitted: VecDeque<String>,
+}
+
+// Hype Chat info for a chat message that's about to be emitted, if IRC already saw it
+pub fn take(message_id: &str) -> Option<HypeChat> {
+    let mut tracker = TRACKER.lock().ok()?;
+    tracker.emitted.push_back(message_id.to_string());
+    if tracker.emitted.len() > MAX_TRACKED {
+        tracker.emitted.pop_front();
+    }
+    tracker.pending.remove(message_id)
+}
+
+fn record(app: &AppHandle, hype_chat: HypeChat) {
+    info!("Hype Chat: {} {} (level {})", hype_chat.amount, hype_chat.currency, hype_chat.level);
+    let Ok(mut tracker) = TRACKER.lock() else {
+        return;
+    };
+
+    if tracker.emitted.contains(&hype_chat.message_id) {
+        drop(tracker);
+        let _ = app.emit_all("twitch-hype-chat", hype_chat);
+        return;
+    }
+
+    tracker.pending_order.push_back(hype_chat.message_id.clone());
+    tracker.pending.insert(hype_chat.message_id.clone(), hype_chat);
+    if tracker.pending_order.len() > MAX_TRACKED {
+        if let Some(oldest) = tracker.pending_order.pop_front() {
+            tracker.pending.remove(&oldest);
+        }
+    }
+}
+
+// Stops watching when dropped, so it ends together with the listener that holds it
+pub struct Watcher(JoinHandle<()>);
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// Watch `channel` (its login name) for Hype Chats
+pub fn start(app: &AppHandle, channel: String) -> Watcher {
+    Watcher(tauri::async_runtime::spawn(run(app.clone(), channel)))
+}
+
+async fn run(app: AppHandle, channel: String) {
+    loop {
+        if let Err(e) = watch(&app, &channel).await {
+            warn!("Hype Chat connection lost: {}", e);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn watch(app: &AppHandle, channel: &str) -> std::io::Result<()> {
+    let stream = TcpStream::connect((IRC_HOST, IRC_TLS_PORT)).await?;
+    let connector = native_tls::TlsConnector::new().map_err(std::io::Error::other)?;
+    let stream = TlsConnector::from(connector)
+        .connect(IRC_HOST, stream)
+        .await
+        .map_err(std::io::Error::other)?;
+    let (reader, mut writer) = tokio::io::split(stream);
+
+    let login = format!(
+        "CAP REQ :twitch.tv/tags\r\nNICK {}\r\nJOIN #{}\r\n",
+        ANONYMOUS_NICK,
+        channel.to_lowercase()
+    );
+    writer.write_all(login.as_bytes()).await?;
+    debug!("Watching #{} for Hype Chats", channel);
+
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim_end();
+        if let Some(server) = line.strip_prefix("PING ") {
+            writer.write_all(format!("PONG {}\r\n", server).as_bytes()).await?;
+            continue;
+        }
+        if let Some(hype_chat) = parse_hype_chat(line) {
+            record(app, hype_chat);
+        }
+    }
+    Ok(())
+}
+
+// Paid PRIVMSG lines carry `pinned-chat-paid-*` tags, e.g.
+// `@id=...;pinned-chat-paid-amount=500;pinned-chat-paid-exponent=2;... :user PRIVMSG #chan :hi`
+fn parse_hype_chat(line: &str) -> Option<HypeChat> {
+    let (tags, rest) = line.strip_prefix('@')?.split_once(' ')?;
+    if !rest.contains(" PRIVMSG ") {
+        return None;
+    }
+
+    let tags = tags.split(';')
+        .filter_map(|tag| tag.split_once('='))
+        .collect::<HashMap<_, _>>();
+    let amount: f64 = tags.get("pinned-chat-paid-amount")?.parse().ok()?;
+    let exponent: i32 = tags.get("pinned-chat-paid-exponent").and_then(|e| e.parse().ok()).unwrap_or(2);
+
+    Some(HypeChat {
+        message_id: tags.get("id")?.to_string(),
+        amount: amount / 10f64.powi(exponent),
+        currency: tags.get("pinned-chat-paid-currency").unwrap_or(&"").to_string(),
+        level: tags.get("pinned-chat-paid-level").unwrap_or(&"").to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_paid_message_tags() {
+        let line = "@badges=;id=abc-123;pinned-chat-paid-amount=500;pinned-chat-paid-currency=USD;\
+pinned-chat-paid-exponent=2;pinned-chat-paid-level=ONE;user-id=1 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #channel :hello";
+        let hype_chat = parse_hype_chat(line).expect("paid message");
+
+        assert_eq!(hype_chat.message_id, "abc-123");
+        assert_eq!(hype_chat.amount, 5.0);
+        assert_eq!(hype_chat.currency, "USD");
+        assert_eq!(hype_chat.level, "ONE");
+    }
+
+    #[test]
+    fn ignores_unpaid_messages_and_other_commands() {
+        let unpaid = "@id=abc;user-id=1 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #channel :hello";
+        let notice = "@msg-id=sub;pinned-chat-paid-amount=500 :tmi.twitch.tv USERNOTICE #channel";
+
+        assert!(parse_hype_chat(unpaid).is_none());
+        assert!(parse_hype_chat(notice).is_none());
+        assert!(parse_hype_chat("PING :tmi.twitch.tv").is_none());
+    }
+}
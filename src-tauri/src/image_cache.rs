@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::http::{Request, Response, ResponseBuilder};
+
+// Local proxy for remote images (badges, emotes, avatars). The webview loads them through
+// the `imgcache` URI scheme instead of hitting the CDNs directly. Images are downloaded as
+// soon as a message refers to them, and the scheme handler serves them once they're on
+// disk, so they also work offline.
+//
+// Files are content-addressed: each image is stored once under the SHA-256 of its bytes,
+// and `index.json` maps the SHA-256 of each source URL to the image it points at.
+pub const SCHEME: &str = "imgcache";
+
+const INDEX_FILE: &str = "index.json";
+// How long a background download may take
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+// How long the scheme handler waits for an image that's still downloading. Handlers run
+// on the UI thread, so this is kept short; the download carries on either way.
+const HANDLER_WAIT: Duration = Duration::from_secs(3);
+// New index entries are written out at most this often, so prefetching hundreds of
+// images doesn't rewrite the index for each one
+const INDEX_FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+// Only images from these hosts are proxied, so the scheme can't be used to fetch arbitrary URLs
+const ALLOWED_HOSTS: &[&str] = &[
+    "static-cdn.jtvnw.net",
+    "yt3.ggpht.com",
+    "yt4.ggpht.com",
+    "yt3.googleusercontent.com",
+    "lh3.googleusercontent.com",
+];
+
+static CACHE_DIR: OnceCell<PathBuf> = OnceCell::new();
+// URL key -> content key
+static INDEX: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// Whether INDEX has entries that aren't in index.json yet
+static INDEX_DIRTY: AtomicBool = AtomicBool::new(false);
+// Source URLs being downloaded, with whoever waits for them to finish
+static IN_FLIGHT: Lazy<Mutex<HashMap<String, Vec<Sender<()>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// Makes temp file names unique, so concurrent writes of the same file don't collide
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+});
+static PREFERRED_SCALE: Lazy<Mutex<ImageScale>> = Lazy::new(|| Mutex::new(ImageScale::Small));
+
+// Badges and emotes come in three sizes: 18/36/72px badges and 28/56/112px emotes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageScale {
+    Small,
+    Medium,
+    Large,
+}
+
+impl ImageScale {
+    fn index(self) -> usize {
+        match self {
+            ImageScale::Small => 0,
+            ImageScale::Medium => 1,
+            ImageScale::Large => 2,
+        }
+    }
+}
+
+pub fn preferred_scale() -> ImageScale {
+    PREFERRED_SCALE.lock().map(|scale| *scale).unwrap_or(ImageScale::Small)
+}
+
+pub fn set_preferred_scale(scale: ImageScale) {
+    if let Ok(mut preferred) = PREFERRED_SCALE.lock() {
+        *preferred = scale;
+    }
+}
+
+// An image in all three sizes, ready to hand to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct ScaledImage {
+    // The size picked by the preferred scale
+    pub url: String,
+    // All sizes with their pixel densities, for <img srcset>
+    pub srcset: String,
+}
+
+impl ScaledImage {
+    // `urls` are the small, medium and large versions
+    pub fn new(urls: [&str; 3]) -> Self {
+        let densities = ["1x", "2x", "4x"];
+        let srcset = urls.iter()
+            .zip(densities)
+            .filter(|(url, _)| !url.is_empty())
+            .map(|(url, density)| format!("{} {}", resolve(url), density))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        ScaledImage {
+            url: resolve(pick(urls)),
+            srcset,
+        }
+    }
+}
+
+// The URL for the preferred scale, falling back to the small one if that size is missing
+pub fn pick(urls: [&str; 3]) -> &str {
+    match urls[preferred_scale().index()] {
+        "" => urls[0],
+        url => url,
+    }
+}
+
+// Set the cache folder and load the index of images already in it
+pub fn init(dir: PathBuf) -> Result<(), String> {
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create image cache {}: {}", dir.display(), e))?;
+
+    let mut index = fs::read_to_string(dir.join(INDEX_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<HashMap<String, String>>(&contents).ok())
+        .unwrap_or_default();
+
+    // Forget entries whose image has gone missing
+    index.retain(|_, content_key| dir.join(content_key.as_str()).exists());
+
+    info!("Image cache at {} has {} images", dir.display(), index.len());
+
+    if let Ok(mut current) = INDEX.lock() {
+        current.extend(index);
+    }
+    let _ = CACHE_DIR.set(dir);
+
+    tauri::async_runtime::spawn(async {
+        loop {
+            flush_index();
+            tokio::time::sleep(INDEX_FLUSH_INTERVAL).await;
+        }
+    });
+    Ok(())
+}
+
+// Write the index if it changed since the last write. The lock is only held to copy it.
+pub fn flush_index() {
+    let Some(dir) = CACHE_DIR.get() else {
+        return;
+    };
+    if !INDEX_DIRTY.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let Some(index) = INDEX.lock().ok().map(|index| index.clone()) else {
+        return;
+    };
+    save_index(dir, &index);
+}
+
+fn save_index(dir: &Path, index: &HashMap<String, String>) {
+    let result = serde_json::to_string(index)
+        .map_err(|e| e.to_string())
+        .and_then(|json| write_atomic(&dir.join(INDEX_FILE), json.as_bytes()));
+
+    if let Err(e) = result {
+        warn!("Failed to save image cache index: {}", e);
+    }
+}
+
+fn hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+pub fn key_for(url: &str) -> String {
+    hash(url.as_bytes())
+}
+
+fn is_valid_key(key: &str) -> bool {
+    key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+pub fn is_cached(url: &str) -> bool {
+    INDEX.lock()
+        .map(|index| index.contains_key(&key_for(url)))
+        .unwrap_or(false)
+}
+
+fn is_proxyable(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .map(|parsed| parsed.scheme() == "https"
+            && parsed.host_str().map(|host| ALLOWED_HOSTS.contains(&host)).unwrap_or(false))
+        .unwrap_or(false)
+}
+
+// URL the webview loads a cached image from. Custom schemes are exposed as
+// https://<scheme>.localhost on Windows and <scheme>://localhost elsewhere.
+// The source URL rides along so the image can be fetched again if it went missing.
+fn local_url(url: &str) -> String {
+    let base = if cfg!(windows) {
+        format!("https://{}.localhost/{}", SCHEME, key_for(url))
+    } else {
+        format!("{}://localhost/{}", SCHEME, key_for(url))
+    };
+
+    match reqwest::Url::parse_with_params(&base, &[("src", url)]) {
+        Ok(local) => local.to_string(),
+        Err(_) => url.to_string(),
+    }
+}
+
+// The proxied URL for images from known CDNs. Images that aren't cached yet start
+// downloading now, so they're usually on disk by the time the webview asks for them.
+pub fn resolve(url: &str) -> String {
+    if !is_proxyable(url) || CACHE_DIR.get().is_none() {
+        return url.to_string();
+    }
+    if !is_cached(url) {
+        fetch_in_background(url.to_string(), None);
+    }
+    local_url(url)
+}
+
+// Download an image on the async runtime unless it's already on its way. `done` is
+// notified once the download has finished, whether it worked or not.
+fn fetch_in_background(url: String, done: Option<Sender<()>>) {
+    let started = match IN_FLIGHT.lock() {
+        Ok(mut in_flight) => {
+            let started = !in_flight.contains_key(&url);
+            in_flight.entry(url.clone()).or_default().extend(done);
+            started
+        },
+        Err(_) => false,
+    };
+    if !started {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = fetch(&CLIENT, &url).await {
+            warn!("Failed to fetch image {}: {}", url, e);
+        }
+        let waiters = IN_FLIGHT.lock().ok().and_then(|mut in_flight| in_flight.remove(&url));
+        for waiter in waiters.into_iter().flatten() {
+            let _ = waiter.send(());
+        }
+    });
+}
+
+// Download an image into the cache unless it's already there
+pub async fn fetch(client: &reqwest::Client, url: &str) -> Result<(), String> {
+    if url.is_empty() || is_cached(url) {
+        return Ok(());
+    }
+
+    let dir = CACHE_DIR.get().ok_or_else(|| "Image cache is not initialized".to_string())?;
+    let bytes = download(client, url).await?;
+    store(dir, url, &bytes)
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    let response = client.get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Image download failed: Status code {}", response.status()));
+    }
+
+    let bytes = response.bytes()
+        .await
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+
+    Ok(bytes.to_vec())
+}
+
+// Write to a temp file first so a crash never leaves a half-written file behind
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("file");
+    let temp_path = path.with_file_name(format!(
+        "{}.{}-{}.tmp",
+        file_name,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    fs::write(&temp_path, bytes).map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
+    fs::rename(&temp_path, path).map_err(|e| format!("Failed to store {}: {}", path.display(), e))
+}
+
+fn store(dir: &Path, url: &str, bytes: &[u8]) -> Result<(), String> {
+    let content_key = hash(bytes);
+    let path = dir.join(&content_key);
+    // Identical images from different URLs are only stored once
+    if !path.exists() {
+        write_atomic(&path, bytes)?;
+    }
+
+    let mut index = INDEX.lock().map_err(|e| format!("Failed to lock image cache: {}", e))?;
+    index.insert(key_for(url), content_key);
+    INDEX_DIRTY.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+fn content_type(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(b"\x89PNG") {
+        "image/png"
+    } else if bytes.starts_with(b"GIF8") {
+        "image/gif"
+    } else if bytes.starts_with(b"\xff\xd8\xff") {
+        "image/jpeg"
+    } else if bytes.len() > 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        "image/webp"
+    } else if bytes.starts_with(b"<svg") || bytes.starts_with(b"<?xml") {
+        "image/svg+xml"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+fn read_cached(dir: &Path, url_key: &str) -> Option<Vec<u8>> {
+    let content_key = INDEX.lock().ok()?.get(url_key)?.clone();
+    fs::read(dir.join(content_key)).ok()
+}
+
+// Bytes for a proxied image, downloading it first if it isn't cached. The download runs
+// on the async runtime; this only waits up to HANDLER_WAIT for it.
+fn load(url_key: &str, src: Option<&str>) -> Option<Vec<u8>> {
+    let dir = CACHE_DIR.get()?;
+    if let Some(bytes) = read_cached(dir, url_key) {
+        return Some(bytes);
+    }
+
+    // The key must belong to the source URL, so a page can't poison another image's entry
+    let src = src.filter(|src| key_for(src) == url_key && is_proxyable(src))?;
+    let (done, finished) = mpsc::channel();
+    fetch_in_background(src.to_string(), Some(done));
+    if finished.recv_timeout(HANDLER_WAIT).is_err() {
+        warn!("Image {} is taking too long to download", src);
+        return None;
+    }
+    read_cached(dir, url_key)
+}
+
+// Handler for the `imgcache` URI scheme
+pub fn handle_request(request: &Request) -> Result<Response, Box<dyn std::error::Error>> {
+    let uri = reqwest::Url::parse(request.uri()).ok();
+    let url_key = uri.as_ref()
+        .and_then(|uri| uri.path_segments())
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default()
+        .to_string();
+    let src = uri.as_ref()
+        .and_then(|uri| uri.query_pairs().find(|(name, _)| name == "src"))
+        .map(|(_, value)| value.into_owned());
+
+    let bytes = if is_valid_key(&url_key) {
+        load(&url_key, src.as_deref())
+    } else {
+        None
+    };
+
+    match bytes {
+        Some(bytes) => ResponseBuilder::new()
+            .mimetype(content_type(&bytes))
+            .header("Cache-Control", "max-age=31536000, immutable")
+            .body(bytes),
+        // Download failed or is still running; don't let the webview remember the miss
+        None => ResponseBuilder::new()
+            .status(404)
+            .header("Cache-Control", "no-store")
+            .body(Vec::new()),
+    }
+}
@@ -0,0 +1,371 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use colored::Colorize;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Serialize;
+
+use crate::error::{AppError, AppResult};
+
+// Logging for the whole app, including the twitch_eventsub library. Records go to the
+// console and, once the log dir is known, to `streamchatbox.log` as JSON lines. The file
+// is rotated at MAX_FILE_SIZE and the last MAX_ROTATED_FILES are kept.
+//
+// Levels are set with STREAMCHATBOX_LOG, e.g. "info,streamchatbox::batcher=debug".
+// Tokens and keys are redacted before anything is written.
+const LOG_FILE: &str = "streamchatbox.log";
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+const MAX_ROTATED_FILES: usize = 5;
+
+static LOGGER: OnceCell<Logger> = OnceCell::new();
+// Secret values seen at runtime (API keys, tokens); replaced wherever they show up
+static SECRETS: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+// Query parameters and prefixes that are followed by a secret
+const SECRET_MARKERS: &[&str] = &[
+    "key=",
+    "token=",
+    "secret=",
+    "password=",
+    "Bearer ",
+    "oauth:",
+    "TWITCH_CLIENT_SECRET=",
+    "YOUTUBE_API_KEY=",
+];
+
+const REDACTED: &str = "[REDACTED]";
+
+struct Logger {
+    default_level: LevelFilter,
+    // (target prefix, level), longest prefix wins
+    targets: Vec<(String, LevelFilter)>,
+    file: Mutex<Option<LogFile>>,
+}
+
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct LogLine<'a> {
+    ts: String,
+    level: &'a str,
+    target: &'a str,
+    message: &'a str,
+}
+
+impl Logger {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets.iter()
+            .filter(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default_level)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = redact(&record.args().to_string());
+        let level = record.level();
+
+        let label = match level {
+            Level::Error => "ERROR".red().bold(),
+            Level::Warn => "WARN ".yellow().bold(),
+            Level::Info => "INFO ".green(),
+            Level::Debug => "DEBUG".blue(),
+            Level::Trace => "TRACE".bright_black(),
+        };
+        println!("{} {} {}", label, record.target().bright_black(), message);
+
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(log_file) = file.as_mut() {
+                let line = LogLine {
+                    ts: chrono::Utc::now().to_rfc3339(),
+                    level: level.as_str(),
+                    target: record.target(),
+                    message: &message,
+                };
+                if let Ok(json) = serde_json::to_string(&line) {
+                    log_file.write_line(&json);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(log_file) = file.as_mut() {
+                let _ = log_file.file.flush();
+            }
+        }
+    }
+}
+
+impl LogFile {
+    fn open(dir: &Path) -> std::io::Result<LogFile> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(LOG_FILE);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+        Ok(LogFile { dir: dir.to_path_buf(), file, size })
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.size + line.len() as u64 > MAX_FILE_SIZE {
+            if let Err(e) = self.rotate() {
+                eprintln!("Failed to rotate log file: {}", e);
+            }
+        }
+
+        if writeln!(self.file, "{}", line).is_ok() {
+            self.size += line.len() as u64 + 1;
+        }
+    }
+
+    // streamchatbox.log -> streamchatbox.1.log -> ... -> streamchatbox.N.log (deleted)
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let oldest = rotated_path(&self.dir, MAX_ROTATED_FILES);
+        if oldest.exists() {
+            fs::remove_file(oldest)?;
+        }
+        for index in (1..MAX_ROTATED_FILES).rev() {
+            let from = rotated_path(&self.dir, index);
+            if from.exists() {
+                fs::rename(from, rotated_path(&self.dir, index + 1))?;
+            }
+        }
+        fs::rename(self.dir.join(LOG_FILE), rotated_path(&self.dir, 1))?;
+
+        *self = LogFile::open(&self.dir)?;
+        Ok(())
+    }
+}
+
+fn rotated_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("streamchatbox.{}.log", index))
+}
+
+// Parse "info,streamchatbox::batcher=debug,twitch_eventsub=warn"
+fn parse_filters(spec: &str) -> (LevelFilter, Vec<(String, LevelFilter)>) {
+    let mut default_level = LevelFilter::Info;
+    let mut targets = Vec::new();
+
+    for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        match part.split_once('=') {
+            Some((target, level)) => {
+                if let Ok(level) = level.trim().parse() {
+                    targets.push((target.trim().to_string(), level));
+                }
+            },
+            None => {
+                if let Ok(level) = part.parse() {
+                    default_level = level;
+                }
+            },
+        }
+    }
+
+    (default_level, targets)
+}
+
+// Install the logger. Called first thing in main, before the log dir is known.
+pub fn init() {
+    let spec = std::env::var("STREAMCHATBOX_LOG").unwrap_or_else(|_| "info".to_string());
+    let (default_level, targets) = parse_filters(&spec);
+    let max_level = targets.iter().map(|(_, level)| *level).chain([default_level]).max().unwrap_or(LevelFilter::Info);
+
+    let logger = LOGGER.get_or_init(|| Logger {
+        default_level,
+        targets,
+        file: Mutex::new(None),
+    });
+
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+// Start writing to the log file in `dir`. Called from setup once the app log dir is known.
+pub fn init_file(dir: &Path) {
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+
+    match LogFile::open(dir) {
+        Ok(log_file) => {
+            if let Ok(mut file) = logger.file.lock() {
+                *file = Some(log_file);
+            }
+            log::info!("Logging to {}", dir.join(LOG_FILE).display());
+        },
+        Err(e) => log::error!("Failed to open log file in {}: {}", dir.display(), e),
+    }
+}
+
+pub fn log_dir() -> Option<PathBuf> {
+    LOGGER.get()?
+        .file.lock().ok()?
+        .as_ref()
+        .map(|log_file| log_file.dir.clone())
+}
+
+// Remember a secret so it's redacted from every later log line
+pub fn register_secret(secret: &str) {
+    let secret = secret.trim();
+    // Very short values would redact ordinary words
+    if secret.len() < 8 {
+        return;
+    }
+
+    if let Ok(mut secrets) = SECRETS.lock() {
+        if !secrets.iter().any(|known| known == secret) {
+            secrets.push(secret.to_string());
+        }
+    }
+}
+
+// Hide known secrets and anything that looks like a key or token
+pub fn redact(text: &str) -> String {
+    let mut result = text.to_string();
+
+    if let Ok(secrets) = SECRETS.lock() {
+        for secret in secrets.iter() {
+            if result.contains(secret.as_str()) {
+                result = result.replace(secret.as_str(), REDACTED);
+            }
+        }
+    }
+
+    for marker in SECRET_MARKERS {
+        result = redact_after(&result, marker);
+    }
+    result
+}
+
+// Replace the value that follows each occurrence of `marker`
+fn redact_after(text: &str, marker: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(index) = rest.find(marker) {
+        let value_start = index + marker.len();
+        result.push_str(&rest[..value_start]);
+
+        let value = &rest[value_start..];
+        let value_len = value.find(|c: char| c.is_whitespace() || "&\"',;)}".contains(c))
+            .unwrap_or(value.len());
+
+        if value_len > 0 && !value[..value_len].starts_with(REDACTED) {
+            result.push_str(REDACTED);
+        } else {
+            result.push_str(&value[..value_len]);
+        }
+        rest = &value[value_len..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+// Zip the log files together with a redacted summary of the config for bug reports.
+// Returns the path of the archive.
+pub fn export_diagnostics(destination: &Path, config: &serde_json::Value) -> AppResult<PathBuf> {
+    let dir = log_dir().ok_or_else(|| AppError::config("Logging to file is not set up"))?;
+
+    let file = File::create(destination)
+        .map_err(|e| AppError::config(format!("Failed to create {}: {}", destination.display(), e)))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    let zip_error = |e: zip::result::ZipError| AppError::internal(format!("Failed to write diagnostics: {}", e));
+    let io_error = |e: std::io::Error| AppError::internal(format!("Failed to write diagnostics: {}", e));
+
+    let mut log_files = vec![dir.join(LOG_FILE)];
+    log_files.extend((1..=MAX_ROTATED_FILES).map(|index| rotated_path(&dir, index)));
+
+    for path in log_files.iter().filter(|path| path.exists()) {
+        let contents = fs::read_to_string(path).unwrap_or_default();
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or(LOG_FILE);
+        zip.start_file(format!("logs/{}", name), options).map_err(zip_error)?;
+        // Redact again in case the file was written before a secret was registered
+        zip.write_all(redact(&contents).as_bytes()).map_err(io_error)?;
+    }
+
+    let config = serde_json::to_string_pretty(config).unwrap_or_default();
+    zip.start_file("config.json", options).map_err(zip_error)?;
+    zip.write_all(redact(&config).as_bytes()).map_err(io_error)?;
+
+    zip.finish().map_err(zip_error)?;
+    log::info!("Exported diagnostics to {}", destination.display());
+    Ok(destination.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Registered secrets are global, so each test uses its own value and removes it again
+    fn forget_secret(secret: &str) {
+        SECRETS.lock().unwrap().retain(|known| known != secret);
+    }
+
+    #[test]
+    fn redacts_registered_secrets() {
+        let secret = "registered-secret-value";
+        register_secret(&format!("  {}  ", secret));
+        let redacted = redact("Logging in with registered-secret-value (again: registered-secret-value)");
+        forget_secret(secret);
+
+        assert_eq!(redacted, "Logging in with [REDACTED] (again: [REDACTED])");
+        assert_eq!(redact("registered-secret-value"), "registered-secret-value");
+    }
+
+    #[test]
+    fn ignores_short_secrets() {
+        register_secret("short");
+        let redacted = redact("short is not a secret");
+        forget_secret("short");
+
+        assert_eq!(redacted, "short is not a secret");
+    }
+
+    #[test]
+    fn redacts_values_after_markers() {
+        assert_eq!(
+            redact("GET https://www.googleapis.com/youtube/v3/channels?part=id&key=AIzaSyExample&id=UC1"),
+            "GET https://www.googleapis.com/youtube/v3/channels?part=id&key=[REDACTED]&id=UC1"
+        );
+        assert_eq!(
+            redact("callback#access_token=abc123xyz&scope=chat%3Aread"),
+            "callback#access_token=[REDACTED]&scope=chat%3Aread"
+        );
+        assert_eq!(
+            redact("Authorization: Bearer abc123xyz sent"),
+            "Authorization: Bearer [REDACTED] sent"
+        );
+    }
+
+    #[test]
+    fn redaction_is_idempotent() {
+        let once = redact("{\"Authorization\": \"Bearer abc123xyz\", \"url\": \"/?key=abc\"}");
+        assert_eq!(once, "{\"Authorization\": \"Bearer [REDACTED]\", \"url\": \"/?key=[REDACTED]\"}");
+        assert_eq!(redact(&once), once);
+        assert_eq!(redact_after("key=", "key="), "key=");
+    }
+}
@@ -4,25 +4,52 @@
 use tauri::{AppHandle, Manager};
 use twitch_eventsub::*;
 use std::time::Duration;
-use std::sync::Once;
-use random_color::RandomColor;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::collections::VecDeque;
-use tokio::time::sleep;
-use std::{env, fs, error::Error};
+use std::error::Error;
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
-use std::path::PathBuf;
-use std::io::{BufRead, BufReader};
-use colored::Colorize;
+use std::path::{Component, Path, PathBuf};
+use log::{debug, info, warn};
 use chrono::{DateTime, Local};
+use serde::de::DeserializeOwned;
+
+use error::{AppError, AppResult};
 
 // Add the badges module
+mod avatars;
 mod badges;
-
-static START: Once = Once::new();
-static MOCK_START: Once = Once::new();
-static YOUTUBE_START: Once = Once::new();
+mod batcher;
+mod cheermotes;
+mod colors;
+mod config_export;
+mod credentials;
+mod emotes;
+mod error;
+mod eventsub;
+mod hype_chat;
+mod image_cache;
+mod logging;
+mod mock;
+mod profiles;
+mod raids;
+mod secrets;
+mod settings;
+mod stress;
+mod twitch_auth;
+mod twitch_events;
+mod validation;
+
+// The running listeners and their generations, so they can be restarted with new
+// credentials or another profile
+type ListenerTask = (u64, tauri::async_runtime::JoinHandle<()>);
+type ListenerSlot = once_cell::sync::Lazy<Mutex<Option<ListenerTask>>>;
+static TWITCH_LISTENER: ListenerSlot = once_cell::sync::Lazy::new(|| Mutex::new(None));
+static YOUTUBE_LISTENER: ListenerSlot = once_cell::sync::Lazy::new(|| Mutex::new(None));
+static LISTENER_GENERATION: AtomicU64 = AtomicU64::new(0);
+// How often the Twitch listener looks for tokens the library refreshed
+const TOKEN_CHECK_INTERVAL: Duration = Duration::from_secs(5);
 // Message queue for chat messages
 static TWITCH_MESSAGE_QUEUE: once_cell::sync::Lazy<Arc<Mutex<VecDeque<String>>>> = 
     once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(VecDeque::new())));
@@ -55,10 +82,31 @@ struct MessageSnippet {
     display_message: String,
     #[serde(rename = "publishedAt")]
     published_at: String,
+    // Set on membership announcements, which carry the member's level
+    #[serde(rename = "newSponsorDetails", default)]
+    new_sponsor_details: Option<NewSponsorDetails>,
+    #[serde(rename = "memberMilestoneChatDetails", default)]
+    member_milestone_details: Option<MemberMilestoneDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NewSponsorDetails {
+    #[serde(rename = "memberLevelName")]
+    member_level_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MemberMilestoneDetails {
+    #[serde(rename = "memberLevelName")]
+    member_level_name: Option<String>,
+    #[serde(rename = "memberMonth")]
+    member_month: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
 struct AuthorDetails {
+    #[serde(rename = "channelId")]
+    channel_id: Option<String>,
     #[serde(rename = "displayName")]
     display_name: String,
     #[serde(rename = "isChatOwner")]
@@ -67,6 +115,10 @@ struct AuthorDetails {
     is_moderator: Option<bool>,
     #[serde(rename = "isChatSponsor")]
     is_sponsor: Option<bool>,
+    #[serde(rename = "isVerified")]
+    is_verified: Option<bool>,
+    #[serde(rename = "profileImageUrl")]
+    profile_image_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -124,61 +176,70 @@ struct TextMessageDetails {
 
 
 
-async fn get_live_video_id(client: &Client, channel_id: &str, api_key: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+// Send a YouTube API request and parse the JSON reply, classifying any failure
+async fn youtube_request<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> AppResult<T> {
+    let response = request.send()
+        .await
+        .map_err(|e| AppError::from_request("youtube", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("youtube", status, &body));
+    }
+
+    response.json::<T>()
+        .await
+        .map_err(|e| AppError::from_request("youtube", e))
+}
+
+async fn get_live_video_id(client: &Client, channel_id: &str, api_key: &str) -> AppResult<String> {
     let search_url = format!(
         "https://www.googleapis.com/youtube/v3/search?part=id&eventType=live&type=video&channelId={}&key={}",
         channel_id, api_key
     );
     
-    let search_response = client.get(&search_url)
-        .send()
-        .await?
-        .json::<SearchResponse>()
-        .await?;
+    let search_response: SearchResponse = youtube_request(client.get(&search_url)).await?;
     
     if search_response.items.is_empty() {
-        return Err("No live streams found for this channel".into());
+        return Err(AppError::platform_api("youtube", None, "No live streams found for this channel"));
     }
     
     match &search_response.items[0].id.video_id {
         Some(video_id) => Ok(video_id.clone()),
-        None => Err("Could not find video ID in the search response".into()),
+        None => Err(AppError::platform_api("youtube", None, "Could not find video ID in the search response")),
     }
 }
 
-async fn get_live_chat_id(client: &Client, video_id: &str, api_key: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+async fn get_live_chat_id(client: &Client, video_id: &str, api_key: &str) -> AppResult<String> {
     let video_url = format!(
         "https://www.googleapis.com/youtube/v3/videos?part=liveStreamingDetails&id={}&key={}",
         video_id, api_key
     );
     
-    let video_response = client.get(&video_url)
-        .send()
-        .await?
-        .json::<LiveVideoResponse>()
-        .await?;
+    let video_response: LiveVideoResponse = youtube_request(client.get(&video_url)).await?;
     
     if video_response.items.is_empty() {
-        return Err("No video details found".into());
+        return Err(AppError::platform_api("youtube", None, "No video details found"));
     }
     
     match &video_response.items[0].live_streaming_details {
         Some(details) => {
             match &details.active_live_chat_id {
                 Some(chat_id) => Ok(chat_id.clone()),
-                None => Err("No active live chat found for this video".into()),
+                None => Err(AppError::platform_api("youtube", None, "No active live chat found for this video")),
             }
         },
-        None => Err("No live streaming details found for this video".into()),
+        None => Err(AppError::platform_api("youtube", None, "No live streaming details found for this video")),
     }
 }
 
-async fn youtube_send_chat(client: &Client, video_id: &str, message: &str) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+async fn youtube_send_chat(client: &Client, video_id: &str, message: &str) -> AppResult<()> {
     let url = format!(
         "https://www.googleapis.com/youtube/v3/liveChat/messages?part=snippet"
     );
 
-    println!("YouTube chat URL: {}", url);
+    debug!("YouTube chat URL: {}", url);
 
     let request = SendMessageRequest {
         snippet: Snippet {
@@ -190,16 +251,23 @@ async fn youtube_send_chat(client: &Client, video_id: &str, message: &str) -> Re
         },
     };
 
-    println!("YouTube chat request: {:?}", request);
+    debug!("YouTube chat request: {:?}", request);
 
     let response = client.get(&url)
         .json(&request)
         .send()
-        .await?;
+        .await
+        .map_err(|e| AppError::from_request("youtube", e))?;
 
     // Rprint the response to see if it is bad
-    println!("YouTube chat response: {:?}", response);
-    Ok(response)
+    debug!("YouTube chat response: {:?}", response);
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("youtube", status, &body));
+    }
+    Ok(())
 
 }
 
@@ -209,7 +277,7 @@ async fn fetch_chat_messages(
     chat_id: &str, 
     api_key: &str, 
     next_page_token: Option<&str>
-) -> Result<LiveChatResponse, Box<dyn std::error::Error + Send + Sync>> {
+) -> AppResult<LiveChatResponse> {
     let mut url = format!(
         "https://www.googleapis.com/youtube/v3/liveChat/messages?liveChatId={}&part=snippet,authorDetails&key={}",
         chat_id, api_key
@@ -219,14 +287,7 @@ async fn fetch_chat_messages(
         url.push_str(&format!("&pageToken={}", token));
     }
     
-    let response = client.get(&url)
-        .send()
-        .await?
-        .json::<LiveChatResponse>()
-        .await?;
-
-
-    Ok(response)
+    youtube_request(client.get(&url)).await
 }
 
 fn format_timestamp(timestamp_str: &str) -> Result<String, Box<dyn Error>> {
@@ -243,23 +304,18 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
-// Return a random color hex code
-fn get_random_color() -> String {
-    return RandomColor::new().to_hex();
-}
-
 // New function to queue chat messages from frontend
 #[tauri::command]
-fn send_chat_message(message: String) -> Result<(), String> {
+fn send_chat_message(message: String) -> AppResult<()> {
     {
         let mut twitch_queue = TWITCH_MESSAGE_QUEUE.lock()
-            .map_err(|e| format!("Failed to lock Twitch queue: {}", e))?;
+            .map_err(|e| AppError::internal(format!("Failed to lock Twitch queue: {}", e)))?;
         twitch_queue.push_back(message.clone());
     }
 
     {
         let mut youtube_queue = YOUTUBE_MESSAGE_QUEUE.lock()
-            .map_err(|e| format!("Failed to lock YouTube queue: {}", e))?;
+            .map_err(|e| AppError::internal(format!("Failed to lock YouTube queue: {}", e)))?;
         youtube_queue.push_back(message);
     }
 
@@ -268,22 +324,50 @@ fn send_chat_message(message: String) -> Result<(), String> {
 
 // Command to initialize badges
 #[tauri::command]
-fn initialize_twitch_badges(client_id: String, access_token: String, broadcaster_id: String) -> Result<(), String> {
-    println!("Initializing Twitch badges...");
-    badges::ensure_badges_initialized(client_id, access_token, broadcaster_id);
+fn initialize_twitch_badges(app: AppHandle, client_id: String, access_token: String, broadcaster_id: String) -> AppResult<()> {
+    info!("Initializing Twitch badges...");
+    avatars::set_twitch_credentials(client_id.clone(), access_token.clone());
+    tauri::async_runtime::spawn(async move {
+        match badges::refresh_badges(client_id, access_token, broadcaster_id).await {
+            Ok(_) => info!("Successfully initialized badges"),
+            Err(e) => error::emit(&app, "badges", &e),
+        }
+    });
     Ok(())
 }
 
+// Command to re-fetch badges without restarting, e.g. after switching channels or when
+// the streamer has added new sub badges. Uses the configured channel if none is given.
+#[tauri::command]
+async fn refresh_badges(app: AppHandle, broadcaster_id: Option<String>) -> AppResult<()> {
+    let (client_id, configured_broadcaster_id, access_token) = twitch_badge_credentials()?;
+    let broadcaster_id = broadcaster_id
+        .filter(|id| !id.trim().is_empty())
+        .unwrap_or(configured_broadcaster_id);
+
+    info!("Refreshing badges for broadcaster {}", broadcaster_id);
+    match badges::refresh_badges(client_id, access_token, broadcaster_id).await {
+        Ok(_) => {
+            let _ = app.emit_all("badges-initialized", true);
+            Ok(())
+        },
+        Err(e) => {
+            let _ = app.emit_all("badges-initialization-failed", e.to_string());
+            Err(e)
+        }
+    }
+}
+
 // Command to initialize badges from environment files
 #[tauri::command]
-fn initialize_badges_from_env() -> Result<(), String> {
-    println!("Initializing Twitch badges from environment files (frontend request)...");
+fn initialize_badges_from_env(app: AppHandle) -> AppResult<()> {
+    info!("Initializing Twitch badges from environment files (frontend request)...");
     
     // Spawn the async task
-    tauri::async_runtime::spawn(async {
+    tauri::async_runtime::spawn(async move {
         match initialize_badges_from_env_internal().await {
-            Ok(_) => println!("Badge initialization from frontend successful"),
-            Err(e) => println!("Badge initialization from frontend failed: {}", e),
+            Ok(_) => info!("Badge initialization from frontend successful"),
+            Err(e) => error::emit(&app, "badges", &e),
         }
     });
     
@@ -291,124 +375,160 @@ fn initialize_badges_from_env() -> Result<(), String> {
     Ok(())
 }
 
-// Start a mock events generator for testing donation and subscription events
-#[tauri::command]
-fn start_mock_events(app: AppHandle) {
-    MOCK_START.call_once(|| {
-        tauri::async_runtime::spawn(async move {
-            println!("Started mock events generator...");
-            
-            loop {
-                // Random delay between events (5-15 seconds)
-                let delay = rand::random::<u64>() % 10 + 5;
-                sleep(Duration::from_secs(delay)).await;
-                
-                // Randomly select event type
-                let event_type = rand::random::<u8>() % 3;
-                
-                match event_type {
-                    0 => {
-                        // Generate mock donation
-                        let amount = (rand::random::<f64>() * 100.0).round() / 100.0;
-                        let username = format!("Donor{}", rand::random::<u16>() % 1000);
-                        let message = if rand::random::<bool>() {
-                            Some(format!("Thanks for the stream! Keep up the good work!"))
-                        } else {
-                            None
-                        };
-                        
-                        println!("{} donated ${:.2}!", username, amount);
-                        
-                        let _ = app.emit_all("twitch-donation", serde_json::json!({
-                            "username": username,
-                            "amount": amount,
-                            "message": message,
-                        }));
-                    },
-                    1 => {
-                        // Generate mock subscription
-                        let username = format!("Sub{}", rand::random::<u16>() % 1000);
-                        let tier = rand::random::<u8>() % 3 + 1;
-                        let is_gift = rand::random::<bool>();
-                        
-                        println!("{} subscribed with tier {}!", username, tier);
-                        
-                        let _ = app.emit_all("twitch-subscription", serde_json::json!({
-                            "username": username,
-                            "tier": tier,
-                            "is_gift": is_gift,
-                        }));
-                    },
-                    _ => {
-                        // Generate mock follow (already handled by the real system, 
-                        // but we'll add extra ones for testing)
-                        let username = format!("Follower{}", rand::random::<u16>() % 1000);
-                        
-                        println!("{} followed!", username);
-                        
-                        let _ = app.emit_all("twitch-follow", serde_json::json!({
-                            "user": username
-                        }));
-                    }
-                }
-            }
-        });
-    });
+// Start the mock events generator for testing chat, donation and subscription events.
+// Without a config it uses the mock settings.
+#[tauri::command]
+fn start_mock_events(app: AppHandle, config: Option<mock::MockConfig>) {
+    mock::start(app, config.unwrap_or_else(|| settings::get().mock));
 }
 
+// Stop the mock events generator and any running scenario
 #[tauri::command]
-fn start_youtube_listener(app: AppHandle) {
-    let app_clone = app.clone();
-    YOUTUBE_START.call_once(move || {
-        let app_clone2 = app_clone.clone();
-        tauri::async_runtime::spawn(async move {
-            simple_env_load::load_env_from([".secrets.env"]);
-
-            fn get(key: &str) -> Result<String, String> {
-                std::env::var(key).map_err(|_| format!("please set {key} in .example.env"))
-            }
+fn stop_mock_events() {
+    mock::stop();
+}
 
-            let youtube_channel_id = match get("YOUTUBE_CHANNEL_ID") {
-                Ok(s) => s,
-                Err(e) => {
-                    error!("{}", e);
-                    String::from("default")
-                }
-            };
+// Play a scenario file, e.g. "raid of 300 followed by chat flood"
+#[tauri::command]
+fn run_mock_scenario(app: AppHandle, path: String, seed: Option<u64>) -> AppResult<()> {
+    mock::start_scenario(app, &path, seed)
+}
 
-            let youtube_api_key = match get("YOUTUBE_API_KEY") {
-                Ok(s) => s,
-                Err(e) => {
-                    error!("{}", e);
-                    String::from("default")
-                }
-            };
+// Push thousands of mock chat messages per second through the real event path
+#[tauri::command]
+fn start_stress_test(app: AppHandle, config: Option<stress::StressConfig>) {
+    stress::start(app, config.unwrap_or_default());
+}
+
+#[tauri::command]
+fn stop_stress_test() {
+    stress::stop();
+}
+
+// Measure latency and throughput of badge processing, serialization and emit
+#[tauri::command]
+async fn run_pipeline_benchmark(app: AppHandle, iterations: Option<u32>, seed: Option<u64>) -> AppResult<stress::BenchmarkReport> {
+    if stress::is_running() {
+        return Err(AppError::config("Stop the stress test before running the benchmark"));
+    }
+
+    let iterations = iterations.unwrap_or(10_000);
+    tauri::async_runtime::spawn_blocking(move || stress::run_benchmark(&app, iterations, seed))
+        .await
+        .map_err(|e| AppError::internal(format!("Benchmark failed: {}", e)))
+}
+
+// Current chat batching and slow chat settings
+#[tauri::command]
+fn get_chat_throttle() -> batcher::BatchConfig {
+    batcher::config()
+}
+
+// Change chat batching and slow chat settings, takes effect immediately
+#[tauri::command]
+fn set_chat_throttle(config: batcher::BatchConfig) -> AppResult<()> {
+    settings::update_with(|settings| settings.chat_throttle = config).map(drop)
+}
+
+#[tauri::command]
+fn get_color_settings() -> colors::ColorSettings {
+    colors::settings()
+}
+
+// Change the chat background used for contrast correction, or turn correction on and off
+#[tauri::command]
+fn set_color_settings(settings: colors::ColorSettings) -> AppResult<()> {
+    settings::update_with(|current| current.colors = settings).map(drop)
+}
+
+#[tauri::command]
+fn get_image_scale() -> image_cache::ImageScale {
+    image_cache::preferred_scale()
+}
+
+// Pick the badge and emote size used for new messages, e.g. "large" for big overlays
+#[tauri::command]
+fn set_image_scale(scale: image_cache::ImageScale) -> AppResult<()> {
+    settings::update_with(|settings| settings.image_scale = scale).map(drop)
+}
+
+// Every Twitch EventSub subscription that can be turned on in the settings
+#[tauri::command]
+fn available_twitch_subscriptions() -> Vec<eventsub::AvailableSubscription> {
+    eventsub::available()
+}
+
+// Which of the selected subscriptions are active and why the others were rejected
+#[tauri::command]
+fn twitch_subscription_status() -> Vec<eventsub::SubscriptionStatus> {
+    eventsub::statuses()
+}
 
-            println!("{}", "Starting YouTube Listener".red().bold());
+#[tauri::command]
+fn get_settings() -> settings::Settings {
+    settings::get()
+}
+
+// Change any part of the settings, e.g. `{"youtube": {"idle_poll_ms": 2000}}`. Applied
+// straight away; the Twitch listener reconnects if its subscriptions or token files
+// changed. Returns the settings now in effect.
+#[tauri::command]
+fn update_settings(app: AppHandle, changes: serde_json::Value) -> AppResult<settings::Settings> {
+    let previous = settings::update(changes)?;
+    let current = settings::get();
+    if current.twitch.needs_restart(&previous.twitch) {
+        restart_twitch_listener(&app);
+    }
+    Ok(current)
+}
 
-            let client = Client::new();
-            
-            match async_youtube_listener(client, youtube_channel_id, youtube_api_key, app_clone2).await {
-                Ok(_) => println!("YouTube listener finished successfully"),
-                Err(e) => println!("YouTube listener error: {}", e),
+#[tauri::command]
+fn start_youtube_listener(app: AppHandle) {
+    spawn_listener(&YOUTUBE_LISTENER, async move {
+        let (youtube_channel_id, youtube_api_key) = match credentials::get().youtube() {
+            Ok(keys) => keys,
+            Err(e) => {
+                error::emit(&app, "youtube-listener", &e);
+                return;
             }
-        });
+        };
+
+        info!("Starting YouTube Listener");
+
+        let client = Client::new();
+
+        match async_youtube_listener(client, youtube_channel_id, youtube_api_key, app.clone()).await {
+            Ok(_) => info!("YouTube listener finished successfully"),
+            Err(e) => error::emit(&app, "youtube-listener", &e),
+        }
     });
 }
 
+// Stop the YouTube listener. Returns whether it was running.
+fn stop_youtube_listener() -> bool {
+    stop_listener(&YOUTUBE_LISTENER, "YouTube")
+}
+
+// Restart the YouTube listener so it picks up new credentials, if it was running
+fn restart_youtube_listener(app: &AppHandle) {
+    if stop_youtube_listener() {
+        start_youtube_listener(app.clone());
+    }
+}
+
 // Separate async function to handle all the YouTube listener logic
 async fn async_youtube_listener(
     client: Client,
     youtube_channel_id: String,
     youtube_api_key: String,
     app: AppHandle,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+) -> AppResult<()> {
     let video_id = get_live_video_id(&client, &youtube_channel_id, &youtube_api_key).await?;
     let chat_id = get_live_chat_id(&client, &video_id, &youtube_api_key).await?;
 
     let mut next_token: Option<String> = None;
 
-    println!("{}", "YouTube Setup successful".red());
+    info!("YouTube Setup successful");
     
     loop {
         let response = fetch_chat_messages(
@@ -418,363 +538,822 @@ async fn async_youtube_listener(
             next_token.as_deref()
         ).await?;
 
-        if response.items.is_none() {
-            println!("{}", "No chat detected. Continuing".red());
-            tokio::time::sleep(Duration::from_secs(1)).await;
+        let poll = settings::get().youtube;
+        let Some(items) = &response.items else {
+            debug!("No chat detected. Continuing");
+            tokio::time::sleep(Duration::from_millis(poll.idle_poll_ms)).await;
             continue;
-        }
-        
-        for message in &response.items.unwrap() {
-            let timestamp = match format_timestamp(&message.snippet.published_at) {
-                Ok(ts) => ts,
-                Err(_) => "??:??:??".to_string()
-            };
-            
-            let username = if message.author_details.is_owner.unwrap_or(false) {
-                message.author_details.display_name.red().bold()
-            } else if message.author_details.is_moderator.unwrap_or(false) {
-                message.author_details.display_name.blue().bold()
-            } else if message.author_details.is_sponsor.unwrap_or(false) {
-                message.author_details.display_name.green().bold()
-            } else {
-                message.author_details.display_name.yellow()
-            };
-            
-            println!("[{}] {}: {}", 
-                timestamp.bright_black(), 
-                username, 
-                message.snippet.display_message);
-
-            let _ = app.emit_all("youtube-chat-message", serde_json::json!({
-                "user": message.author_details.display_name,
-                "color": get_random_color(),
-                "message": message.snippet.display_message,
-                "timestamp": timestamp
-            }));
+        };
+
+        for message in items {
+            handle_youtube_chat_message(&app, message);
         }
 
         if response.polling_interval_millis.is_some() {
             next_token = response.next_page_token.clone();
         }
 
-        // Process outgoing messages
-        let messages = {
-            let mut queue = YOUTUBE_MESSAGE_QUEUE.lock().unwrap();
-            let msgs = queue.drain(..).collect::<Vec<_>>();
-            msgs // Return msgs outside of the lock scope
+        // Process outgoing messages, after the lock is released
+        let messages = match YOUTUBE_MESSAGE_QUEUE.lock() {
+            Ok(mut queue) => queue.drain(..).collect::<Vec<_>>(),
+            Err(e) => {
+                error::emit(&app, "youtube-send", &AppError::internal(format!("Failed to lock YouTube queue: {}", e)));
+                Vec::new()
+            },
         };
-        
-        // Process each message after the lock is released
         for message in messages {
             match youtube_send_chat(&client, &video_id, &message).await {
-                Ok(_) => println!("Sent YouTube chat message: {}", message),
-                Err(e) => println!("Failed to send YouTube chat message: {}", e)
+                Ok(_) => info!("Sent YouTube chat message: {}", message),
+                Err(e) => error::emit(&app, "youtube-send", &e),
             }
         }
-        
-        if response.polling_interval_millis.is_some() {
-            tokio::time::sleep(Duration::from_millis(response.polling_interval_millis.unwrap())).await;
-        } else {
-            tokio::time::sleep(Duration::from_secs(1)).await;
-        } 
+
+        let wait = response.polling_interval_millis.unwrap_or(poll.fallback_poll_ms);
+        tokio::time::sleep(Duration::from_millis(wait)).await;
+    }
+}
+
+// Print and emit a single YouTube chat message. Shared by the live listener and the mock generator.
+fn handle_youtube_chat_message(app: &AppHandle, message: &ChatMessage) {
+    let timestamp = match format_timestamp(&message.snippet.published_at) {
+        Ok(ts) => ts,
+        Err(_) => "??:??:??".to_string()
+    };
+    
+    // Log files don't keep console colors, so roles are spelled out
+    let role = if message.author_details.is_owner.unwrap_or(false) {
+        " (owner)"
+    } else if message.author_details.is_moderator.unwrap_or(false) {
+        " (moderator)"
+    } else if message.author_details.is_sponsor.unwrap_or(false) {
+        " (member)"
+    } else {
+        ""
+    };
+    
+    debug!("[{}] {}{}: {}", 
+        timestamp, 
+        message.author_details.display_name,
+        role,
+        message.snippet.display_message);
+
+    batcher::emit(app, "youtube-chat-message", youtube_chat_payload(message, &timestamp));
+}
+
+// Stable ID for a YouTube chatter; older responses may lack the channel ID
+fn youtube_user_id(message: &ChatMessage) -> &str {
+    message.author_details.channel_id.as_deref()
+        .unwrap_or(&message.author_details.display_name)
+}
+
+// Owner/moderator/member/verified flags, plus the member level when the message has one
+fn youtube_roles(message: &ChatMessage) -> badges::YoutubeRoles {
+    let author = &message.author_details;
+    let milestone = message.snippet.member_milestone_details.as_ref();
+    let member_level = milestone.and_then(|details| details.member_level_name.clone())
+        .or_else(|| message.snippet.new_sponsor_details.as_ref().and_then(|details| details.member_level_name.clone()));
+
+    badges::YoutubeRoles {
+        is_owner: author.is_owner.unwrap_or(false),
+        is_moderator: author.is_moderator.unwrap_or(false),
+        is_member: author.is_sponsor.unwrap_or(false),
+        is_verified: author.is_verified.unwrap_or(false),
+        member_level,
+        member_months: milestone.and_then(|details| details.member_month),
     }
 }
 
+// Build the frontend payload for a YouTube chat message
+fn youtube_chat_payload(message: &ChatMessage, timestamp: &str) -> serde_json::Value {
+    serde_json::json!({
+        "user": message.author_details.display_name,
+        "color": colors::user_color("youtube", youtube_user_id(message), None),
+        "user_id": youtube_user_id(message),
+        "avatar": avatars::youtube_avatar(
+            message.author_details.channel_id.as_deref(),
+            message.author_details.profile_image_url.as_deref(),
+        ),
+        "message": message.snippet.display_message,
+        "timestamp": timestamp,
+        "badges": badges::youtube_badges(&youtube_roles(message)),
+    })
+}
+
 #[tauri::command]
 fn start_twitch_listener(app: AppHandle) {
-    START.call_once(|| {
-        tauri::async_runtime::spawn(async move {
-            let keys = TwitchKeys::from_secrets_env()
-                .expect("Set TWITCH_* env vars");
-
-            let twitch = TwitchEventSubApi::builder(keys.clone())
-                .set_redirect_url("http://localhost:3001")
-                .generate_new_token_if_none(true)
-                .generate_access_token_on_expire(true)
-                .auto_save_load_created_tokens(".user_token.env", ".refresh_token.env")
-                .add_subscriptions(vec![
-                    Subscription::ChatMessage,
-                    Subscription::ChannelFollow
-                ]);
-
-            let mut api = twitch.build()
-                .expect("Failed to build EventSub API");
-
-            println!("Started Twitch Monitoring...");
-            
-            // Initialize badges after API is built and token is available
-            initialize_badges_after_api_built(&app, keys).await;
-            
-            loop {
-                // Process incoming messages
-                let responses = api.receive_all_messages(Some(Duration::from_millis(1)));
-                for response in responses {
-                    match response {
-                        ResponseType::Event(Event::ChatMessage(md)) => {
-                            println!("{} ({}): {}", md.chatter.name, md.colour, md.message.text);
-
-                            let mut color = md.colour; 
-
-                            if color == "" {
-                                // color = String::from("#ffffff");
-                                color = get_random_color();
-                            }
-                            
-                            // Process the badges to get URLs
-                            let processed_badges = badges::process_message_badges(&md.badges);
-                            
-                            // Convert processed badges to a format suitable for JSON
-                            let badge_data = processed_badges.into_iter().map(|badge| {
-                                serde_json::json!({
-                                    "id": badge.id,
-                                    "version": badge.version,
-                                    "image_url": badge.image_url,
-                                    "title": badge.title
-                                })
-                            }).collect::<Vec<_>>();
-
-                            let _ = app.emit_all("twitch-chat-message", serde_json::json!({
-                                "user": md.chatter.name,
-                                "color": color,
-                                "message": md.message.text,
-                                "badges": badge_data,
-                            }));
-                           },
-                        ResponseType::Event(Event::Follow(fd)) => {
-                            println!("{} followed on Twitch!", fd.user.name);
-
-                            let _ = app.emit_all("twitch-follow", serde_json::json!({
-                                "user": fd.user.name
-                            }));
-                        },
-                        _ => {}
-                    }
-                }
+    spawn_listener(&TWITCH_LISTENER, async move {
+        if let Err(e) = run_twitch_listener(&app).await {
+            error::emit(&app, "twitch-listener", &e);
+        }
+    });
+}
 
-                // Send any queued messages
-                if let Ok(mut queue) = TWITCH_MESSAGE_QUEUE.lock() {
-                    while let Some(message) = queue.pop_front() {
-                        match api.send_chat_message(&message) {
-                            Ok(_) => println!("Sent chat message: {}", message),
-                            Err(_e) => println!("Failed to send chat message")
-                        }
-                    }
-                }
+// Stop the Twitch listener, turning off raid slow mode it left on. Returns whether it
+// was running.
+fn stop_twitch_listener() -> bool {
+    if let Ok(client) = http_client() {
+        raids::end_slow_mode(&client);
+    }
+    stop_listener(&TWITCH_LISTENER, "Twitch")
+}
+
+// Run a listener in `slot` unless one is running already
+fn spawn_listener(slot: &'static ListenerSlot, task: impl std::future::Future<Output = ()> + Send + 'static) {
+    let Ok(mut listener) = slot.lock() else {
+        return;
+    };
+    if listener.is_some() {
+        return;
+    }
+
+    let generation = LISTENER_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let handle = tauri::async_runtime::spawn(async move {
+        task.await;
 
-                tokio::time::sleep(Duration::from_millis(100)).await;
+        // Allow starting again after a failure, unless a newer listener has taken over
+        if let Ok(mut listener) = slot.lock() {
+            if listener.as_ref().map(|task| task.0 == generation).unwrap_or(false) {
+                *listener = None;
             }
-        });
+        }
     });
+    *listener = Some((generation, handle));
 }
 
-// Helper function to initialize badges after API is built
-async fn initialize_badges_after_api_built(app: &AppHandle, keys: TwitchKeys) {
-    // Wait a moment to ensure token files are written
-    tokio::time::sleep(Duration::from_secs(2)).await;
+fn stop_listener(slot: &'static ListenerSlot, platform: &str) -> bool {
+    let task = slot.lock().ok().and_then(|mut listener| listener.take());
+    match task {
+        Some((_, handle)) => {
+            handle.abort();
+            info!("Stopped {} listener", platform);
+            true
+        },
+        None => false,
+    }
+}
+
+// Restart the Twitch listener so it picks up new credentials, if it was running
+fn restart_twitch_listener(app: &AppHandle) {
+    if stop_twitch_listener() {
+        start_twitch_listener(app.clone());
+    }
+}
+
+// twitch_eventsub writes the access token to "./" followed by the path it's given, so the
+// token files are handed over relative to the working directory
+fn relative_to_working_dir(path: &Path) -> PathBuf {
+    let Ok(working_dir) = std::env::current_dir() else {
+        return path.to_path_buf();
+    };
+    let base = working_dir.components().collect::<Vec<_>>();
+    let target = path.components().collect::<Vec<_>>();
+    let common = base.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        // Not absolute, or on another drive
+        return path.to_path_buf();
+    }
+
+    let mut relative = std::iter::repeat_n(Component::ParentDir, base.len() - common).collect::<PathBuf>();
+    relative.extend(&target[common..]);
+    relative
+}
+
+async fn run_twitch_listener(app: &AppHandle) -> AppResult<()> {
+    let keys = credentials::get().twitch_keys()?;
+    let [token_file, refresh_file] = credentials::twitch_token_files();
+    let client = http_client()?;
+    raids::resume_slow_mode(app, &client);
+
+    // Only ask for what the token allows. Without a token the library logs in through the
+    // browser redirect and asks for every scope the subscriptions need.
+    let token_scopes = match &keys.access_token {
+        Some(TokenAccess::User(token)) => match twitch_auth::validate_token(&client, token).await {
+            Ok(info) => Some(info.scopes),
+            Err(e) => {
+                warn!("Couldn't check the Twitch token's scopes: {}", e);
+                None
+            },
+        },
+        _ => {
+            twitch_auth::check_redirect_port()?;
+            None
+        },
+    };
+    let (subscriptions, statuses) = eventsub::check(&twitch_subscriptions(), token_scopes.as_deref());
+    eventsub::publish(app, statuses);
+    if subscriptions.is_empty() {
+        return Err(AppError::config("None of the selected Twitch subscriptions can be used, check the subscriptions in the settings"));
+    }
+
+    let twitch = TwitchEventSubApi::builder(keys)
+        .set_redirect_url(twitch_auth::redirect_url())
+        .generate_new_token_if_none(true)
+        .generate_access_token_on_expire(true)
+        .auto_save_load_created_tokens(
+            relative_to_working_dir(&token_file).to_string_lossy(),
+            relative_to_working_dir(&refresh_file).to_string_lossy(),
+        )
+        .add_subscriptions(subscriptions);
+
+    let mut api = twitch.build().map_err(AppError::from_twitch)?;
+
+    info!("Started Twitch Monitoring...");
+
+    // Keep any token the library generated while logging in
+    credentials::absorb_twitch_token_files();
     
-    println!("Attempting to initialize badges after Twitch API startup");
+    // Initialize badges after API is built and token is available
+    initialize_badges_after_api_built(app).await;
+
+    // Hype Chats are only visible over IRC; the watcher stops when this listener ends
+    let _hype_chat = match credentials::get().twitch_api() {
+        Ok((_, _, token)) => twitch_auth::validate_token(&client, &token).await.ok()
+            .and_then(|info| info.login)
+            .map(|login| hype_chat::start(app, login)),
+        Err(_) => None,
+    };
+
+    let mut last_token_check = std::time::Instant::now();
     
-    // Get client_id directly from keys (it's not an Option)
-    let client_id = keys.client_id;
+    loop {
+        // Process incoming messages
+        let responses = api.receive_all_messages(Some(Duration::from_millis(1)));
+        for response in responses {
+            match response {
+                ResponseType::Event(Event::ChatMessage(md)) => {
+                    handle_twitch_chat_message(app, md);
+                },
+                ResponseType::Event(Event::Follow(fd)) => {
+                    info!("{} followed on Twitch!", fd.user.name);
+
+                    let _ = app.emit_all("twitch-follow", serde_json::json!({
+                        "user": fd.user.name
+                    }));
+                },
+                ResponseType::Event(Event::NewSubscription(sd)) => {
+                    twitch_events::emit_subscription(app, (&sd).into());
+                },
+                ResponseType::Event(Event::Resubscription(rd)) => {
+                    twitch_events::emit_subscription(app, (&rd).into());
+                },
+                ResponseType::Event(Event::GiftSubscription(gd)) => {
+                    twitch_events::emit_subscription(app, (&gd).into());
+                },
+                ResponseType::Event(Event::Raid(rd)) => {
+                    twitch_events::emit_raid(app, (&rd).into());
+
+                    if let Some(message) = raids::welcome(app, &client, &settings::get().twitch.raid_welcome, &rd) {
+                        if let Ok(mut queue) = TWITCH_MESSAGE_QUEUE.lock() {
+                            queue.push_back(message);
+                        }
+                    }
+                },
+                ResponseType::Event(Event::PointsCustomRewardRedeem(rd)) => {
+                    twitch_events::emit_redemption(app, (&rd).into());
+                },
+                ResponseType::Event(Event::Cheer(cd)) => {
+                    twitch_events::emit_cheer(app, &(&cd).into());
+                },
+                ResponseType::RawResponse(raw) => {
+                    twitch_events::handle_raw(app, &raw);
+                },
+                ResponseType::Error(e) => {
+                    error::emit(app, "twitch-listener", &AppError::from_twitch(e));
+                },
+                // The library has sent all subscription requests
+                ResponseType::Ready => {
+                    if let Ok((client_id, _, token)) = credentials::get().twitch_api() {
+                        if let Err(e) = eventsub::confirm(app, &client, &client_id, &token).await {
+                            warn!("Couldn't confirm Twitch subscriptions: {}", e);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        // Send any queued messages
+        if let Ok(mut queue) = TWITCH_MESSAGE_QUEUE.lock() {
+            while let Some(message) = queue.pop_front() {
+                match api.send_chat_message(&message) {
+                    Ok(_) => info!("Sent chat message: {}", message),
+                    Err(e) => error::emit(app, "twitch-send", &AppError::from_twitch(e)),
+                }
+            }
+        }
+
+        // The library refreshes expired tokens by itself and writes them to its token files
+        if last_token_check.elapsed() >= TOKEN_CHECK_INTERVAL {
+            last_token_check = std::time::Instant::now();
+            if credentials::absorb_twitch_token_files() {
+                use_new_twitch_token();
+            }
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+// Point avatar lookups and badge refreshes at a refreshed Twitch token
+fn use_new_twitch_token() {
+    if let Ok((client_id, broadcaster_id, access_token)) = credentials::get().twitch_api() {
+        avatars::set_twitch_credentials(client_id.clone(), access_token.clone());
+        badges::start_background_refresh(client_id, access_token, broadcaster_id);
+    }
+}
+
+// EventSub subscriptions the Twitch listener asks for
+fn twitch_subscriptions() -> Vec<Subscription> {
+    settings::get().twitch.subscriptions()
+}
+
+// Print and emit a single Twitch chat message. Shared by the live listener and the mock generator.
+fn handle_twitch_chat_message(app: &AppHandle, md: MessageData) {
+    debug!("{} ({}): {}", md.chatter.name, md.colour, md.message.text);
+
+    batcher::emit(app, "twitch-chat-message", twitch_chat_payload(&md));
+}
+
+// Build the frontend payload for a Twitch chat message, resolving badge URLs
+fn twitch_chat_payload(md: &MessageData) -> serde_json::Value {
+    // Chatters without a Twitch color get one derived from their user ID
+    let color = colors::user_color("twitch", &md.chatter.id, Some(&md.colour));
     
-    // Get broadcaster_id directly from keys (it's not an Option)
-    let broadcaster_id = keys.broadcaster_account_id;
+    // Process the badges to get URLs
+    let processed_badges = badges::process_message_badges(&md.broadcaster.id, &md.badges);
     
-    // For the token, we still need to read from file as TwitchKeys.access_token
-    // is an Option<TokenAccess> which doesn't directly give us the string we need
-    let access_token = match std::fs::read_to_string(".user_token.env") {
-        Ok(content) => {
-            let token = content.trim();
-            if token.is_empty() {
-                let error = "Token file is empty";
-                println!("{}", error);
-                let _ = app.emit_all("badges-initialization-failed", error);
-                return;
-            }
-            token.to_string()
-        },
+    // Convert processed badges to a format suitable for JSON
+    let badge_data = processed_badges.into_iter().map(|badge| {
+        serde_json::json!({
+            "id": badge.id,
+            "version": badge.version,
+            "image_url": badge.image_url,
+            "srcset": badge.srcset,
+            "title": badge.title
+        })
+    }).collect::<Vec<_>>();
+
+    serde_json::json!({
+        "user": md.chatter.name,
+        "user_id": md.chatter.id,
+        "avatar": avatars::twitch_avatar(&md.chatter.id),
+        "color": color,
+        "message": md.message.text,
+        "fragments": emotes::twitch_fragments(&md.broadcaster.id, &md.message),
+        "badges": badge_data,
+        "message_id": md.message_id,
+        // Bits cheered with the message, if any
+        "bits": md.cheer.as_ref().map(|cheer| cheer.bits),
+        // Amount and level if the message was a Hype Chat the IRC side already saw; later
+        // ones follow as `twitch-hype-chat` events
+        "hype_chat": hype_chat::take(&md.message_id),
+    })
+}
+
+// Helper function to initialize badges after API is built
+async fn initialize_badges_after_api_built(app: &AppHandle) {
+    // Badges saved by the last run are already loaded, so there's no rush here;
+    // the token is in the credential store by the time build() returns
+    info!("Attempting to initialize badges after Twitch API startup");
+
+    let (client_id, broadcaster_id, access_token) = match credentials::get().twitch_api() {
+        Ok(credentials) => credentials,
         Err(e) => {
-            let error = format!("Error reading token file: {}", e);
-            println!("{}", error);
-            let _ = app.emit_all("badges-initialization-failed", error);
+            error::emit(app, "badges", &e);
+            let _ = app.emit_all("badges-initialization-failed", e.to_string());
             return;
         }
     };
-    
-    println!("Using keys directly - client_id length: {}, broadcaster_id: {}, token length: {}", 
+
+    debug!("Using stored credentials - client_id length: {}, broadcaster_id: {}, token length: {}", 
             client_id.len(), broadcaster_id, access_token.len());
+
+    avatars::set_twitch_credentials(client_id.clone(), access_token.clone());
     
-    // Initialize badges
-    match badges::initialize_badges(&client_id, &access_token, &broadcaster_id).await {
+    // Initialize badges, skipping the fetch if the ones on disk are still fresh
+    match badges::refresh_if_stale(&client_id, &access_token, &broadcaster_id).await {
         Ok(_) => {
-            println!("Successfully initialized badges after API startup");
+            info!("Successfully initialized badges after API startup");
             // Emit an event to the frontend to notify that badges are ready
             let _ = app.emit_all("badges-initialized", true);
         },
         Err(e) => {
-            println!("Failed to initialize badges after API startup: {}", e);
+            error::emit(app, "badges", &e);
             // Emit an event to the frontend to notify that badge initialization failed
-            let _ = app.emit_all("badges-initialization-failed", e);
+            let _ = app.emit_all("badges-initialization-failed", e.to_string());
         }
     }
+
+    // Cheermote images for cheers in chat and the activity feed
+    if let Err(e) = cheermotes::refresh_if_stale(&client_id, &access_token, &broadcaster_id).await {
+        warn!("Couldn't load cheermotes: {}", e);
+    }
+
+    badges::start_background_refresh(client_id, access_token, broadcaster_id);
 }
 
-// Internal function that implements the badge initialization logic
-async fn initialize_badges_from_env_internal() -> Result<(), String> {
-    // Get client ID from .secrets.env
-    let client_id = match env::var("TWITCH_CLIENT_ID") {
-        Ok(id) => id,
-        Err(_) => return Err("TWITCH_CLIENT_ID not found in environment".to_string()),
-    };
-    
-    // Get broadcaster ID from .secrets.env
-    let broadcaster_id = match env::var("TWITCH_CHANNEL_ID") {
-        Ok(id) => id,
-        Err(_) => return Err("TWITCH_CHANNEL_ID not found in environment".to_string()),
-    };
-    
-    // Try to read access token from .user_token.env
-    let access_token = match std::fs::read_to_string(".user_token.env") {
-        Ok(content) => {
-            println!("Found user token file");
-            let token = content.trim();
-            if token.is_empty() {
-                return Err("Token file is empty".to_string());
-            }
-            token.to_string()
-        },
-        Err(e) => {
-            println!("Error reading user token file: {}", e);
-            // Try loading .secrets.env for access token
-            match env::var("TWITCH_USER_TOKEN") {
-                Ok(token) => {
-                    println!("Using TWITCH_USER_TOKEN from environment");
-                    token
-                },
-                Err(_) => {
-                    return Err(format!("Could not read access token from any source: {}", e));
-                }
-            }
-        }
-    };
-    
-    println!("Using client_id length: {}, broadcaster_id: {}, token length: {}", 
+// Client ID, broadcaster ID and user token from the credential store
+fn twitch_badge_credentials() -> AppResult<(String, String, String)> {
+    let (client_id, broadcaster_id, access_token) = credentials::get().twitch_api()?;
+
+    debug!("Using client_id length: {}, broadcaster_id: {}, token length: {}", 
             client_id.len(), broadcaster_id, access_token.len());
-    
+
+    Ok((client_id, broadcaster_id, access_token))
+}
+
+// Internal function that implements the badge initialization logic
+async fn initialize_badges_from_env_internal() -> AppResult<()> {
+    let (client_id, broadcaster_id, access_token) = twitch_badge_credentials()?;
+
     // Initialize badges
-    badges::initialize_badges(&client_id, &access_token, &broadcaster_id).await
+    badges::refresh_badges(client_id, access_token, broadcaster_id).await
 }
 
-// Command to save API keys to a secure file
+// Command to save API keys to the credential store
 #[tauri::command]
 fn save_api_keys(
-    app_handle: tauri::AppHandle,
     twitch_client_id: String,
     twitch_client_secret: String,
     twitch_broadcaster_id: String,
     youtube_channel_id: String,
     youtube_api_key: String,
-) -> Result<(), String> {
-    let contents = format!(
-        "TWITCH_CLIENT_ID={}\nTWITCH_CLIENT_SECRET={}\nTWITCH_BROADCASTER_ID={}\nYOUTUBE_CHANNEL_ID={}\nYOUTUBE_API_KEY={}",
-        twitch_client_id, twitch_client_secret, twitch_broadcaster_id, youtube_channel_id, youtube_api_key
-    );
-    
-    // Use the app's resource path for better security
-    let resource_path = app_handle.path_resolver().app_config_dir()
-        .ok_or_else(|| "Could not find config directory".to_string())?;
-    
-    // Create directory if it doesn't exist
-    match fs::create_dir_all(&resource_path) {
-        Ok(_) => {},
-        Err(e) => return Err(format!("Failed to create config directory: {}", e))
+) -> AppResult<()> {
+    credentials::update(|credentials| {
+        // Tokens belong to the app they were issued for
+        if credentials.twitch_client_id != twitch_client_id.trim() {
+            credentials.twitch_user_token.clear();
+            credentials.twitch_refresh_token.clear();
+        }
+        credentials.twitch_client_id = twitch_client_id.trim().to_string();
+        credentials.twitch_client_secret = twitch_client_secret.trim().to_string();
+        credentials.twitch_broadcaster_id = twitch_broadcaster_id.trim().to_string();
+        credentials.youtube_channel_id = youtube_channel_id.trim().to_string();
+        credentials.youtube_api_key = youtube_api_key.trim().to_string();
+    })?;
+
+    info!("Saved API keys");
+    Ok(())
+}
+
+// Command to read API keys from the credential store
+#[tauri::command]
+fn read_api_keys() -> serde_json::Value {
+    credentials::get().api_keys()
+}
+
+// Check API keys and the Twitch token against Twitch and YouTube without saving anything,
+// with a result for each field. Keys that aren't passed are taken from the saved ones.
+#[tauri::command]
+async fn validate_credentials(
+    twitch_client_id: Option<String>,
+    twitch_client_secret: Option<String>,
+    twitch_broadcaster_id: Option<String>,
+    youtube_channel_id: Option<String>,
+    youtube_api_key: Option<String>,
+) -> AppResult<validation::ValidationReport> {
+    if credentials::is_locked() {
+        return Err(secrets::locked_error());
     }
-    
-    let secrets_path = resource_path.join(".secrets.env");
-    
-    match fs::write(&secrets_path, contents) {
-        Ok(_) => {
-            // Also write to current directory for compatibility with existing code
-            // match fs::write("./.secrets.env", contents) {
-            //     Ok(_) => Ok(()),
-            //     Err(e) => Err(format!("Failed to save API keys to current directory: {}", e))
-            // }
-            println!("Saved API keys to {}", secrets_path.display());
-            Ok(())
-        },
-        Err(e) => Err(format!("Failed to save API keys: {}", e))
+
+    let mut credentials = credentials::get();
+    let typed = [
+        (&mut credentials.twitch_client_id, twitch_client_id),
+        (&mut credentials.twitch_client_secret, twitch_client_secret),
+        (&mut credentials.twitch_broadcaster_id, twitch_broadcaster_id),
+        (&mut credentials.youtube_channel_id, youtube_channel_id),
+        (&mut credentials.youtube_api_key, youtube_api_key),
+    ];
+    for (field, value) in typed {
+        if let Some(value) = value {
+            *field = value;
+        }
     }
+
+    let client = http_client()?;
+    let scopes = validation::required_scopes(&twitch_subscriptions());
+
+    Ok(validation::validate(&client, &validation::Endpoints::default(), &credentials, &scopes).await)
+}
+
+fn http_client() -> AppResult<Client> {
+    Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| AppError::internal(format!("Failed to create HTTP client: {}", e)))
 }
 
-// Command to read API keys from the secure file
+// Login, scopes and expiry of the stored Twitch token
 #[tauri::command]
-fn read_api_keys(app_handle: tauri::AppHandle) -> Result<serde_json::Value, String> {
-    // Try app config directory first
-    let resource_path = app_handle.path_resolver().app_config_dir()
-        .ok_or_else(|| "Could not find config directory".to_string())?;
-    
-    let secrets_path = resource_path.join(".secrets.env");
-    
-    let path = if secrets_path.exists() {
-        secrets_path
-    } else {
-        // Fallback to current directory
-        PathBuf::from("./.secrets.env")
+async fn twitch_token_status() -> AppResult<twitch_auth::TokenStatus> {
+    twitch_auth::token_status(&http_client()?).await
+}
+
+// Get a new Twitch access token from the refresh token now instead of waiting for expiry
+#[tauri::command]
+async fn refresh_twitch_token(app: AppHandle) -> AppResult<twitch_auth::TokenStatus> {
+    let status = twitch_auth::refresh(&http_client()?).await?;
+    use_new_twitch_token();
+    restart_twitch_listener(&app);
+    Ok(status)
+}
+
+// Revoke the Twitch token and forget it. The listener stops until the next login.
+#[tauri::command]
+async fn logout_twitch() -> AppResult<()> {
+    let client = http_client()?;
+    // Raid slow mode needs the token to go off again, so wait for it before revoking
+    if let Some(task) = raids::end_slow_mode(&client) {
+        let _ = task.await;
+    }
+    stop_twitch_listener();
+    twitch_auth::revoke(&client).await
+}
+
+// Log in to Twitch again in the browser, asking for the scopes chat needs plus the ones
+// for the given features, e.g. ["moderation", "channel_points"]
+#[tauri::command]
+async fn authorize_twitch(app: AppHandle, permissions: Option<Vec<twitch_auth::Permission>>) -> AppResult<twitch_auth::TokenStatus> {
+    let client = http_client()?;
+    let scopes = twitch_auth::scopes_for(&client, &twitch_subscriptions(), &permissions.unwrap_or_default()).await;
+
+    let status = twitch_auth::authorize(&app, &client, &scopes).await?;
+    use_new_twitch_token();
+    restart_twitch_listener(&app);
+    Ok(status)
+}
+
+// Log in with a code entered on twitch.tv/activate instead of a local redirect. Returns
+// the code to show; a `twitch-login` event follows once the login completes or fails.
+#[tauri::command]
+async fn start_twitch_device_login(app: AppHandle, permissions: Option<Vec<twitch_auth::Permission>>) -> AppResult<twitch_auth::DeviceLogin> {
+    let client = http_client()?;
+    let scopes = twitch_auth::scopes_for(&client, &twitch_subscriptions(), &permissions.unwrap_or_default()).await;
+
+    twitch_auth::start_device_login(app, client, scopes, |app| {
+        use_new_twitch_token();
+        restart_twitch_listener(app);
+    }).await
+}
+
+#[tauri::command]
+fn cancel_twitch_device_login() {
+    twitch_auth::cancel_device_login();
+}
+
+#[tauri::command]
+fn get_twitch_redirect_port() -> u16 {
+    twitch_auth::redirect_port()
+}
+
+// Port for the browser login redirect, for when another local tool already uses it.
+// The Twitch app needs http://localhost:<port> as a redirect URL too.
+#[tauri::command]
+fn set_twitch_redirect_port(port: u16) -> AppResult<()> {
+    settings::update_with(|settings| settings.twitch.redirect_port = port).map(drop)
+}
+
+// Save the current profile's settings, and its keys and tokens if `include_secrets`, to a
+// JSON file. Writes to `path` if given, otherwise into the config folder's `exports`.
+// Returns where the file went.
+#[tauri::command]
+fn export_config(app: AppHandle, path: Option<String>, include_secrets: Option<bool>) -> AppResult<String> {
+    let destination = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let config_dir = app.path_resolver().app_config_dir()
+                .ok_or_else(|| AppError::config("Could not find config directory"))?;
+            config_dir.join("exports").join(format!(
+                "{}-{}.json",
+                profiles::active().replace(' ', "_"),
+                Local::now().format("%Y%m%d-%H%M%S")
+            ))
+        },
     };
-    
-    if !path.exists() {
-        return Ok(serde_json::json!({
-            "TWITCH_CLIENT_ID": "",
-            "TWITCH_CLIENT_SECRET": "",
-            "TWITCH_BROADCASTER_ID": "",
-            "YOUTUBE_CHANNEL_ID": "",
-            "YOUTUBE_API_KEY": ""
-        }));
+
+    config_export::export(&destination, include_secrets.unwrap_or(false))
+        .map(|path| path.display().to_string())
+}
+
+// Load an exported config into the current profile and restart what it affects
+#[tauri::command]
+fn import_config(app: AppHandle, path: String) -> AppResult<config_export::ImportResult> {
+    let result = config_export::import(&PathBuf::from(path))?;
+
+    if result.credentials {
+        use_new_twitch_token();
+        restart_youtube_listener(&app);
     }
-    
-    let file = match fs::File::open(&path) {
-        Ok(file) => file,
-        Err(e) => return Err(format!("Failed to open secrets file: {}", e))
+    if result.credentials || settings::get().twitch.needs_restart(&result.previous.twitch) {
+        restart_twitch_listener(&app);
+    }
+    let _ = app.emit_all("settings-changed", settings::get());
+    Ok(result)
+}
+
+#[tauri::command]
+fn list_profiles() -> profiles::ProfileList {
+    profiles::list()
+}
+
+// Add a profile, e.g. for a co-stream or test channel. It starts without keys; its
+// settings are a copy of the current profile's if `copy_settings` is set.
+#[tauri::command]
+fn create_profile(name: String, copy_settings: Option<bool>) -> AppResult<profiles::ProfileList> {
+    profiles::create(&name, copy_settings.unwrap_or(false))?;
+    Ok(profiles::list())
+}
+
+// Switch to another profile. Running listeners are stopped first and started again with
+// the new profile's credentials and settings.
+#[tauri::command]
+fn switch_profile(app: AppHandle, name: String) -> AppResult<profiles::ProfileList> {
+    if name == profiles::active() {
+        return Ok(profiles::list());
+    }
+
+    let twitch_running = stop_twitch_listener();
+    let youtube_running = stop_youtube_listener();
+    twitch_auth::cancel_device_login();
+    // Tokens the old listener just refreshed still belong to the old profile
+    credentials::absorb_twitch_token_files();
+    TWITCH_MESSAGE_QUEUE.lock().map(|mut queue| queue.clear()).ok();
+    YOUTUBE_MESSAGE_QUEUE.lock().map(|mut queue| queue.clear()).ok();
+
+    // The profile is active even if its credentials couldn't be read; they stay locked
+    let result = profiles::switch(&name);
+    let switched = profiles::active() == name;
+    if result.is_ok() {
+        use_new_twitch_token();
+    }
+
+    if twitch_running {
+        start_twitch_listener(app.clone());
+    }
+    if youtube_running {
+        start_youtube_listener(app.clone());
+    }
+
+    let list = profiles::list();
+    if switched {
+        let _ = app.emit_all("profile-changed", &list);
+    }
+    result?;
+    Ok(list)
+}
+
+#[tauri::command]
+fn delete_profile(name: String) -> AppResult<profiles::ProfileList> {
+    profiles::delete(&name)?;
+    Ok(profiles::list())
+}
+
+// Whether the credential store is locked, how it's encrypted and which old plaintext
+// files still hold keys
+#[tauri::command]
+fn credentials_status() -> credentials::CredentialsStatus {
+    credentials::status()
+}
+
+// Unlock a passphrase protected credential store for this session
+#[tauri::command]
+fn unlock_credentials(passphrase: String) -> AppResult<()> {
+    credentials::unlock(&passphrase)
+}
+
+// Protect the credential store with a passphrase, or go back to the system keyring
+// (or key file) by passing none
+#[tauri::command]
+fn set_credentials_passphrase(passphrase: Option<String>) -> AppResult<()> {
+    credentials::set_passphrase(passphrase.as_deref())
+}
+
+// Delete the plaintext `.secrets.env` and token files left over from older versions
+#[tauri::command]
+fn remove_plaintext_credentials() -> AppResult<Vec<String>> {
+    credentials::remove_plaintext_files()
+}
+
+// Zip the log files and a redacted copy of the config for attaching to bug reports.
+// Writes to `path` if given, otherwise into the log folder. Returns where the zip went.
+#[tauri::command]
+fn export_diagnostics(app_handle: tauri::AppHandle, path: Option<String>) -> AppResult<String> {
+    let destination = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let log_dir = app_handle.path_resolver().app_log_dir()
+                .ok_or_else(|| AppError::config("Could not find log directory"))?;
+            log_dir.join(format!("diagnostics-{}.zip", Local::now().format("%Y%m%d-%H%M%S")))
+        },
     };
-    
-    let reader = BufReader::new(file);
-    let mut api_keys = serde_json::json!({
-        "TWITCH_CLIENT_ID": "",
-        "TWITCH_CLIENT_SECRET": "",
-        "TWITCH_BROADCASTER_ID": "",
-        "YOUTUBE_CHANNEL_ID": "",
-        "YOUTUBE_API_KEY": ""
+
+    // Only whether each key is set, never the values
+    let api_keys = read_api_keys();
+    let keys_set = api_keys.as_object()
+        .map(|keys| keys.iter()
+            .map(|(name, value)| (name.clone(), serde_json::json!(value.as_str().map(|v| !v.is_empty()).unwrap_or(false))))
+            .collect::<serde_json::Map<_, _>>())
+        .unwrap_or_default();
+
+    let config = serde_json::json!({
+        "version": app_handle.package_info().version.to_string(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "api_keys_set": keys_set,
+        "profile": profiles::active(),
+        "settings": settings::get(),
     });
-    
-    for line in reader.lines() {
-        let line = match line {
-            Ok(line) => line,
-            Err(e) => return Err(format!("Failed to read line: {}", e))
-        };
-        
-        if let Some((key, value)) = line.split_once('=') {
-            if !value.is_empty() {
-                api_keys[key] = serde_json::Value::String(value.to_string());
-            }
-        }
-    }
-    
-    Ok(api_keys)
+
+    logging::export_diagnostics(&destination, &config)
+        .map(|path| path.display().to_string())
 }
 
 fn main() {
+    logging::init();
+
     tauri::Builder::default()
+        .setup(|app| {
+            if let Some(log_dir) = app.path_resolver().app_log_dir() {
+                logging::init_file(&log_dir);
+            }
+            if let Err(e) = profiles::init(app.path_resolver().app_config_dir(), app.path_resolver().app_data_dir()) {
+                error::emit(&app.handle(), "credentials", &e);
+            }
+            batcher::start(app.handle());
+            settings::watch(app.handle(), |app, previous| {
+                if settings::get().twitch.needs_restart(&previous.twitch) {
+                    restart_twitch_listener(app);
+                }
+            });
+
+            // Load badges, avatars and images saved by previous runs so they render straight away
+            let data_dir = app.path_resolver().app_data_dir();
+            if let Some(data_dir) = &data_dir {
+                if let Err(e) = image_cache::init(data_dir.join("images")) {
+                    warn!("{}", e);
+                }
+            }
+            avatars::start(app.handle(), data_dir);
+            Ok(())
+        })
+        .register_uri_scheme_protocol(image_cache::SCHEME, |_app, request| {
+            image_cache::handle_request(request)
+        })
         .invoke_handler(tauri::generate_handler![
             greet, 
             start_twitch_listener, 
             send_chat_message,
             start_mock_events,
+            stop_mock_events,
+            run_mock_scenario,
+            start_stress_test,
+            stop_stress_test,
+            run_pipeline_benchmark,
+            get_chat_throttle,
+            set_chat_throttle,
+            get_color_settings,
+            set_color_settings,
+            get_image_scale,
+            set_image_scale,
+            export_config,
+            import_config,
+            list_profiles,
+            create_profile,
+            switch_profile,
+            delete_profile,
+            available_twitch_subscriptions,
+            twitch_subscription_status,
+            get_settings,
+            update_settings,
             initialize_twitch_badges,
             initialize_badges_from_env,
+            refresh_badges,
             save_api_keys,
             read_api_keys,
+            validate_credentials,
+            twitch_token_status,
+            refresh_twitch_token,
+            logout_twitch,
+            authorize_twitch,
+            start_twitch_device_login,
+            cancel_twitch_device_login,
+            get_twitch_redirect_port,
+            set_twitch_redirect_port,
+            credentials_status,
+            unlock_credentials,
+            set_credentials_passphrase,
+            remove_plaintext_credentials,
+            export_diagnostics,
             start_youtube_listener,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            // Don't leave raid slow mode on in chat when the app closes
+            if let tauri::RunEvent::Exit = event {
+                if let Some(task) = http_client().ok().and_then(|client| raids::end_slow_mode(&client)) {
+                    let _ = tauri::async_runtime::block_on(task);
+                }
+            }
+        });
 }
\ No newline at end of file
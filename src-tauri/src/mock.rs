@@ -0,0 +1,593 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use log::info;
+use once_cell::sync::Lazy;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::error::{AppError, AppResult};
+use crate::twitch_events::{self, RaidEvent, SubscriptionEvent};
+use tokio::time::sleep;
+use twitch_eventsub::{Badge, FragmentType, Fragments, Message, MessageData, MessageType, User};
+
+use crate::{AuthorDetails, ChatMessage, MessageSnippet};
+
+// Handles for the running generator and scenario so they can be stopped again
+static GENERATOR_TASK: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+static SCENARIO_TASK: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+
+// Word lists used to build mock usernames and chat lines
+const NAME_PREFIXES: &[&str] = &["Pixel", "Shadow", "Turbo", "Cozy", "Lucky", "Salty", "Night", "Retro"];
+const NAME_SUFFIXES: &[&str] = &["Gamer", "Fox", "Wizard", "Potato", "Ninja", "Owl", "Panda", "Knight"];
+const CHAT_LINES: &[&str] = &[
+    "Hello everyone!",
+    "LUL",
+    "That was an amazing play!",
+    "gg",
+    "What game is this?",
+    "First time here, love the stream",
+    "PogChamp",
+    "Can't wait to see what happens next",
+    "lmao",
+    "Keep up the good work!",
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Twitch,
+    Youtube,
+}
+
+// Average number of events per minute for each event type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MockRates {
+    pub twitch_chat: f64,
+    pub youtube_chat: f64,
+    pub follow: f64,
+    pub subscription: f64,
+    pub donation: f64,
+    pub raid: f64,
+}
+
+impl Default for MockRates {
+    fn default() -> Self {
+        // Roughly matches the old generator: one follow, sub or donation every 5-15 seconds
+        MockRates {
+            twitch_chat: 0.0,
+            youtube_chat: 0.0,
+            follow: 2.0,
+            subscription: 2.0,
+            donation: 2.0,
+            raid: 0.0,
+        }
+    }
+}
+
+impl MockRates {
+    fn total(&self) -> f64 {
+        self.weights().iter().map(|(_, rate)| rate.max(0.0)).sum()
+    }
+
+    fn weights(&self) -> [(MockEventKind, f64); 6] {
+        [
+            (MockEventKind::TwitchChat, self.twitch_chat),
+            (MockEventKind::YoutubeChat, self.youtube_chat),
+            (MockEventKind::Follow, self.follow),
+            (MockEventKind::Subscription, self.subscription),
+            (MockEventKind::Donation, self.donation),
+            (MockEventKind::Raid, self.raid),
+        ]
+    }
+}
+
+// Configuration passed from the frontend when starting the generator
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MockConfig {
+    pub seed: Option<u64>,
+    pub rates: MockRates,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MockEventKind {
+    TwitchChat,
+    YoutubeChat,
+    Follow,
+    Subscription,
+    Donation,
+    Raid,
+}
+
+// A single mock event. Any field left out is filled in by the generator's RNG,
+// so scenario files only need to specify what they care about.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MockEvent {
+    TwitchChat {
+        user: Option<String>,
+        message: Option<String>,
+    },
+    YoutubeChat {
+        user: Option<String>,
+        message: Option<String>,
+    },
+    // Many chat messages spread evenly over a time window
+    ChatFlood {
+        platform: Platform,
+        count: u32,
+        duration_ms: u64,
+    },
+    Follow {
+        user: Option<String>,
+    },
+    Subscription {
+        user: Option<String>,
+        tier: Option<u8>,
+        is_gift: Option<bool>,
+    },
+    Donation {
+        user: Option<String>,
+        amount: Option<f64>,
+        message: Option<String>,
+    },
+    Raid {
+        from: Option<String>,
+        viewers: Option<u32>,
+    },
+    // Changes the background rates for the rest of the scenario
+    SetRates {
+        rates: MockRates,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScenarioStep {
+    // Delay before this step runs
+    #[serde(default)]
+    pub wait_ms: u64,
+    // How many times to run the event
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    #[serde(flatten)]
+    pub event: MockEvent,
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+// A scripted sequence of mock events loaded from a JSON file
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub steps: Vec<ScenarioStep>,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> AppResult<Scenario> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AppError::config(format!("Failed to read scenario {}: {}", path.display(), e)))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| AppError::config(format!("Failed to parse scenario {}: {}", path.display(), e)))
+    }
+}
+
+// Seedable generator that turns rates and scenario steps into emitted events
+pub struct MockGenerator {
+    rng: StdRng,
+    rates: MockRates,
+    message_counter: u64,
+}
+
+impl MockGenerator {
+    pub fn new(seed: Option<u64>, rates: MockRates) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        MockGenerator {
+            rng,
+            rates,
+            message_counter: 0,
+        }
+    }
+
+    // Time until the next background event, or None if all rates are zero
+    fn next_delay(&mut self) -> Option<Duration> {
+        let total = self.rates.total();
+        if total <= 0.0 {
+            return None;
+        }
+
+        // Exponential inter-arrival times give a Poisson process at the configured rate
+        let uniform: f64 = 1.0 - self.rng.gen::<f64>();
+        let minutes = -uniform.ln() / total;
+        Some(Duration::from_secs_f64(minutes * 60.0))
+    }
+
+    fn pick_kind(&mut self) -> MockEventKind {
+        let mut target = self.rng.gen::<f64>() * self.rates.total();
+        let weights = self.rates.weights();
+
+        for (kind, rate) in weights {
+            let rate = rate.max(0.0);
+            if target < rate {
+                return kind;
+            }
+            target -= rate;
+        }
+
+        MockEventKind::Follow
+    }
+
+    fn random_event(&mut self, kind: MockEventKind) -> MockEvent {
+        match kind {
+            MockEventKind::TwitchChat => MockEvent::TwitchChat { user: None, message: None },
+            MockEventKind::YoutubeChat => MockEvent::YoutubeChat { user: None, message: None },
+            MockEventKind::Follow => MockEvent::Follow { user: None },
+            MockEventKind::Subscription => MockEvent::Subscription { user: None, tier: None, is_gift: None },
+            MockEventKind::Donation => MockEvent::Donation { user: None, amount: None, message: None },
+            MockEventKind::Raid => MockEvent::Raid { from: None, viewers: None },
+        }
+    }
+
+    fn random_name(&mut self) -> String {
+        let prefix = NAME_PREFIXES[self.rng.gen_range(0..NAME_PREFIXES.len())];
+        let suffix = NAME_SUFFIXES[self.rng.gen_range(0..NAME_SUFFIXES.len())];
+        format!("{}{}{}", prefix, suffix, self.rng.gen_range(0..1000))
+    }
+
+    fn random_line(&mut self) -> String {
+        CHAT_LINES[self.rng.gen_range(0..CHAT_LINES.len())].to_string()
+    }
+
+    // Build a Twitch chat message shaped exactly like the ones EventSub delivers
+    pub fn twitch_message(&mut self, user: Option<String>, text: Option<String>) -> MessageData {
+        let name = user.unwrap_or_else(|| self.random_name());
+        let text = text.unwrap_or_else(|| self.random_line());
+        self.message_counter += 1;
+
+        let mut badges = Vec::new();
+        if self.rng.gen_bool(0.3) {
+            let months = self.rng.gen_range(1..=36);
+            badges.push(Badge {
+                set_id: "subscriber".to_string(),
+                id: "0".to_string(),
+                info: months.to_string(),
+            });
+        }
+        if self.rng.gen_bool(0.05) {
+            badges.push(Badge {
+                set_id: "moderator".to_string(),
+                id: "1".to_string(),
+                info: String::new(),
+            });
+        }
+
+        MessageData {
+            broadcaster: User {
+                id: "0".to_string(),
+                name: "MockBroadcaster".to_string(),
+                login: "mockbroadcaster".to_string(),
+            },
+            chatter: User {
+                id: format!("mock-{}", name.to_lowercase()),
+                login: name.to_lowercase(),
+                name,
+            },
+            message_id: format!("mock-message-{}", self.message_counter),
+            message: Message {
+                text: text.clone(),
+                fragments: vec![Fragments {
+                    kind: FragmentType::Text,
+                    text,
+                    cheermote: None,
+                    emote: None,
+                    mention: None,
+                }],
+            },
+            colour: String::new(),
+            badges,
+            message_type: MessageType::Text,
+            cheer: None,
+            reply: None,
+            channel_points_custom_reward_id: None,
+            channel_points_animation_id: None,
+        }
+    }
+
+    // Build a YouTube chat message shaped like the liveChat/messages API response
+    pub fn youtube_message(&mut self, user: Option<String>, text: Option<String>) -> ChatMessage {
+        let name = user.unwrap_or_else(|| self.random_name());
+        let text = text.unwrap_or_else(|| self.random_line());
+
+        ChatMessage {
+            snippet: MessageSnippet {
+                display_message: text,
+                published_at: Utc::now().to_rfc3339(),
+                new_sponsor_details: None,
+                member_milestone_details: None,
+            },
+            author_details: AuthorDetails {
+                channel_id: Some(format!("UCmock{}", name.to_lowercase())),
+                display_name: name,
+                is_owner: Some(false),
+                is_moderator: Some(self.rng.gen_bool(0.05)),
+                is_sponsor: Some(self.rng.gen_bool(0.2)),
+                is_verified: Some(self.rng.gen_bool(0.02)),
+                profile_image_url: None,
+            },
+        }
+    }
+
+    fn emit_chat(&mut self, app: &AppHandle, platform: Platform, user: Option<String>, text: Option<String>) {
+        match platform {
+            Platform::Twitch => {
+                let message = self.twitch_message(user, text);
+                crate::handle_twitch_chat_message(app, message);
+            },
+            Platform::Youtube => {
+                let message = self.youtube_message(user, text);
+                crate::handle_youtube_chat_message(app, &message);
+            }
+        }
+    }
+
+    // Emit a single event to the frontend, filling in any missing fields
+    pub async fn emit(&mut self, app: &AppHandle, event: MockEvent) {
+        match event {
+            MockEvent::TwitchChat { user, message } => {
+                self.emit_chat(app, Platform::Twitch, user, message);
+            },
+            MockEvent::YoutubeChat { user, message } => {
+                self.emit_chat(app, Platform::Youtube, user, message);
+            },
+            MockEvent::ChatFlood { platform, count, duration_ms } => {
+                info!("Mock chat flood: {} {:?} messages over {}ms", count, platform, duration_ms);
+                self.emit_flood(app, platform, count, Duration::from_millis(duration_ms)).await;
+            },
+            MockEvent::Follow { user } => {
+                let username = user.unwrap_or_else(|| self.random_name());
+
+                info!("{} followed!", username);
+
+                let _ = app.emit_all("twitch-follow", serde_json::json!({
+                    "user": username
+                }));
+            },
+            MockEvent::Subscription { user, tier, is_gift } => {
+                let username = user.unwrap_or_else(|| self.random_name());
+                let tier = tier.unwrap_or_else(|| self.rng.gen_range(1..=3));
+                let is_gift = is_gift.unwrap_or_else(|| self.rng.gen_bool(0.5));
+
+                let event = if is_gift {
+                    SubscriptionEvent::Gifted { recipient: username, tier }
+                } else {
+                    SubscriptionEvent::Subscribe { username, tier }
+                };
+                twitch_events::emit_subscription(app, event);
+            },
+            MockEvent::Donation { user, amount, message } => {
+                let username = user.unwrap_or_else(|| self.random_name());
+                let amount = amount.unwrap_or_else(|| (self.rng.gen::<f64>() * 10000.0).round() / 100.0);
+                let message = message.or_else(|| {
+                    if self.rng.gen_bool(0.5) {
+                        Some("Thanks for the stream! Keep up the good work!".to_string())
+                    } else {
+                        None
+                    }
+                });
+
+                info!("{} donated ${:.2}!", username, amount);
+
+                let _ = app.emit_all("twitch-donation", serde_json::json!({
+                    "username": username,
+                    "amount": amount,
+                    "message": message,
+                }));
+            },
+            MockEvent::Raid { from, viewers } => {
+                let from = from.unwrap_or_else(|| self.random_name());
+                let viewers = viewers.unwrap_or_else(|| self.rng.gen_range(1..500));
+
+                twitch_events::emit_raid(app, RaidEvent {
+                    from_login: from.to_lowercase(),
+                    from,
+                    viewers,
+                });
+            },
+            MockEvent::SetRates { rates } => {
+                self.rates = rates;
+            }
+        }
+    }
+
+    // Spread `count` chat messages evenly over `duration`, emitting in 10ms ticks
+    // so high counts aren't limited by timer resolution
+    async fn emit_flood(&mut self, app: &AppHandle, platform: Platform, count: u32, duration: Duration) {
+        let tick = Duration::from_millis(10);
+        let ticks = (duration.as_millis() / tick.as_millis()).max(1) as u64;
+        let per_tick = count as f64 / ticks as f64;
+
+        let mut owed = 0.0;
+        let mut sent = 0;
+        for _ in 0..ticks {
+            owed += per_tick;
+            while owed >= 1.0 && sent < count {
+                self.emit_chat(app, platform, None, None);
+                owed -= 1.0;
+                sent += 1;
+            }
+            sleep(tick).await;
+        }
+
+        // Rounding can leave a few messages over
+        while sent < count {
+            self.emit_chat(app, platform, None, None);
+            sent += 1;
+        }
+    }
+
+    // Emit random events at the configured rates until the task is aborted
+    pub async fn run(mut self, app: AppHandle) {
+        loop {
+            match self.next_delay() {
+                Some(delay) => {
+                    sleep(delay).await;
+                    let kind = self.pick_kind();
+                    let event = self.random_event(kind);
+                    self.emit(&app, event).await;
+                },
+                None => {
+                    // Nothing to do until a scenario changes the rates
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+
+    // Play a scenario step by step, then keep emitting background events
+    // if the scenario left any non-zero rates behind
+    pub async fn run_scenario(mut self, app: AppHandle, scenario: Scenario) {
+        info!("Running mock scenario '{}' ({} steps)", scenario.name, scenario.steps.len());
+
+        for step in scenario.steps {
+            if step.wait_ms > 0 {
+                sleep(Duration::from_millis(step.wait_ms)).await;
+            }
+            for _ in 0..step.repeat {
+                self.emit(&app, step.event.clone()).await;
+            }
+        }
+
+        info!("Mock scenario '{}' finished", scenario.name);
+
+        if self.rates.total() > 0.0 {
+            self.run(app).await;
+        }
+    }
+}
+
+fn replace_task(slot: &Mutex<Option<JoinHandle<()>>>, task: Option<JoinHandle<()>>) {
+    if let Ok(mut slot) = slot.lock() {
+        if let Some(old) = slot.take() {
+            old.abort();
+        }
+        *slot = task;
+    }
+}
+
+// Start (or restart) the background generator with the given config
+pub fn start(app: AppHandle, config: MockConfig) {
+    info!("Started mock events generator (seed: {:?})...", config.seed);
+
+    let generator = MockGenerator::new(config.seed, config.rates);
+    let task = tauri::async_runtime::spawn(generator.run(app));
+    replace_task(&GENERATOR_TASK, Some(task));
+}
+
+// Run a scenario file. Relative paths are looked up in the app config's scenarios folder.
+pub fn start_scenario(app: AppHandle, path: &str, seed: Option<u64>) -> AppResult<()> {
+    let path = resolve_scenario_path(&app, path);
+    let scenario = Scenario::load(&path)?;
+
+    let generator = MockGenerator::new(seed.or(scenario.seed), MockRates {
+        follow: 0.0,
+        subscription: 0.0,
+        donation: 0.0,
+        ..MockRates::default()
+    });
+    let task = tauri::async_runtime::spawn(generator.run_scenario(app, scenario));
+    replace_task(&SCENARIO_TASK, Some(task));
+    Ok(())
+}
+
+// Stop both the background generator and any running scenario
+pub fn stop() {
+    replace_task(&GENERATOR_TASK, None);
+    replace_task(&SCENARIO_TASK, None);
+    info!("Stopped mock events");
+}
+
+fn resolve_scenario_path(app: &AppHandle, path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.is_absolute() || path.exists() {
+        return path;
+    }
+
+    match app.path_resolver().app_config_dir() {
+        Some(config_dir) => config_dir.join("scenarios").join(path),
+        None => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Delays, event kinds and chat messages, as far as the RNG decides them
+    fn sequence(generator: &mut MockGenerator, len: usize) -> Vec<String> {
+        (0..len)
+            .map(|_| {
+                let delay = generator.next_delay();
+                let kind = generator.pick_kind();
+                let message = generator.twitch_message(None, None);
+                let badges = message.badges.iter().map(|badge| badge.set_id.as_str()).collect::<Vec<_>>();
+                format!("{:?} {:?} {} {} {:?}", delay, kind, message.chatter.name, message.message.text, badges)
+            })
+            .collect()
+    }
+
+    fn chat_rates() -> MockRates {
+        MockRates { twitch_chat: 30.0, youtube_chat: 10.0, ..MockRates::default() }
+    }
+
+    #[test]
+    fn same_seed_gives_same_events() {
+        let first = sequence(&mut MockGenerator::new(Some(42), chat_rates()), 100);
+        let second = sequence(&mut MockGenerator::new(Some(42), chat_rates()), 100);
+        let other_seed = sequence(&mut MockGenerator::new(Some(43), chat_rates()), 100);
+
+        assert_eq!(first, second);
+        assert_ne!(first, other_seed);
+    }
+
+    #[test]
+    fn parses_bundled_scenario() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios").join("raid_then_chat_flood.json");
+        let scenario = Scenario::load(&path).unwrap();
+
+        assert_eq!(scenario.seed, Some(42));
+        assert_eq!(scenario.steps.len(), 6);
+
+        let first = &scenario.steps[0];
+        assert_eq!((first.wait_ms, first.repeat), (0, 1));
+        assert!(matches!(&first.event, MockEvent::TwitchChat { user: Some(user), .. } if user == "RegularViewer"));
+
+        let raid = &scenario.steps[1];
+        assert_eq!(raid.wait_ms, 2000);
+        assert!(matches!(&raid.event, MockEvent::Raid { from: Some(from), viewers: Some(300) } if from == "BigStreamer"));
+
+        let flood = &scenario.steps[2];
+        assert_eq!(flood.wait_ms, 500);
+        assert!(matches!(flood.event, MockEvent::ChatFlood { platform: Platform::Twitch, count: 600, duration_ms: 20000 }));
+
+        let follows = &scenario.steps[3];
+        assert_eq!((follows.wait_ms, follows.repeat), (0, 25));
+        assert!(matches!(follows.event, MockEvent::Follow { user: None }));
+
+        assert!(matches!(&scenario.steps[5].event, MockEvent::SetRates { rates } if rates.twitch_chat == 30.0 && rates.follow == 2.0));
+    }
+}
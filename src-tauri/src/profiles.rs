@@ -0,0 +1,222 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::{info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+
+use crate::avatars;
+use crate::badges;
+use crate::cheermotes;
+use crate::credentials;
+use crate::error::{AppError, AppResult};
+use crate::raids;
+use crate::settings::{self, Settings};
+
+// Named profiles, e.g. one per channel or account. Each profile has a folder under
+// `profiles/` in the config dir with its own credential store and settings, and one in
+// the data dir with its badge and cheermote caches. `profiles.json` remembers the active
+// profile.
+const PROFILES_DIR: &str = "profiles";
+const PROFILES_FILE: &str = "profiles.json";
+pub const DEFAULT_PROFILE: &str = "default";
+const MAX_NAME_LEN: usize = 40;
+
+// Older versions kept a single configuration straight in the config and data dirs. Those
+// files become the default profile.
+const LEGACY_CONFIG_FILES: &[&str] = &["settings.json", ".secrets.env"];
+const LEGACY_DATA_FILES: &[&str] = &["badges.json"];
+
+static ACTIVE: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(DEFAULT_PROFILE.to_string()));
+static DIRS: OnceCell<AppDirs> = OnceCell::new();
+
+struct AppDirs {
+    config: Option<PathBuf>,
+    data: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProfilesFile {
+    active: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<String>,
+}
+
+// Move an old single configuration into the default profile and load the active
+// profile. Called once from setup, before anything reads settings or credentials.
+pub fn init(config_dir: Option<PathBuf>, data_dir: Option<PathBuf>) -> AppResult<()> {
+    if let Some(config_dir) = &config_dir {
+        move_legacy_files(config_dir, LEGACY_CONFIG_FILES);
+    }
+    if let Some(data_dir) = &data_dir {
+        move_legacy_files(data_dir, LEGACY_DATA_FILES);
+    }
+
+    let active = config_dir.as_ref()
+        .and_then(|dir| fs::read_to_string(dir.join(PROFILES_FILE)).ok())
+        .and_then(|contents| serde_json::from_str::<ProfilesFile>(&contents).ok())
+        .map(|file| file.active)
+        .filter(|name| validate_name(name).is_ok())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+
+    let _ = DIRS.set(AppDirs { config: config_dir, data: data_dir });
+    load(&active)
+}
+
+fn move_legacy_files(dir: &Path, files: &[&str]) {
+    let profile_dir = dir.join(PROFILES_DIR).join(DEFAULT_PROFILE);
+    for file in files {
+        let from = dir.join(file);
+        let to = profile_dir.join(file);
+        if !from.exists() || to.exists() {
+            continue;
+        }
+
+        let result = fs::create_dir_all(&profile_dir).and_then(|_| fs::rename(&from, &to));
+        match result {
+            Ok(_) => info!("Moved {} into the default profile", from.display()),
+            Err(e) => warn!("Failed to move {} into the default profile: {}", from.display(), e),
+        }
+    }
+}
+
+fn config_dir(name: &str) -> Option<PathBuf> {
+    Some(DIRS.get()?.config.as_ref()?.join(PROFILES_DIR).join(name))
+}
+
+fn data_dir(name: &str) -> Option<PathBuf> {
+    Some(DIRS.get()?.data.as_ref()?.join(PROFILES_DIR).join(name))
+}
+
+// Point settings, credentials and caches at a profile. The rest of the profile is
+// loaded even when its credentials can't be read; that error is returned.
+fn load(name: &str) -> AppResult<()> {
+    info!("Loading profile {}", name);
+    if let Ok(mut active) = ACTIVE.lock() {
+        *active = name.to_string();
+    }
+
+    settings::init(config_dir(name));
+    // Keys from `.secrets.env` files and the environment predate profiles, so only the
+    // default profile picks them up
+    let credentials = credentials::init(config_dir(name), name == DEFAULT_PROFILE);
+
+    avatars::clear_twitch_credentials();
+    if let Some(dir) = data_dir(name) {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            warn!("Failed to create {}: {}", dir.display(), e);
+        }
+        raids::init(&dir);
+        badges::load_disk_cache(dir.clone());
+        cheermotes::load_disk_cache(dir);
+    }
+    credentials
+}
+
+pub fn active() -> String {
+    ACTIVE.lock().map(|active| active.clone()).unwrap_or_else(|_| DEFAULT_PROFILE.to_string())
+}
+
+pub fn list() -> ProfileList {
+    let mut profiles = DIRS.get()
+        .and_then(|dirs| dirs.config.as_ref())
+        .and_then(|dir| fs::read_dir(dir.join(PROFILES_DIR)).ok())
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .filter(|name| validate_name(name).is_ok())
+            .collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    for name in [DEFAULT_PROFILE.to_string(), active()] {
+        if !profiles.contains(&name) {
+            profiles.push(name);
+        }
+    }
+    profiles.sort();
+
+    ProfileList { active: active(), profiles }
+}
+
+fn exists(name: &str) -> bool {
+    list().profiles.iter().any(|profile| profile == name)
+}
+
+// Letters, digits, spaces, dashes and underscores, so the name is also a safe folder name
+fn validate_name(name: &str) -> AppResult<()> {
+    let valid_chars = name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+    if name.trim().is_empty() || name.trim() != name || name.len() > MAX_NAME_LEN || !valid_chars {
+        return Err(AppError::config(format!(
+            "Invalid profile name {:?}: use up to {} letters, digits, spaces, dashes or underscores",
+            name, MAX_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+// Create a profile with no credentials. Its settings start as a copy of the active
+// profile's when `copy_settings` is set, otherwise as defaults.
+pub fn create(name: &str, copy_settings: bool) -> AppResult<()> {
+    validate_name(name)?;
+    if exists(name) {
+        return Err(AppError::config(format!("Profile {} already exists", name)));
+    }
+    let dir = config_dir(name)
+        .ok_or_else(|| AppError::config("Could not find config directory"))?;
+
+    let mut settings = if copy_settings { settings::get() } else { Settings::default() };
+    // Token files are kept in the profile's own config dir
+    settings.twitch.token_file = format!(".user_token.{}.env", name);
+    settings.twitch.refresh_token_file = format!(".refresh_token.{}.env", name);
+    settings::save_to(&dir, &settings)?;
+
+    info!("Created profile {}", name);
+    Ok(())
+}
+
+// Make another profile active. The caller stops and restarts the listeners around this.
+pub fn switch(name: &str) -> AppResult<()> {
+    validate_name(name)?;
+    if !exists(name) {
+        return Err(AppError::config(format!("Profile {} doesn't exist", name)));
+    }
+
+    if let Some(dir) = DIRS.get().and_then(|dirs| dirs.config.as_ref()) {
+        let contents = serde_json::to_string_pretty(&ProfilesFile { active: name.to_string() })
+            .map_err(|e| AppError::internal(format!("Failed to serialize profiles: {}", e)))?;
+        fs::write(dir.join(PROFILES_FILE), contents)
+            .map_err(|e| AppError::config(format!("Failed to save active profile: {}", e)))?;
+    }
+
+    load(name)
+}
+
+// Delete a profile with its credentials, settings and caches
+pub fn delete(name: &str) -> AppResult<()> {
+    validate_name(name)?;
+    if name == DEFAULT_PROFILE {
+        return Err(AppError::config("The default profile can't be deleted"));
+    }
+    if name == active() {
+        return Err(AppError::config("Switch to another profile before deleting this one"));
+    }
+    if !exists(name) {
+        return Err(AppError::config(format!("Profile {} doesn't exist", name)));
+    }
+
+    for dir in [config_dir(name), data_dir(name)].into_iter().flatten() {
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .map_err(|e| AppError::config(format!("Failed to delete {}: {}", dir.display(), e)))?;
+        }
+    }
+
+    info!("Deleted profile {}", name);
+    Ok(())
+}
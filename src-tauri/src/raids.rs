@@ -0,0 +1,372 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::AppHandle;
+use twitch_eventsub::RaidData;
+
+use crate::credentials;
+use crate::error::{self, AppError, AppResult};
+use crate::twitch_auth;
+
+// What the app does when another channel raids: nothing, a shoutout for the raider, a
+// thank-you in chat, or chat slow mode on Twitch for a while so the raiders' greetings
+// don't bury the stream chat.
+const SHOUTOUT_URL: &str = "https://api.twitch.tv/helix/chat/shoutouts";
+const CHAT_SETTINGS_URL: &str = "https://api.twitch.tv/helix/chat/settings";
+
+// Twitch limits for the slow mode wait time
+const SLOW_MODE_WAIT_RANGE: std::ops::RangeInclusive<u32> = 3..=120;
+const MAX_SLOW_MODE_DURATION_SECS: u64 = 60 * 60;
+const MAX_MESSAGE_LEN: usize = 500;
+// Slow mode the app still has to turn off, in the profile's data directory
+const SLOW_MODE_FILE: &str = "raid_slow_mode.json";
+
+static SLOW_MODE: Lazy<Mutex<SlowModeState>> = Lazy::new(|| Mutex::new(SlowModeState::default()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RaidAction {
+    None,
+    Shoutout,
+    ThankYou,
+    SlowMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RaidWelcome {
+    pub action: RaidAction,
+    // Raids smaller than this are only shown in the activity feed
+    pub min_viewers: u32,
+    // Thank-you message; "{raider}" and "{viewers}" are filled in
+    pub message: String,
+    // Seconds chatters have to wait between messages while slow mode is on
+    pub slow_mode_wait_secs: u32,
+    // How long slow mode stays on
+    pub slow_mode_duration_secs: u64,
+}
+
+impl Default for RaidWelcome {
+    fn default() -> Self {
+        RaidWelcome {
+            action: RaidAction::None,
+            min_viewers: 1,
+            message: "Thank you for the raid, {raider}! Welcome to all {viewers} of you!".to_string(),
+            slow_mode_wait_secs: 10,
+            slow_mode_duration_secs: 120,
+        }
+    }
+}
+
+impl RaidWelcome {
+    // Scopes a login needs for the chosen action. Thank-you messages go out with the
+    // chat scopes the listener already has.
+    pub fn required_scopes(&self) -> Vec<String> {
+        match self.action {
+            RaidAction::Shoutout => vec!["moderator:manage:shoutouts".to_string()],
+            RaidAction::SlowMode => vec!["moderator:manage:chat_settings".to_string()],
+            RaidAction::None | RaidAction::ThankYou => Vec::new(),
+        }
+    }
+}
+
+pub fn validate(welcome: &RaidWelcome) -> AppResult<()> {
+    if welcome.action == RaidAction::ThankYou && welcome.message.trim().is_empty() {
+        return Err(AppError::config("Raid thank-you message can't be empty"));
+    }
+    if welcome.message.chars().count() > MAX_MESSAGE_LEN {
+        return Err(AppError::config(format!("Raid thank-you message can be at most {} characters", MAX_MESSAGE_LEN)));
+    }
+    if !SLOW_MODE_WAIT_RANGE.contains(&welcome.slow_mode_wait_secs) {
+        return Err(AppError::config(format!(
+            "Raid slow mode wait must be between {} and {} seconds",
+            SLOW_MODE_WAIT_RANGE.start(), SLOW_MODE_WAIT_RANGE.end()
+        )));
+    }
+    if welcome.slow_mode_duration_secs == 0 || welcome.slow_mode_duration_secs > MAX_SLOW_MODE_DURATION_SECS {
+        return Err(AppError::config("Raid slow mode must last between 1 second and an hour"));
+    }
+    Ok(())
+}
+
+fn thank_you_message(template: &str, raid: &RaidData) -> String {
+    template.replace("{raider}", &raid.from_broadcaster.name)
+        .replace("{viewers}", &raid.viewers.to_string())
+}
+
+// Run the configured welcome action for a raid. Thank-you messages are returned for the
+// caller to put on the outgoing chat queue; the API actions run in the background and
+// report failures through the error event.
+pub fn welcome(app: &AppHandle, client: &Client, welcome: &RaidWelcome, raid: &RaidData) -> Option<String> {
+    if welcome.action == RaidAction::None || raid.viewers < welcome.min_viewers {
+        return None;
+    }
+    info!("Welcoming the raid from {} with {:?}", raid.from_broadcaster.name, welcome.action);
+
+    if welcome.action == RaidAction::ThankYou {
+        return Some(thank_you_message(&welcome.message, raid));
+    }
+
+    let app = app.clone();
+    let client = client.clone();
+    let welcome = welcome.clone();
+    let raider_id = raid.from_broadcaster.id.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = match welcome.action {
+            RaidAction::Shoutout => shoutout(&client, &raider_id).await,
+            RaidAction::SlowMode => slow_mode(&app, &client, &welcome).await,
+            RaidAction::None | RaidAction::ThankYou => Ok(()),
+        };
+        if let Err(e) = result {
+            error::emit(&app, "twitch-raid", &e);
+        }
+    });
+    None
+}
+
+// Client ID, broadcaster ID, token and the token owner's ID, who acts as the moderator.
+// That's usually the broadcaster but can be a moderator running the app for them.
+async fn api_credentials(client: &Client) -> AppResult<(String, String, String, String)> {
+    let (client_id, broadcaster_id, token) = credentials::get().twitch_api()?;
+    let moderator_id = twitch_auth::validate_token(client, &token).await?
+        .user_id
+        .ok_or_else(|| AppError::auth("twitch", "Token doesn't belong to a user"))?;
+    Ok((client_id, broadcaster_id, token, moderator_id))
+}
+
+async fn shoutout(client: &Client, raider_id: &str) -> AppResult<()> {
+    let (client_id, broadcaster_id, token, moderator_id) = api_credentials(client).await?;
+
+    let response = client.post(SHOUTOUT_URL)
+        .query(&[
+            ("from_broadcaster_id", broadcaster_id.as_str()),
+            ("to_broadcaster_id", raider_id),
+            ("moderator_id", moderator_id.as_str()),
+        ])
+        .bearer_auth(&token)
+        .header("Client-Id", &client_id)
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+    info!("Sent a shoutout for raider {}", raider_id);
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ChatSettingsResponse {
+    data: Vec<ChatSettings>,
+}
+
+#[derive(Deserialize)]
+struct ChatSettings {
+    slow_mode: bool,
+}
+
+// Slow mode the app turned on and has to turn off again. It's saved to disk until then,
+// so a run that ends early (or can't reach Twitch in time) still turns it off on the
+// next start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingSlowMode {
+    broadcaster_id: String,
+    moderator_id: String,
+    // Unix timestamp when slow mode should go off
+    until: i64,
+}
+
+#[derive(Default)]
+struct SlowModeState {
+    file: Option<PathBuf>,
+    pending: Option<PendingSlowMode>,
+    // Sleeps until `pending.until`, then turns slow mode off
+    timer: Option<JoinHandle<()>>,
+}
+
+// Point the pending slow mode file at a profile's data directory. Called when a profile
+// is loaded; the listener picks up anything left in it when it starts.
+pub fn init(dir: &Path) {
+    if let Ok(mut state) = SLOW_MODE.lock() {
+        state.file = Some(dir.join(SLOW_MODE_FILE));
+    }
+}
+
+// Turn on slow mode for `slow_mode_duration_secs`, unless it's already on. Slow mode the
+// streamer set up themselves is left alone.
+async fn slow_mode(app: &AppHandle, client: &Client, welcome: &RaidWelcome) -> AppResult<()> {
+    let (client_id, broadcaster_id, token, moderator_id) = api_credentials(client).await?;
+    let query = [("broadcaster_id", broadcaster_id.as_str()), ("moderator_id", moderator_id.as_str())];
+
+    let response = client.get(CHAT_SETTINGS_URL)
+        .query(&query)
+        .bearer_auth(&token)
+        .header("Client-Id", &client_id)
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+    let current: ChatSettingsResponse = response.json().await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+    if current.data.first().map(|settings| settings.slow_mode).unwrap_or(false) {
+        info!("Slow mode is already on, leaving it as it is");
+        return Ok(());
+    }
+
+    let body = serde_json::json!({ "slow_mode": true, "slow_mode_wait_time": welcome.slow_mode_wait_secs });
+    let response = client.patch(CHAT_SETTINGS_URL)
+        .query(&query)
+        .bearer_auth(&token)
+        .header("Client-Id", &client_id)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+    info!("Slow mode on for {} seconds", welcome.slow_mode_duration_secs);
+
+    let pending = PendingSlowMode {
+        broadcaster_id,
+        moderator_id,
+        until: chrono::Utc::now().timestamp() + welcome.slow_mode_duration_secs as i64,
+    };
+    let file = SLOW_MODE.lock().ok().and_then(|state| state.file.clone());
+    if let Some(file) = &file {
+        save_pending(file, &pending);
+    }
+    schedule_slow_mode_off(app, client, pending);
+    Ok(())
+}
+
+// Turn slow mode off once the pending deadline passes
+fn schedule_slow_mode_off(app: &AppHandle, client: &Client, pending: PendingSlowMode) {
+    let Ok(mut state) = SLOW_MODE.lock() else {
+        return;
+    };
+    if let Some(timer) = state.timer.take() {
+        timer.abort();
+    }
+
+    let delay = (pending.until - chrono::Utc::now().timestamp()).max(0) as u64;
+    let app = app.clone();
+    let client = client.clone();
+    state.pending = Some(pending);
+    state.timer = Some(tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+
+        let Some((pending, file)) = take_pending() else {
+            return;
+        };
+        let result = match credentials::get().twitch_api() {
+            Ok((client_id, _, token)) => slow_mode_off(&client, &client_id, &token, &pending, file.as_deref()).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            error::emit(&app, "twitch-raid", &e);
+        }
+    }));
+}
+
+// The pending slow mode and the file it's saved in, leaving its timer to run out
+fn take_pending() -> Option<(PendingSlowMode, Option<PathBuf>)> {
+    let mut state = SLOW_MODE.lock().ok()?;
+    state.timer = None;
+    let pending = state.pending.take()?;
+    Some((pending, state.file.clone()))
+}
+
+// Turn slow mode off now if the app turned it on and hasn't turned it off yet. Called when
+// the listener stops and on shutdown, with the current profile's token, so it still works
+// while a profile switch replaces the credentials. The returned task finishes once Twitch
+// has answered.
+pub fn end_slow_mode(client: &Client) -> Option<JoinHandle<()>> {
+    let (pending, file) = {
+        let mut state = SLOW_MODE.lock().ok()?;
+        let pending = state.pending.take()?;
+        if let Some(timer) = state.timer.take() {
+            timer.abort();
+        }
+        (pending, state.file.clone())
+    };
+
+    let credentials = credentials::get().twitch_api();
+    let client = client.clone();
+    Some(tauri::async_runtime::spawn(async move {
+        let result = match credentials {
+            Ok((client_id, _, token)) => slow_mode_off(&client, &client_id, &token, &pending, file.as_deref()).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("Failed to turn raid slow mode off, trying again on the next start: {}", e);
+        }
+    }))
+}
+
+// Pick up slow mode a previous run didn't turn off. Off it goes if the deadline has
+// passed, otherwise it's turned off when it's due.
+pub fn resume_slow_mode(app: &AppHandle, client: &Client) {
+    let file = match SLOW_MODE.lock() {
+        Ok(state) if state.pending.is_none() => state.file.clone(),
+        _ => None,
+    };
+    let Some(pending) = file.and_then(|file| fs::read_to_string(file).ok())
+        .and_then(|contents| serde_json::from_str::<PendingSlowMode>(&contents).ok()) else {
+        return;
+    };
+
+    info!("Raid slow mode from a previous run is still on");
+    schedule_slow_mode_off(app, client, pending);
+}
+
+fn save_pending(file: &Path, pending: &PendingSlowMode) {
+    let result = serde_json::to_string(pending)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| fs::write(file, contents).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("Failed to save raid slow mode to {}: {}", file.display(), e);
+    }
+}
+
+async fn slow_mode_off(client: &Client, client_id: &str, token: &str, pending: &PendingSlowMode, file: Option<&Path>) -> AppResult<()> {
+    let response = client.patch(CHAT_SETTINGS_URL)
+        .query(&[
+            ("broadcaster_id", pending.broadcaster_id.as_str()),
+            ("moderator_id", pending.moderator_id.as_str()),
+        ])
+        .bearer_auth(token)
+        .header("Client-Id", client_id)
+        .json(&serde_json::json!({ "slow_mode": false }))
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        warn!("Failed to turn slow mode off again");
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+
+    info!("Slow mode off again");
+    if let Some(file) = file {
+        let _ = fs::remove_file(file);
+    }
+    Ok(())
+}
@@ -0,0 +1,403 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+
+// Encryption for the credential store, so keys and tokens never sit on disk as plaintext.
+//
+// The data key comes from one of three places:
+// - the OS keyring (Windows Credential Manager, macOS Keychain, Secret Service), the default
+// - a key file next to the store when there is no usable keyring. This only keeps the
+//   secrets out of sight in a file explorer or text editor, it's no protection against
+//   someone who can read the config dir.
+// - a passphrase the user chose, stretched with Argon2id. The store stays locked after
+//   startup until the passphrase is entered.
+const KEYRING_SERVICE: &str = "streamchatbox";
+const KEYRING_USER: &str = "credentials-key";
+const KEY_FILE: &str = "credentials.key";
+const ENVELOPE_VERSION: u32 = 1;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+static KEY: Lazy<Mutex<Option<DataKey>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    Keyring,
+    KeyFile,
+    Passphrase,
+}
+
+struct DataKey {
+    source: KeySource,
+    key: [u8; KEY_LEN],
+    // Only for passphrase keys, saved with the data so the key can be derived again
+    salt: Option<[u8; SALT_LEN]>,
+}
+
+// What's written to disk
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    key_source: KeySource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Whether `data` is an encrypted store rather than the plaintext JSON older versions wrote
+pub fn is_encrypted(data: &str) -> bool {
+    serde_json::from_str::<Envelope>(data).is_ok()
+}
+
+// Where the key for an encrypted store comes from, if it can be read
+pub fn key_source(data: &str) -> Option<KeySource> {
+    serde_json::from_str::<Envelope>(data).ok().map(|envelope| envelope.key_source)
+}
+
+// The source of the key currently in use
+pub fn current_source() -> Option<KeySource> {
+    KEY.lock().ok()?.as_ref().map(|key| key.source)
+}
+
+// Decrypt a store. Keyring and key file keys are loaded as needed; passphrase stores
+// need `unlock` first.
+pub fn decrypt(data: &str, dir: &Path) -> AppResult<Vec<u8>> {
+    let envelope: Envelope = serde_json::from_str(data)
+        .map_err(|e| AppError::config(format!("Unreadable credentials file: {}", e)))?;
+    if envelope.version > ENVELOPE_VERSION {
+        return Err(AppError::config("Credentials file was written by a newer version"));
+    }
+
+    let mut current = KEY.lock()
+        .map_err(|e| AppError::internal(format!("Failed to lock credentials key: {}", e)))?;
+
+    let key = match (&*current, envelope.key_source) {
+        (Some(key), source) if key.source == source => key.key,
+        (_, KeySource::Passphrase) => return Err(locked_error()),
+        (_, KeySource::Keyring) => {
+            let key = keyring_key(false)?
+                .ok_or_else(|| AppError::config("The credentials key is missing from the system keyring"))?;
+            *current = Some(DataKey { source: KeySource::Keyring, key, salt: None });
+            key
+        },
+        (_, KeySource::KeyFile) => {
+            let key = key_file_key(dir, false)?
+                .ok_or_else(|| AppError::config(format!("The credentials key file {} is missing", dir.join(KEY_FILE).display())))?;
+            *current = Some(DataKey { source: KeySource::KeyFile, key, salt: None });
+            key
+        },
+    };
+
+    open(&envelope, &key)
+}
+
+// Encrypt a store with the current key, picking the keyring or key file on first use
+pub fn encrypt(plaintext: &[u8], dir: &Path) -> AppResult<String> {
+    let mut current = KEY.lock()
+        .map_err(|e| AppError::internal(format!("Failed to lock credentials key: {}", e)))?;
+
+    if current.is_none() {
+        *current = Some(default_key(dir)?);
+    }
+    let Some(key) = current.as_ref() else {
+        return Err(AppError::internal("No credentials key"));
+    };
+
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = cipher(&key.key)
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .map_err(|_| AppError::internal("Failed to encrypt credentials"))?;
+
+    let envelope = Envelope {
+        version: ENVELOPE_VERSION,
+        key_source: key.source,
+        salt: key.salt.map(|salt| BASE64.encode(salt)),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&envelope)
+        .map_err(|e| AppError::internal(format!("Failed to serialize credentials: {}", e)))
+}
+
+// Derive the key for a passphrase protected store and check it against `data`
+pub fn unlock(data: &str, passphrase: &str) -> AppResult<Vec<u8>> {
+    let envelope: Envelope = serde_json::from_str(data)
+        .map_err(|e| AppError::config(format!("Unreadable credentials file: {}", e)))?;
+    if envelope.key_source != KeySource::Passphrase {
+        return Err(AppError::config("Credentials are not protected by a passphrase"));
+    }
+
+    let salt: [u8; SALT_LEN] = envelope.salt.as_deref()
+        .and_then(|salt| BASE64.decode(salt).ok())
+        .and_then(|salt| salt.try_into().ok())
+        .ok_or_else(|| AppError::config("Credentials file is missing its salt"))?;
+
+    let key = derive_key(passphrase, &salt)?;
+    let plaintext = open(&envelope, &key)
+        .map_err(|_| AppError::auth("credentials", "Wrong passphrase"))?;
+
+    if let Ok(mut current) = KEY.lock() {
+        *current = Some(DataKey { source: KeySource::Passphrase, key, salt: Some(salt) });
+    }
+    Ok(plaintext)
+}
+
+// Protect future saves with a passphrase, or go back to the keyring/key file with None
+pub fn set_passphrase(passphrase: Option<&str>, dir: &Path) -> AppResult<()> {
+    let key = match passphrase.filter(|p| !p.is_empty()) {
+        Some(passphrase) => {
+            let mut salt = [0u8; SALT_LEN];
+            rand::rngs::OsRng.fill_bytes(&mut salt);
+            DataKey { source: KeySource::Passphrase, key: derive_key(passphrase, &salt)?, salt: Some(salt) }
+        },
+        None => default_key(dir)?,
+    };
+
+    let mut current = KEY.lock()
+        .map_err(|e| AppError::internal(format!("Failed to lock credentials key: {}", e)))?;
+    *current = Some(key);
+    Ok(())
+}
+
+// Drop the key in use, e.g. before loading another profile's store
+pub fn forget_key() {
+    if let Ok(mut current) = KEY.lock() {
+        *current = None;
+    }
+}
+
+pub fn locked_error() -> AppError {
+    AppError::auth("credentials", "Credentials are locked, enter the passphrase to unlock them")
+}
+
+fn open(envelope: &Envelope, key: &[u8; KEY_LEN]) -> AppResult<Vec<u8>> {
+    let nonce = BASE64.decode(&envelope.nonce)
+        .ok()
+        .filter(|nonce| nonce.len() == NONCE_LEN)
+        .ok_or_else(|| AppError::config("Credentials file has an invalid nonce"))?;
+    let ciphertext = BASE64.decode(&envelope.ciphertext)
+        .map_err(|e| AppError::config(format!("Credentials file is corrupt: {}", e)))?;
+
+    cipher(key)
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| AppError::config("Failed to decrypt credentials, the key doesn't match"))
+}
+
+fn cipher(key: &[u8; KEY_LEN]) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new(Key::from_slice(key))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8; SALT_LEN]) -> AppResult<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| AppError::internal(format!("Failed to derive key from passphrase: {}", e)))?;
+    Ok(key)
+}
+
+fn random_key() -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut key);
+    key
+}
+
+fn decode_key(encoded: &str) -> Option<[u8; KEY_LEN]> {
+    BASE64.decode(encoded.trim()).ok()?.try_into().ok()
+}
+
+// The keyring key, or the key file when the keyring can't be used
+fn default_key(dir: &Path) -> AppResult<DataKey> {
+    match keyring_key(true) {
+        Ok(Some(key)) => return Ok(DataKey { source: KeySource::Keyring, key, salt: None }),
+        Ok(None) => {},
+        Err(e) => warn!("System keyring unavailable, using a key file instead: {}", e),
+    }
+
+    let key = key_file_key(dir, true)?
+        .ok_or_else(|| AppError::internal("Failed to create credentials key file"))?;
+    Ok(DataKey { source: KeySource::KeyFile, key, salt: None })
+}
+
+fn keyring_key(create: bool) -> AppResult<Option<[u8; KEY_LEN]>> {
+    let keyring_error = |e: keyring::Error| AppError::config(format!("System keyring error: {}", e));
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(keyring_error)?;
+
+    match entry.get_password() {
+        Ok(encoded) => Ok(decode_key(&encoded)),
+        Err(keyring::Error::NoEntry) if create => {
+            let key = random_key();
+            entry.set_password(&BASE64.encode(key)).map_err(keyring_error)?;
+            // Make sure it can be read back; some keyrings accept writes they then forget
+            match entry.get_password().ok().and_then(|encoded| decode_key(&encoded)) {
+                Some(saved) if saved == key => {
+                    info!("Created credentials key in the system keyring");
+                    Ok(Some(key))
+                },
+                _ => Err(AppError::config("System keyring did not keep the credentials key")),
+            }
+        },
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(keyring_error(e)),
+    }
+}
+
+fn key_file_key(dir: &Path, create: bool) -> AppResult<Option<[u8; KEY_LEN]>> {
+    let path: PathBuf = dir.join(KEY_FILE);
+
+    if let Ok(encoded) = fs::read_to_string(&path) {
+        return decode_key(&encoded)
+            .map(Some)
+            .ok_or_else(|| AppError::config(format!("Invalid credentials key file {}", path.display())));
+    }
+    if !create {
+        return Ok(None);
+    }
+
+    let key = random_key();
+    fs::create_dir_all(dir)
+        .map_err(|e| AppError::config(format!("Failed to create config directory: {}", e)))?;
+    fs::write(&path, BASE64.encode(key))
+        .map_err(|e| AppError::config(format!("Failed to write {}: {}", path.display(), e)))?;
+    restrict_permissions(&path);
+
+    info!("Created credentials key file {}", path.display());
+    Ok(Some(key))
+}
+
+// Only the current user should be able to read key material
+pub fn restrict_permissions(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The key in use is global, so tests that set it take turns
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    // A fresh config dir under the system temp dir; removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("streamchatbox-secrets-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Use a key file in `dir`, without going near the system keyring
+    fn use_key_file(dir: &Path) {
+        let key = key_file_key(dir, true).unwrap().unwrap();
+        *KEY.lock().unwrap() = Some(DataKey { source: KeySource::KeyFile, key, salt: None });
+    }
+
+    // Change one field of an encrypted store
+    fn edit_envelope(data: &str, edit: impl FnOnce(&mut Envelope)) -> String {
+        let mut envelope: Envelope = serde_json::from_str(data).unwrap();
+        edit(&mut envelope);
+        serde_json::to_string(&envelope).unwrap()
+    }
+
+    fn flip_first_byte(encoded: &str) -> String {
+        let mut bytes = BASE64.decode(encoded).unwrap();
+        bytes[0] ^= 0x01;
+        BASE64.encode(bytes)
+    }
+
+    #[test]
+    fn key_file_round_trip() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = TempDir::new("round-trip");
+        use_key_file(&dir.0);
+
+        let data = encrypt(b"{\"twitch_user_token\":\"abc\"}", &dir.0).unwrap();
+        assert!(is_encrypted(&data));
+        assert_eq!(key_source(&data), Some(KeySource::KeyFile));
+        assert!(!data.contains("abc"));
+
+        // Read the key back from the file, like a fresh start does
+        forget_key();
+        assert_eq!(decrypt(&data, &dir.0).unwrap(), b"{\"twitch_user_token\":\"abc\"}");
+        assert_eq!(current_source(), Some(KeySource::KeyFile));
+    }
+
+    #[test]
+    fn passphrase_unlocks_only_with_the_right_one() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = TempDir::new("passphrase");
+
+        set_passphrase(Some("correct horse"), &dir.0).unwrap();
+        let data = encrypt(b"secret", &dir.0).unwrap();
+        assert_eq!(key_source(&data), Some(KeySource::Passphrase));
+
+        forget_key();
+        assert!(decrypt(&data, &dir.0).is_err(), "passphrase stores stay locked until unlocked");
+        assert!(unlock(&data, "wrong horse").is_err());
+        assert_eq!(current_source(), None);
+
+        assert_eq!(unlock(&data, "correct horse").unwrap(), b"secret");
+        assert_eq!(decrypt(&data, &dir.0).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn tampered_stores_are_rejected() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = TempDir::new("tampered");
+        use_key_file(&dir.0);
+        let data = encrypt(b"secret", &dir.0).unwrap();
+
+        let ciphertext = edit_envelope(&data, |envelope| envelope.ciphertext = flip_first_byte(&envelope.ciphertext));
+        assert!(decrypt(&ciphertext, &dir.0).is_err());
+
+        let nonce = edit_envelope(&data, |envelope| envelope.nonce = flip_first_byte(&envelope.nonce));
+        assert!(decrypt(&nonce, &dir.0).is_err());
+
+        let short_nonce = edit_envelope(&data, |envelope| envelope.nonce = BASE64.encode([0u8; 12]));
+        assert!(decrypt(&short_nonce, &dir.0).is_err());
+
+        assert_eq!(decrypt(&data, &dir.0).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn newer_envelope_versions_are_refused() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = TempDir::new("version");
+        use_key_file(&dir.0);
+        let data = encrypt(b"secret", &dir.0).unwrap();
+
+        let newer = edit_envelope(&data, |envelope| envelope.version = ENVELOPE_VERSION + 1);
+        let error = decrypt(&newer, &dir.0).unwrap_err();
+        assert!(error.to_string().contains("newer version"));
+    }
+}
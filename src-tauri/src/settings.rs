@@ -0,0 +1,483 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use twitch_eventsub::Subscription;
+
+use crate::batcher::{self, BatchConfig};
+use crate::colors::{self, ColorSettings};
+use crate::error::{self, AppError, AppResult};
+use crate::eventsub;
+use crate::image_cache::{self, ImageScale};
+use crate::mock::MockConfig;
+use crate::raids::{self, RaidWelcome};
+use crate::twitch_auth;
+
+// Everything that isn't a secret: what to subscribe to, where token files go, how often
+// to poll, how chat looks. Saved as JSON in the config dir next to the credential store.
+//
+// Changing the layout means bumping SCHEMA_VERSION and adding a step to MIGRATIONS, so
+// files from older versions are upgraded on load instead of losing their values.
+const SETTINGS_FILE: &str = "settings.json";
+pub const SCHEMA_VERSION: u32 = 1;
+// Step `n` upgrades a version `n` file to version `n + 1`
+const MIGRATIONS: &[fn(&mut Value)] = &[
+    // Files written before the version field already match version 1
+    |_| {},
+];
+
+static SETTINGS: Lazy<Mutex<Settings>> = Lazy::new(|| Mutex::new(Settings::default()));
+// The settings file of the active profile
+static STORE_FILE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+// What the settings file held when it was last read or written
+static LAST_CONTENTS: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+// How often the settings file is checked for edits made outside the app
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub twitch: TwitchSettings,
+    pub youtube: YoutubeSettings,
+    pub chat_throttle: BatchConfig,
+    pub colors: ColorSettings,
+    pub image_scale: ImageScale,
+    // Used when the mock generator is started without a config
+    pub mock: MockConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SCHEMA_VERSION,
+            twitch: TwitchSettings::default(),
+            youtube: YoutubeSettings::default(),
+            chat_throttle: BatchConfig::default(),
+            colors: ColorSettings::default(),
+            image_scale: ImageScale::Small,
+            mock: MockConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TwitchSettings {
+    // Local port for the browser login redirect
+    pub redirect_port: u16,
+    // EventSub subscription types, e.g. "channel.chat.message"
+    pub subscriptions: Vec<String>,
+    // Where the EventSub library briefly writes new tokens before they're moved into the
+    // credential store. Relative to the working directory.
+    pub token_file: String,
+    pub refresh_token_file: String,
+    // What to do when another channel raids
+    pub raid_welcome: RaidWelcome,
+}
+
+impl Default for TwitchSettings {
+    fn default() -> Self {
+        TwitchSettings {
+            redirect_port: twitch_auth::DEFAULT_REDIRECT_PORT,
+            subscriptions: vec![
+                Subscription::ChatMessage.tag(),
+                Subscription::ChannelFollow.tag(),
+                Subscription::ChannelNewSubscription.tag(),
+                Subscription::ChannelResubscription.tag(),
+                Subscription::ChannelGiftSubscription.tag(),
+                Subscription::ChannelCheer.tag(),
+                Subscription::ChannelRaid.tag(),
+            ],
+            token_file: ".user_token.env".to_string(),
+            refresh_token_file: ".refresh_token.env".to_string(),
+            raid_welcome: RaidWelcome::default(),
+        }
+    }
+}
+
+impl TwitchSettings {
+    // Subscriptions to request, skipping any type the library doesn't know and those
+    // older versions offered that the listener never handled
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.iter()
+            .filter(|tag| eventsub::is_available(tag))
+            .filter_map(|tag| parse_subscription(tag))
+            .collect()
+    }
+
+    // The token files are kept in the profile's config dir
+    pub fn token_files(&self, config_dir: &Path) -> [PathBuf; 2] {
+        [config_dir.join(&self.token_file), config_dir.join(&self.refresh_token_file)]
+    }
+
+    // Changes that only take effect when the listener reconnects
+    pub fn needs_restart(&self, other: &TwitchSettings) -> bool {
+        self.subscriptions != other.subscriptions
+            || self.token_file != other.token_file
+            || self.refresh_token_file != other.refresh_token_file
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct YoutubeSettings {
+    // Wait between polls while the stream has no chat yet
+    pub idle_poll_ms: u64,
+    // Wait between polls when YouTube doesn't say how long to wait
+    pub fallback_poll_ms: u64,
+}
+
+impl Default for YoutubeSettings {
+    fn default() -> Self {
+        YoutubeSettings {
+            idle_poll_ms: 1000,
+            fallback_poll_ms: 1000,
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> AppResult<()> {
+        if self.twitch.redirect_port == 0 {
+            return Err(AppError::config("Twitch redirect port can't be 0"));
+        }
+        for tag in &self.twitch.subscriptions {
+            if parse_subscription(tag).is_none() {
+                return Err(AppError::config(format!("Unknown Twitch subscription: {}", tag)));
+            }
+        }
+        validate_token_file(&self.twitch.token_file)?;
+        validate_token_file(&self.twitch.refresh_token_file)?;
+        if self.twitch.token_file == self.twitch.refresh_token_file {
+            return Err(AppError::config("Twitch token and refresh token files must be different"));
+        }
+        raids::validate(&self.twitch.raid_welcome)?;
+
+        if self.youtube.idle_poll_ms < 100 || self.youtube.fallback_poll_ms < 100 {
+            return Err(AppError::config("YouTube poll intervals must be at least 100ms"));
+        }
+
+        batcher::validate(&self.chat_throttle)?;
+        colors::validate(&self.colors)
+    }
+}
+
+fn parse_subscription(tag: &str) -> Option<Subscription> {
+    // Permission-only variants have an empty tag and can't be subscribed to
+    Subscription::from_string(tag).filter(|_| !tag.is_empty())
+}
+
+// Token files go in the profile's config dir, so they have to stay inside it
+fn validate_token_file(file: &str) -> AppResult<()> {
+    let path = Path::new(file);
+    if file.trim().is_empty() || path.is_absolute() || path.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(AppError::config(format!("Invalid Twitch token file {:?}, use a plain relative path", file)));
+    }
+    Ok(())
+}
+
+// Load the settings file in `config_dir` and apply it. Called from setup before anything
+// reads the settings, and again whenever the profile changes.
+pub fn init(config_dir: Option<PathBuf>) {
+    if let Ok(mut store_file) = STORE_FILE.lock() {
+        *store_file = config_dir.as_ref().map(|dir| dir.join(SETTINGS_FILE));
+    }
+
+    let Some(dir) = config_dir else {
+        warn!("No config directory, settings won't be saved");
+        let settings = Settings::default();
+        apply_or_warn(&settings);
+        if let Ok(mut current) = SETTINGS.lock() {
+            *current = settings;
+        }
+        return;
+    };
+
+    let path = dir.join(SETTINGS_FILE);
+
+    let settings = match fs::read_to_string(&path) {
+        Ok(contents) => match load(&path, &contents) {
+            Ok(settings) => settings,
+            Err(e) => {
+                // Keep the broken file around rather than overwriting it
+                let backup = path.with_extension("json.bak");
+                warn!("{}; moving {} to {} and using defaults", e, path.display(), backup.display());
+                let _ = fs::rename(&path, &backup);
+                Settings::default()
+            },
+        },
+        Err(_) => Settings::default(),
+    };
+
+    apply_or_warn(&settings);
+    if let Err(e) = save(&settings) {
+        warn!("{}", e);
+    }
+    if let Ok(mut current) = SETTINGS.lock() {
+        *current = settings;
+    }
+}
+
+fn load(path: &Path, contents: &str) -> AppResult<Settings> {
+    let value: Value = serde_json::from_str(contents)
+        .map_err(|e| AppError::config(format!("Unreadable settings file: {}", e)))?;
+
+    let version = schema_version(&value);
+    if version != SCHEMA_VERSION {
+        // Keep the original; a newer file can't be written back without losing what this
+        // version doesn't understand, and an older one is worth having if a migration misfires
+        let backup = path.with_extension(format!("v{}.json.bak", version));
+        if let Err(e) = fs::copy(path, &backup) {
+            warn!("Failed to back up {}: {}", path.display(), e);
+        }
+    }
+    parse(value)
+}
+
+fn schema_version(value: &Value) -> u32 {
+    value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32
+}
+
+// Migrate and validate settings from a file or an imported config
+pub fn parse(mut value: Value) -> AppResult<Settings> {
+    if !value.is_object() {
+        return Err(AppError::config("Settings must be a JSON object"));
+    }
+
+    let version = schema_version(&value);
+    if version > SCHEMA_VERSION {
+        warn!("Settings were written by a newer version ({}), unknown settings are ignored", version);
+    }
+
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(&mut value);
+    }
+    if version < SCHEMA_VERSION {
+        info!("Migrated settings from version {} to {}", version, SCHEMA_VERSION);
+    }
+    value["version"] = SCHEMA_VERSION.into();
+
+    let settings: Settings = serde_json::from_value(value)
+        .map_err(|e| AppError::config(format!("Invalid settings: {}", e)))?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+// Pick up edits made to the settings file outside the app. Returns the previous settings
+// if the file changed and was applied. An invalid edit is reported once and the current
+// settings stay in effect until the file is fixed.
+pub fn reload_if_changed() -> AppResult<Option<Settings>> {
+    let Some(path) = store_file() else {
+        return Ok(None);
+    };
+    // Held throughout, so an update can't land between reading the file and applying it
+    let mut current = lock()?;
+    // Deleted or mid-write; the next check sees the finished file
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+
+    {
+        let mut last = LAST_CONTENTS.lock()
+            .map_err(|e| AppError::internal(format!("Failed to lock settings: {}", e)))?;
+        if last.as_deref() == Some(contents.as_str()) {
+            return Ok(None);
+        }
+        *last = Some(contents.clone());
+    }
+
+    let settings = load(&path, &contents)
+        .map_err(|e| AppError::config(format!("Ignoring edit to {}: {}", path.display(), message(&e))))?;
+    apply(&settings)?;
+
+    info!("Reloaded settings from {}", path.display());
+    Ok(Some(std::mem::replace(&mut *current, settings)))
+}
+
+fn message(error: &AppError) -> String {
+    match error {
+        AppError::Config { message } | AppError::Internal { message } => message.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Check the settings file for outside edits every few seconds. `on_change` gets the
+// previous settings after a new version was applied; invalid edits go out as errors.
+pub fn watch(app: AppHandle, on_change: impl Fn(&AppHandle, &Settings) + Send + 'static) {
+    if WATCHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+            match reload_if_changed() {
+                Ok(Some(previous)) => {
+                    on_change(&app, &previous);
+                    let _ = app.emit_all("settings-changed", get());
+                },
+                Ok(None) => {},
+                Err(e) => error::emit(&app, "settings", &e),
+            }
+        }
+    });
+}
+
+// Push settings to the modules that keep their own copy
+fn apply(settings: &Settings) -> AppResult<()> {
+    batcher::set_config(settings.chat_throttle.clone())?;
+    colors::set_settings(settings.colors.clone())?;
+    image_cache::set_preferred_scale(settings.image_scale);
+    twitch_auth::set_redirect_port(settings.twitch.redirect_port)
+}
+
+fn apply_or_warn(settings: &Settings) {
+    if let Err(e) = apply(settings) {
+        warn!("Failed to apply settings: {}", e);
+    }
+}
+
+pub fn get() -> Settings {
+    SETTINGS.lock().map(|settings| settings.clone()).unwrap_or_default()
+}
+
+fn lock() -> AppResult<MutexGuard<'static, Settings>> {
+    SETTINGS.lock().map_err(|e| AppError::internal(format!("Failed to lock settings: {}", e)))
+}
+
+// Merge a partial settings object over the current settings, e.g.
+// `{"chat_throttle": {"slow_mode": true}}`. Validates, applies and saves the result and
+// returns the previous settings so callers can tell what changed. Keys the settings don't
+// have are refused, so a typo isn't silently dropped.
+pub fn update(changes: Value) -> AppResult<Settings> {
+    let mut current = lock()?;
+    let mut merged = serde_json::to_value(&*current)
+        .map_err(|e| AppError::internal(format!("Failed to serialize settings: {}", e)))?;
+    merge(&mut merged, changes, "")?;
+    merged["version"] = SCHEMA_VERSION.into();
+
+    let settings: Settings = serde_json::from_value(merged)
+        .map_err(|e| AppError::config(format!("Invalid settings: {}", e)))?;
+    store(&mut current, settings)
+}
+
+// Change settings in code; same as `update`
+pub fn update_with(change: impl FnOnce(&mut Settings)) -> AppResult<Settings> {
+    let mut current = lock()?;
+    let mut settings = current.clone();
+    change(&mut settings);
+    store(&mut current, settings)
+}
+
+// Validate, apply and save a complete set of settings. Returns the previous settings.
+pub fn replace(settings: Settings) -> AppResult<Settings> {
+    store(&mut *lock()?, settings)
+}
+
+// Callers hold the settings lock from reading the current settings until here, so
+// concurrent updates and reloads don't overwrite each other
+fn store(current: &mut Settings, settings: Settings) -> AppResult<Settings> {
+    settings.validate()?;
+    // Saved first, so settings that can't be written are never in use
+    save(&settings)?;
+    if let Err(e) = apply(&settings) {
+        apply_or_warn(current);
+        if let Err(e) = save(current) {
+            warn!("Failed to restore the previous settings: {}", e);
+        }
+        return Err(e);
+    }
+    Ok(std::mem::replace(current, settings))
+}
+
+// `path` is where `target` sits in the settings, for naming unknown keys
+fn merge(target: &mut Value, changes: Value, path: &str) -> AppResult<()> {
+    match (target, changes) {
+        (Value::Object(target), Value::Object(changes)) => {
+            for (key, value) in changes {
+                let key_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                let Some(target) = target.get_mut(&key) else {
+                    return Err(AppError::config(format!("Unknown setting: {}", key_path)));
+                };
+                merge(target, value, &key_path)?;
+            }
+        },
+        (target, changes) => *target = changes,
+    }
+    Ok(())
+}
+
+fn store_file() -> Option<PathBuf> {
+    STORE_FILE.lock().ok()?.clone()
+}
+
+fn save(settings: &Settings) -> AppResult<()> {
+    let Some(dir) = store_file().and_then(|path| path.parent().map(Path::to_path_buf)) else {
+        return Ok(());
+    };
+    let contents = save_to(&dir, settings)?;
+    // So the watcher doesn't mistake our own write for an outside edit
+    if let Ok(mut last) = LAST_CONTENTS.lock() {
+        *last = Some(contents);
+    }
+    Ok(())
+}
+
+// Write settings into a config dir, e.g. one for a new profile. Returns what was written.
+pub fn save_to(dir: &Path, settings: &Settings) -> AppResult<String> {
+    settings.validate()?;
+    let contents = serde_json::to_string_pretty(settings)
+        .map_err(|e| AppError::internal(format!("Failed to serialize settings: {}", e)))?;
+
+    fs::create_dir_all(dir)
+        .map_err(|e| AppError::config(format!("Failed to create config directory: {}", e)))?;
+    let path = dir.join(SETTINGS_FILE);
+    fs::write(&path, &contents)
+        .map_err(|e| AppError::config(format!("Failed to write {}: {}", path.display(), e)))?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_refuses_unknown_keys() {
+        let mut settings = serde_json::to_value(Settings::default()).unwrap();
+
+        let error = merge(&mut settings, serde_json::json!({ "youtub": { "idle_poll_ms": 500 } }), "").unwrap_err();
+        assert!(error.to_string().contains("youtub"));
+        let error = merge(&mut settings, serde_json::json!({ "youtube": { "idle_poll": 500 } }), "").unwrap_err();
+        assert!(error.to_string().contains("youtube.idle_poll"));
+
+        merge(&mut settings, serde_json::json!({ "youtube": { "idle_poll_ms": 500 } }), "").unwrap();
+        assert_eq!(settings["youtube"]["idle_poll_ms"], 500);
+    }
+
+    #[test]
+    fn settings_that_fail_to_save_are_not_used() {
+        // A file where the config dir should be, so the settings can't be written
+        let blocker = std::env::temp_dir().join(format!("streamchatbox-settings-blocker-{}", std::process::id()));
+        fs::write(&blocker, "").unwrap();
+        *STORE_FILE.lock().unwrap() = Some(blocker.join(SETTINGS_FILE));
+
+        let before = get();
+        let mut changed = before.clone();
+        changed.youtube.idle_poll_ms += 1;
+        assert!(replace(changed).is_err());
+        assert_eq!(get().youtube.idle_poll_ms, before.youtube.idle_poll_ms);
+
+        *STORE_FILE.lock().unwrap() = None;
+        let _ = fs::remove_file(&blocker);
+    }
+}
@@ -0,0 +1,271 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::mock::{MockGenerator, MockRates, Platform};
+
+// Handle for the running stress test so it can be stopped early
+static STRESS_TASK: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+static STRESS_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// Event name used by the benchmark so its emits pay the full IPC cost without
+// showing up in the chat box
+const BENCHMARK_EVENT: &str = "pipeline-benchmark";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StressConfig {
+    pub seed: Option<u64>,
+    // Total chat messages per second across both platforms
+    pub messages_per_second: u32,
+    pub duration_secs: u64,
+    // Fraction of messages that go to YouTube instead of Twitch
+    pub youtube_share: f64,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        StressConfig {
+            seed: None,
+            messages_per_second: 2000,
+            duration_secs: 30,
+            youtube_share: 0.2,
+        }
+    }
+}
+
+// Sent to the frontend once a second while the stress test runs, and once more at the end
+#[derive(Debug, Clone, Serialize)]
+pub struct StressProgress {
+    pub elapsed_ms: u128,
+    pub sent: u64,
+    pub target_rate: u32,
+    pub actual_rate: f64,
+    // How far the generator has fallen behind its schedule
+    pub lag_ms: u128,
+    pub max_lag_ms: u128,
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub total_ms: f64,
+    pub throughput_per_sec: f64,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p95_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+}
+
+impl LatencyStats {
+    fn from_samples(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+
+        let count = samples.len();
+        let total: Duration = samples.iter().sum();
+        let micros = |d: Duration| d.as_secs_f64() * 1_000_000.0;
+        let percentile = |p: f64| -> f64 {
+            if count == 0 {
+                return 0.0;
+            }
+            let index = ((count as f64 - 1.0) * p).round() as usize;
+            micros(samples[index])
+        };
+
+        LatencyStats {
+            count,
+            total_ms: total.as_secs_f64() * 1000.0,
+            throughput_per_sec: if total.is_zero() { 0.0 } else { count as f64 / total.as_secs_f64() },
+            mean_us: if count == 0 { 0.0 } else { micros(total) / count as f64 },
+            p50_us: percentile(0.50),
+            p95_us: percentile(0.95),
+            p99_us: percentile(0.99),
+            max_us: samples.last().map(|d| micros(*d)).unwrap_or(0.0),
+        }
+    }
+}
+
+// Per-stage timings for the Twitch chat pipeline
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub iterations: u32,
+    pub badge_processing: LatencyStats,
+    // Includes the badge lookups as well as building the JSON value
+    pub payload_build: LatencyStats,
+    pub serialization: LatencyStats,
+    pub emit: LatencyStats,
+    pub end_to_end: LatencyStats,
+}
+
+// Push chat messages through the real event path at the configured rate until
+// the duration is up or the test is stopped
+pub fn start(app: AppHandle, config: StressConfig) {
+    stop();
+
+    info!(
+        "Starting stress test: {} msg/s for {}s",
+        config.messages_per_second, config.duration_secs
+    );
+
+    STRESS_RUNNING.store(true, Ordering::SeqCst);
+    let task = tauri::async_runtime::spawn(async move {
+        run(app, config).await;
+        STRESS_RUNNING.store(false, Ordering::SeqCst);
+    });
+
+    if let Ok(mut slot) = STRESS_TASK.lock() {
+        *slot = Some(task);
+    }
+}
+
+pub fn stop() {
+    if let Ok(mut slot) = STRESS_TASK.lock() {
+        if let Some(task) = slot.take() {
+            task.abort();
+            info!("Stopped stress test");
+        }
+    }
+    STRESS_RUNNING.store(false, Ordering::SeqCst);
+}
+
+pub fn is_running() -> bool {
+    STRESS_RUNNING.load(Ordering::SeqCst)
+}
+
+async fn run(app: AppHandle, config: StressConfig) {
+    let mut generator = MockGenerator::new(config.seed, MockRates::default());
+    let rate = config.messages_per_second.max(1) as f64;
+    let duration = Duration::from_secs(config.duration_secs);
+    let tick = Duration::from_millis(5);
+
+    let start = Instant::now();
+    let mut last_report = start;
+    let mut sent: u64 = 0;
+    let mut max_lag = Duration::ZERO;
+    let mut youtube_owed = 0.0;
+
+    while start.elapsed() < duration {
+        // Catch up to where the schedule says we should be; if emitting is slower
+        // than the target rate the gap shows up as lag
+        let due = (start.elapsed().as_secs_f64() * rate) as u64;
+        let backlog = due.saturating_sub(sent);
+        let lag = Duration::from_secs_f64(backlog as f64 / rate);
+        max_lag = max_lag.max(lag);
+
+        for _ in 0..backlog {
+            youtube_owed += config.youtube_share;
+            let platform = if youtube_owed >= 1.0 {
+                youtube_owed -= 1.0;
+                Platform::Youtube
+            } else {
+                Platform::Twitch
+            };
+
+            match platform {
+                Platform::Twitch => {
+                    let message = generator.twitch_message(None, None);
+                    crate::handle_twitch_chat_message(&app, message);
+                },
+                Platform::Youtube => {
+                    let message = generator.youtube_message(None, None);
+                    crate::handle_youtube_chat_message(&app, &message);
+                }
+            }
+            sent += 1;
+        }
+
+        if last_report.elapsed() >= Duration::from_secs(1) {
+            last_report = Instant::now();
+            emit_progress(&app, start, sent, config.messages_per_second, lag, max_lag, false);
+        }
+
+        tokio::time::sleep(tick).await;
+    }
+
+    emit_progress(&app, start, sent, config.messages_per_second, Duration::ZERO, max_lag, true);
+    info!("Stress test finished: {} messages in {:?}", sent, start.elapsed());
+}
+
+fn emit_progress(
+    app: &AppHandle,
+    start: Instant,
+    sent: u64,
+    target_rate: u32,
+    lag: Duration,
+    max_lag: Duration,
+    finished: bool,
+) {
+    let elapsed = start.elapsed();
+    let progress = StressProgress {
+        elapsed_ms: elapsed.as_millis(),
+        sent,
+        target_rate,
+        actual_rate: sent as f64 / elapsed.as_secs_f64().max(0.001),
+        lag_ms: lag.as_millis(),
+        max_lag_ms: max_lag.as_millis(),
+        finished,
+    };
+
+    let _ = app.emit_all("stress-progress", progress);
+}
+
+// Time each stage of the Twitch chat pipeline over `iterations` generated messages
+pub fn run_benchmark(app: &AppHandle, iterations: u32, seed: Option<u64>) -> BenchmarkReport {
+    let mut generator = MockGenerator::new(seed.or(Some(0)), MockRates::default());
+    let messages = (0..iterations)
+        .map(|_| generator.twitch_message(None, None))
+        .collect::<Vec<_>>();
+
+    let capacity = iterations as usize;
+    let mut badge_samples = Vec::with_capacity(capacity);
+    let mut payload_samples = Vec::with_capacity(capacity);
+    let mut serialize_samples = Vec::with_capacity(capacity);
+    let mut emit_samples = Vec::with_capacity(capacity);
+    let mut end_to_end_samples = Vec::with_capacity(capacity);
+
+    for message in &messages {
+        let started = Instant::now();
+
+        let stage = Instant::now();
+        let _ = crate::badges::process_message_badges(&message.broadcaster.id, &message.badges);
+        badge_samples.push(stage.elapsed());
+
+        let stage = Instant::now();
+        let payload = crate::twitch_chat_payload(message);
+        payload_samples.push(stage.elapsed());
+
+        let stage = Instant::now();
+        let _ = serde_json::to_string(&payload);
+        serialize_samples.push(stage.elapsed());
+
+        let stage = Instant::now();
+        let _ = app.emit_all(BENCHMARK_EVENT, payload);
+        emit_samples.push(stage.elapsed());
+
+        end_to_end_samples.push(started.elapsed());
+    }
+
+    let report = BenchmarkReport {
+        iterations,
+        badge_processing: LatencyStats::from_samples(badge_samples),
+        payload_build: LatencyStats::from_samples(payload_samples),
+        serialization: LatencyStats::from_samples(serialize_samples),
+        emit: LatencyStats::from_samples(emit_samples),
+        end_to_end: LatencyStats::from_samples(end_to_end_samples),
+    };
+
+    info!(
+        "Pipeline benchmark: {} messages, {:.0} msg/s end to end (p99 {:.0}us)",
+        iterations, report.end_to_end.throughput_per_sec, report.end_to_end.p99_us
+    );
+
+    report
+}
@@ -0,0 +1,526 @@
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Client, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use twitch_eventsub::Subscription;
+
+use crate::credentials;
+use crate::error::{AppError, AppResult};
+use crate::settings;
+use crate::validation;
+
+// Twitch user tokens: logging in, inspecting, refreshing and revoking. The tokens
+// themselves live in the credential store.
+//
+// There are two ways to log in. The browser redirect (authorization code) flow needs a
+// free local port matching the redirect URL registered for the Twitch app. The device
+// code flow shows a code to enter on twitch.tv/activate and needs no port at all.
+const TWITCH_AUTH_URL: &str = "https://id.twitch.tv/oauth2";
+pub const DEFAULT_REDIRECT_PORT: u16 = 3001;
+// How long to wait for the user to finish logging in in the browser
+const AUTHORIZE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+static REDIRECT_PORT: AtomicU16 = AtomicU16::new(DEFAULT_REDIRECT_PORT);
+// The device code login being polled, if any
+static DEVICE_LOGIN: Lazy<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+
+// Response of the token validation endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenInfo {
+    pub client_id: String,
+    #[serde(default)]
+    pub login: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Seconds; 0 for tokens that don't expire
+    #[serde(default)]
+    pub expires_in: i64,
+}
+
+// What the settings show about the current token
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenStatus {
+    pub login: Option<String>,
+    pub user_id: Option<String>,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+    pub can_refresh: bool,
+}
+
+impl TokenStatus {
+    fn new(info: TokenInfo, can_refresh: bool) -> Self {
+        let expires_at = (info.expires_in > 0)
+            .then(|| (chrono::Utc::now() + chrono::Duration::seconds(info.expires_in)).to_rfc3339());
+
+        TokenStatus {
+            login: info.login,
+            user_id: info.user_id,
+            scopes: info.scopes,
+            expires_at,
+            can_refresh,
+        }
+    }
+}
+
+// Extra permissions a feature can ask for on top of what chat needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Moderation,
+    ChannelPoints,
+}
+
+impl Permission {
+    fn subscriptions(self) -> Vec<Subscription> {
+        match self {
+            Permission::Moderation => vec![
+                Subscription::PermissionBanTimeoutUser,
+                Subscription::PermissionDeleteMessage,
+                Subscription::PermissionReadModerator,
+                Subscription::PermissionReadChatters,
+            ],
+            Permission::ChannelPoints => vec![
+                Subscription::ChannelPointsCustomRewardRedeem,
+                Subscription::PermissionManageRewards,
+            ],
+        }
+    }
+}
+
+// Shown while waiting for the user to enter the code
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceLogin {
+    pub user_code: String,
+    pub verification_uri: String,
+    pub expires_at: String,
+}
+
+#[derive(Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+// Payload of the `twitch-login` event sent when a device code login ends
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum LoginEvent {
+    Success { token: TokenStatus },
+    Failed { error: AppError },
+}
+
+#[derive(Deserialize, Default)]
+struct ErrorBody {
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+// Ask Twitch about a token
+pub async fn validate_token(client: &Client, token: &str) -> AppResult<TokenInfo> {
+    let response = client.get(format!("{}/validate", TWITCH_AUTH_URL))
+        .header(AUTHORIZATION, format!("OAuth {}", token))
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    let status = response.status();
+    if status == StatusCode::UNAUTHORIZED {
+        return Err(AppError::auth("twitch", "Token is expired or revoked"));
+    }
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+
+    response.json().await.map_err(|e| AppError::from_request("twitch", e))
+}
+
+// Login, scopes and expiry of the stored token
+pub async fn token_status(client: &Client) -> AppResult<TokenStatus> {
+    let (_, _, token) = credentials::get().twitch_api()?;
+    let can_refresh = !credentials::get().twitch_refresh_token.is_empty();
+
+    let info = validate_token(client, &token).await?;
+    Ok(TokenStatus::new(info, can_refresh))
+}
+
+// Trade the refresh token for a new access token and store both
+pub async fn refresh(client: &Client) -> AppResult<TokenStatus> {
+    let stored = credentials::get();
+    let client_id = stored.twitch_client_id.trim();
+    let refresh_token = stored.twitch_refresh_token.trim();
+    if refresh_token.is_empty() {
+        return Err(AppError::auth("twitch", "No refresh token, log in again"));
+    }
+
+    let token = request_token(client, &[
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+        ("client_id", client_id),
+        ("client_secret", stored.twitch_client_secret.trim()),
+    ]).await?;
+
+    store_token(token)?;
+    info!("Refreshed Twitch token");
+    token_status(client).await
+}
+
+// Revoke the token with Twitch and forget it
+pub async fn revoke(client: &Client) -> AppResult<()> {
+    let stored = credentials::get();
+    let token = stored.twitch_user_token.trim();
+
+    if !token.is_empty() {
+        let response = client.post(format!("{}/revoke", TWITCH_AUTH_URL))
+            .form(&[("client_id", stored.twitch_client_id.trim()), ("token", token)])
+            .send()
+            .await
+            .map_err(|e| AppError::from_request("twitch", e))?;
+
+        // 400 means the token was already invalid, which is just as good
+        let status = response.status();
+        if !status.is_success() && status != StatusCode::BAD_REQUEST {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::from_status("twitch", status, &body));
+        }
+    }
+
+    credentials::update(|credentials| {
+        credentials.twitch_user_token.clear();
+        credentials.twitch_refresh_token.clear();
+    })?;
+    info!("Logged out of Twitch");
+    Ok(())
+}
+
+// Scopes for a new login: what chat needs, what the current token already has, what the
+// raid welcome action needs and whatever the requested features add
+pub async fn scopes_for(client: &Client, base: &[Subscription], permissions: &[Permission]) -> Vec<String> {
+    let mut subscriptions = base.to_vec();
+    for permission in permissions {
+        subscriptions.extend(permission.subscriptions());
+    }
+    let mut scopes = validation::required_scopes(&subscriptions);
+    scopes.extend(settings::get().twitch.raid_welcome.required_scopes());
+
+    if let Ok(status) = token_status(client).await {
+        scopes.extend(status.scopes);
+    }
+    scopes.sort();
+    scopes.dedup();
+    scopes
+}
+
+// Log in through the browser with the authorization code flow and store the new tokens.
+// Twitch redirects back to a one-shot listener on the redirect port.
+pub async fn authorize(app: &AppHandle, client: &Client, scopes: &[String]) -> AppResult<TokenStatus> {
+    let stored = credentials::get();
+    let client_id = stored.twitch_client_id.trim().to_string();
+    let client_secret = stored.twitch_client_secret.trim().to_string();
+    if client_id.is_empty() || client_secret.is_empty() {
+        return Err(AppError::config("Set the Twitch client ID and secret before logging in"));
+    }
+
+    let port = redirect_port();
+    let redirect_url = redirect_url();
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| port_taken_error(port, e))?;
+
+    let state: String = rand::thread_rng().sample_iter(&Alphanumeric).take(24).map(char::from).collect();
+    let scope = scopes.join(" ");
+    let authorize_url = Url::parse_with_params(&format!("{}/authorize", TWITCH_AUTH_URL), &[
+        ("response_type", "code"),
+        ("client_id", client_id.as_str()),
+        ("redirect_uri", redirect_url.as_str()),
+        ("scope", scope.as_str()),
+        ("state", state.as_str()),
+        // Show the consent screen again so the new scopes are granted
+        ("force_verify", "true"),
+    ]).map_err(|e| AppError::internal(format!("Failed to build authorize URL: {}", e)))?;
+
+    info!("Opening browser for Twitch login with scopes: {}", scope);
+    tauri::api::shell::open(&app.shell_scope(), authorize_url.as_str(), None)
+        .map_err(|e| AppError::internal(format!("Failed to open browser: {}", e)))?;
+
+    let code = tokio::time::timeout(AUTHORIZE_TIMEOUT, wait_for_code(&listener, &state))
+        .await
+        .map_err(|_| AppError::auth("twitch", "Timed out waiting for the Twitch login"))??;
+
+    let token = request_token(client, &[
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("client_id", client_id.as_str()),
+        ("client_secret", client_secret.as_str()),
+        ("redirect_uri", redirect_url.as_str()),
+    ]).await?;
+
+    store_token(token)?;
+    info!("Logged in to Twitch");
+    token_status(client).await
+}
+
+pub fn redirect_port() -> u16 {
+    REDIRECT_PORT.load(Ordering::SeqCst)
+}
+
+pub fn set_redirect_port(port: u16) -> AppResult<()> {
+    if port == 0 {
+        return Err(AppError::config("Redirect port can't be 0"));
+    }
+    REDIRECT_PORT.store(port, Ordering::SeqCst);
+    Ok(())
+}
+
+// Also has to be added as an OAuth redirect URL of the Twitch app
+pub fn redirect_url() -> String {
+    format!("http://localhost:{}", redirect_port())
+}
+
+// Fail early instead of letting a browser login hang or crash on a port another tool uses
+pub fn check_redirect_port() -> AppResult<()> {
+    let port = redirect_port();
+    std::net::TcpListener::bind(("127.0.0.1", port))
+        .map(drop)
+        .map_err(|e| port_taken_error(port, e))
+}
+
+fn port_taken_error(port: u16, error: std::io::Error) -> AppError {
+    AppError::config(format!(
+        "Can't listen for the Twitch login on port {}: {}. Pick another redirect port or log in with a device code",
+        port, error
+    ))
+}
+
+// Start a device code login. Returns the code to show; polling continues in the
+// background and ends with a `twitch-login` event. `on_login` runs after the new tokens
+// are stored.
+pub async fn start_device_login(
+    app: AppHandle,
+    client: Client,
+    scopes: Vec<String>,
+    on_login: impl FnOnce(&AppHandle) + Send + 'static,
+) -> AppResult<DeviceLogin> {
+    let stored = credentials::get();
+    let client_id = stored.twitch_client_id.trim().to_string();
+    if client_id.is_empty() {
+        return Err(AppError::config("Set the Twitch client ID before logging in"));
+    }
+    let scope = scopes.join(" ");
+
+    let response = client.post(format!("{}/device", TWITCH_AUTH_URL))
+        .form(&[("client_id", client_id.as_str()), ("scopes", scope.as_str())])
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+    let device: DeviceCodeResponse = response.json().await.map_err(|e| AppError::from_request("twitch", e))?;
+
+    let login = DeviceLogin {
+        user_code: device.user_code.clone(),
+        verification_uri: device.verification_uri.clone(),
+        expires_at: (chrono::Utc::now() + chrono::Duration::seconds(device.expires_in as i64)).to_rfc3339(),
+    };
+    info!("Waiting for Twitch device login with code {}", device.user_code);
+
+    cancel_device_login();
+    let client_secret = stored.twitch_client_secret.trim().to_string();
+    let handle = tauri::async_runtime::spawn(async move {
+        let result = poll_device_login(&client, &client_id, &client_secret, &scope, &device).await;
+
+        let event = match result.and_then(store_token) {
+            Ok(_) => match token_status(&client).await {
+                Ok(token) => {
+                    info!("Logged in to Twitch with a device code");
+                    on_login(&app);
+                    LoginEvent::Success { token }
+                },
+                Err(error) => LoginEvent::Failed { error },
+            },
+            Err(error) => {
+                warn!("Twitch device login failed: {}", error);
+                LoginEvent::Failed { error }
+            },
+        };
+        let _ = app.emit_all("twitch-login", event);
+    });
+
+    if let Ok(mut current) = DEVICE_LOGIN.lock() {
+        *current = Some(handle);
+    }
+    Ok(login)
+}
+
+// Stop waiting for a device code login
+pub fn cancel_device_login() {
+    if let Some(handle) = DEVICE_LOGIN.lock().ok().and_then(|mut current| current.take()) {
+        handle.abort();
+    }
+}
+
+async fn poll_device_login(
+    client: &Client,
+    client_id: &str,
+    client_secret: &str,
+    scope: &str,
+    device: &DeviceCodeResponse,
+) -> AppResult<TokenResponse> {
+    let deadline = std::time::Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = Duration::from_secs(device.interval.max(1));
+
+    let mut form = vec![
+        ("client_id", client_id),
+        ("scopes", scope),
+        ("device_code", device.device_code.as_str()),
+        ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+    ];
+    // Confidential apps have to prove who they are as well
+    if !client_secret.is_empty() {
+        form.push(("client_secret", client_secret));
+    }
+
+    loop {
+        tokio::time::sleep(interval).await;
+        if std::time::Instant::now() >= deadline {
+            return Err(AppError::auth("twitch", "The login code expired, start again"));
+        }
+
+        let response = client.post(format!("{}/token", TWITCH_AUTH_URL))
+            .form(&form)
+            .send()
+            .await;
+
+        let response = match response {
+            Ok(response) => response,
+            // Keep trying through short network hiccups until the code expires
+            Err(e) => {
+                warn!("Polling Twitch device login failed: {}", e);
+                continue;
+            },
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return response.json().await.map_err(|e| AppError::from_request("twitch", e));
+        }
+
+        let body: ErrorBody = response.json().await.unwrap_or_default();
+        match body.message.as_str() {
+            "authorization_pending" => {},
+            "slow_down" => interval += Duration::from_secs(5),
+            "access_denied" => return Err(AppError::auth("twitch", "Login was declined")),
+            "invalid device code" | "expired_token" => return Err(AppError::auth("twitch", "The login code expired, start again")),
+            message => return Err(AppError::platform_api("twitch", Some(status.as_u16()), message.to_string())),
+        }
+    }
+}
+
+// Accept connections until Twitch redirects back with our state, answering the browser
+// with a page it can close
+async fn wait_for_code(listener: &TcpListener, state: &str) -> AppResult<String> {
+    loop {
+        let (mut stream, _) = listener.accept()
+            .await
+            .map_err(|e| AppError::network(format!("Twitch login redirect failed: {}", e)))?;
+
+        let mut buffer = vec![0u8; 8192];
+        let read = stream.read(&mut buffer).await.unwrap_or(0);
+        let request = String::from_utf8_lossy(&buffer[..read]);
+
+        // "GET /?code=...&scope=...&state=... HTTP/1.1"
+        let path = request.split_whitespace().nth(1).unwrap_or("/");
+        let Ok(url) = Url::parse(&format!("http://localhost{}", path)) else {
+            continue;
+        };
+        let param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+
+        if param("state").as_deref() != Some(state) {
+            // Favicon requests and anything else that isn't the redirect
+            let _ = respond(&mut stream, "404 Not Found", "").await;
+            continue;
+        }
+
+        if let Some(error) = param("error") {
+            let description = param("error_description").unwrap_or_default();
+            let _ = respond(&mut stream, "200 OK", "Twitch login was cancelled. You can close this window.").await;
+            return Err(AppError::auth("twitch", format!("Login failed: {} {}", error, description)));
+        }
+
+        match param("code") {
+            Some(code) => {
+                let _ = respond(&mut stream, "200 OK", "Logged in to Twitch. You can close this window and return to StreamChatBox.").await;
+                return Ok(code);
+            },
+            None => {
+                let _ = respond(&mut stream, "400 Bad Request", "Missing authorization code").await;
+            },
+        }
+    }
+}
+
+async fn respond(stream: &mut tokio::net::TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await
+}
+
+async fn request_token(client: &Client, form: &[(&str, &str)]) -> AppResult<TokenResponse> {
+    let response = client.post(format!("{}/token", TWITCH_AUTH_URL))
+        .form(form)
+        .send()
+        .await
+        .map_err(|e| AppError::from_request("twitch", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        warn!("Twitch token request failed: {} {}", status, body);
+        // A bad refresh token or code comes back as 400
+        if status == StatusCode::BAD_REQUEST {
+            return Err(AppError::auth("twitch", "Twitch rejected the login, log in again"));
+        }
+        return Err(AppError::from_status("twitch", status, &body));
+    }
+
+    response.json().await.map_err(|e| AppError::from_request("twitch", e))
+}
+
+fn store_token(token: TokenResponse) -> AppResult<()> {
+    credentials::update(|credentials| {
+        credentials.twitch_user_token = token.access_token;
+        if let Some(refresh_token) = token.refresh_token {
+            credentials.twitch_refresh_token = refresh_token;
+        }
+    })
+}
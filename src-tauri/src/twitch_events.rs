@@ -0,0 +1,246 @@
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use twitch_eventsub::{CheerData, CustomPointsRewardRedeemData, GiftData, NewSubscriptionData, RaidData, ResubscriptionData};
+
+use crate::emotes::{self, MessageFragment};
+
+// Twitch EventSub notifications that go to the activity feed, as typed payloads.
+// The mock generator emits the same types for the events it fakes, so the frontend
+// only handles one shape.
+
+// Payload of the `twitch-subscription` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SubscriptionEvent {
+    // A first-time subscription the viewer paid for
+    Subscribe {
+        username: String,
+        tier: u8,
+    },
+    // A viewer received a gifted subscription. Twitch sends one of these per recipient
+    // after the `Gift` that paid for them, without saying who the gifter was.
+    Gifted {
+        recipient: String,
+        tier: u8,
+    },
+    // A subscriber shared their resub message
+    Resubscribe {
+        username: String,
+        tier: u8,
+        cumulative_months: u32,
+        // Only set if the viewer chose to share it
+        streak_months: Option<u32>,
+        // Months paid for in advance
+        duration_months: u32,
+        message: String,
+    },
+    // A viewer gifted one or more subscriptions to the community
+    Gift {
+        // None for anonymous gifts
+        gifter: Option<String>,
+        tier: u8,
+        count: u32,
+        // Gifts from this viewer in the channel so far, if they share it
+        cumulative_total: Option<u32>,
+    },
+}
+
+impl From<&NewSubscriptionData> for SubscriptionEvent {
+    fn from(data: &NewSubscriptionData) -> Self {
+        let tier = parse_tier(&data.tier);
+        if data.is_gift {
+            SubscriptionEvent::Gifted { recipient: data.user.name.clone(), tier }
+        } else {
+            SubscriptionEvent::Subscribe { username: data.user.name.clone(), tier }
+        }
+    }
+}
+
+impl From<&ResubscriptionData> for SubscriptionEvent {
+    fn from(data: &ResubscriptionData) -> Self {
+        SubscriptionEvent::Resubscribe {
+            username: data.user.name.clone(),
+            tier: parse_tier(&data.tier),
+            cumulative_months: data.cumulative_months,
+            streak_months: data.streak_months,
+            duration_months: data.duration_months,
+            message: data.message.text.clone(),
+        }
+    }
+}
+
+impl From<&GiftData> for SubscriptionEvent {
+    fn from(data: &GiftData) -> Self {
+        SubscriptionEvent::Gift {
+            gifter: if data.is_anonymous { None } else { data.user.name.clone() },
+            tier: parse_tier(&data.tier),
+            count: data.total,
+            cumulative_total: data.cumulative_total,
+        }
+    }
+}
+
+// Twitch sends tiers as "1000", "2000" and "3000"
+fn parse_tier(tier: &str) -> u8 {
+    match tier {
+        "2000" => 2,
+        "3000" => 3,
+        _ => 1,
+    }
+}
+
+pub fn emit_subscription(app: &AppHandle, event: SubscriptionEvent) {
+    match &event {
+        SubscriptionEvent::Subscribe { username, tier } => info!("{} subscribed with tier {}!", username, tier),
+        SubscriptionEvent::Gifted { recipient, tier } => info!("{} received a gifted tier {} sub!", recipient, tier),
+        SubscriptionEvent::Resubscribe { username, cumulative_months, .. } => {
+            info!("{} resubscribed for {} months!", username, cumulative_months)
+        },
+        SubscriptionEvent::Gift { gifter, count, .. } => {
+            info!("{} gifted {} subs!", gifter.as_deref().unwrap_or("An anonymous viewer"), count)
+        },
+    }
+
+    let _ = app.emit_all("twitch-subscription", event);
+}
+
+// Payload of the `twitch-cheer` event
+#[derive(Debug, Clone, Serialize)]
+pub struct CheerEvent {
+    // None for anonymous cheers
+    pub username: Option<String>,
+    pub bits: u32,
+    pub message: String,
+    // The message with its cheermotes resolved to images
+    pub fragments: Vec<MessageFragment>,
+}
+
+// A channel.cheer notification. The library's CheerData requires a user, so anonymous
+// cheers (which have none) only arrive as raw messages and are read into this instead.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cheer {
+    pub is_anonymous: bool,
+    #[serde(rename = "user_name")]
+    pub username: Option<String>,
+    pub broadcaster_user_id: String,
+    pub message: String,
+    pub bits: u32,
+}
+
+impl From<&CheerData> for Cheer {
+    fn from(data: &CheerData) -> Self {
+        Cheer {
+            is_anonymous: data.is_anonymous,
+            username: Some(data.user.name.clone()),
+            broadcaster_user_id: data.broadcaster.id.clone(),
+            message: data.message.clone(),
+            bits: data.bits,
+        }
+    }
+}
+
+pub fn emit_cheer(app: &AppHandle, cheer: &Cheer) {
+    let event = CheerEvent {
+        username: cheer.username.clone().filter(|_| !cheer.is_anonymous),
+        bits: cheer.bits,
+        message: cheer.message.clone(),
+        fragments: emotes::cheer_fragments(&cheer.broadcaster_user_id, &cheer.message),
+    };
+    info!("{} cheered {} bits!", event.username.as_deref().unwrap_or("An anonymous viewer"), event.bits);
+
+    let _ = app.emit_all("twitch-cheer", event);
+}
+
+// Payload of the `twitch-raid` event
+#[derive(Debug, Clone, Serialize)]
+pub struct RaidEvent {
+    // Display name of the raiding channel
+    pub from: String,
+    pub from_login: String,
+    pub viewers: u32,
+}
+
+impl From<&RaidData> for RaidEvent {
+    fn from(data: &RaidData) -> Self {
+        RaidEvent {
+            from: data.from_broadcaster.name.clone(),
+            from_login: data.from_broadcaster.login.clone(),
+            viewers: data.viewers,
+        }
+    }
+}
+
+pub fn emit_raid(app: &AppHandle, event: RaidEvent) {
+    info!("{} is raiding with {} viewers!", event.from, event.viewers);
+
+    let _ = app.emit_all("twitch-raid", event);
+}
+
+// Payload of the `twitch-redemption` event
+#[derive(Debug, Clone, Serialize)]
+pub struct RedemptionEvent {
+    pub username: String,
+    // Title of the custom reward
+    pub reward: String,
+    pub cost: u32,
+    // What the viewer typed, for rewards that ask for text
+    pub user_input: Option<String>,
+}
+
+impl From<&CustomPointsRewardRedeemData> for RedemptionEvent {
+    fn from(data: &CustomPointsRewardRedeemData) -> Self {
+        RedemptionEvent {
+            username: data.user.name.clone(),
+            reward: data.reward.title.clone(),
+            cost: data.reward.cost,
+            user_input: Some(data.user_input.clone()).filter(|input| !input.trim().is_empty()),
+        }
+    }
+}
+
+pub fn emit_redemption(app: &AppHandle, event: RedemptionEvent) {
+    info!("{} redeemed {} for {} points!", event.username, event.reward, event.cost);
+
+    let _ = app.emit_all("twitch-redemption", event);
+}
+
+#[derive(Deserialize)]
+struct RawMessage {
+    metadata: RawMetadata,
+    payload: RawPayload,
+}
+
+#[derive(Deserialize)]
+struct RawMetadata {
+    message_type: String,
+    subscription_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawPayload {
+    event: Option<Value>,
+}
+
+// Messages the library couldn't parse are handed over as raw JSON. Notifications we can
+// read ourselves are emitted like their parsed counterparts.
+pub fn handle_raw(app: &AppHandle, raw: &str) {
+    let Ok(message) = serde_json::from_str::<RawMessage>(raw) else {
+        debug!("Ignoring unreadable Twitch message: {}", raw);
+        return;
+    };
+    if message.metadata.message_type != "notification" {
+        return;
+    }
+
+    let subscription_type = message.metadata.subscription_type.unwrap_or_default();
+    let event = message.payload.event.unwrap_or_default();
+    match subscription_type.as_str() {
+        "channel.cheer" => match serde_json::from_value::<Cheer>(event) {
+            Ok(cheer) => emit_cheer(app, &cheer),
+            Err(e) => debug!("Ignoring unreadable cheer: {}", e),
+        },
+        other => debug!("Ignoring unhandled Twitch notification {}", other),
+    }
+}
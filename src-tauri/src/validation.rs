@@ -0,0 +1,680 @@
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use twitch_eventsub::Subscription;
+
+use crate::credentials::Credentials;
+use crate::eventsub;
+use crate::twitch_auth::TokenInfo;
+
+// Checks credentials against the Twitch and YouTube APIs before they're used, so typos
+// show up next to the field instead of as a failed listener later on.
+pub struct Endpoints {
+    pub twitch_auth: String,
+    pub twitch_api: String,
+    pub youtube_api: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints {
+            twitch_auth: "https://id.twitch.tv".to_string(),
+            twitch_api: "https://api.twitch.tv".to_string(),
+            youtube_api: "https://www.googleapis.com".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldStatus {
+    Ok,
+    // Usable, but something needs attention
+    Warning,
+    Error,
+    // Not set, or couldn't be checked because another field is wrong
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldResult {
+    pub status: FieldStatus,
+    pub message: String,
+}
+
+impl FieldResult {
+    fn ok(message: impl Into<String>) -> Self {
+        FieldResult { status: FieldStatus::Ok, message: message.into() }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        FieldResult { status: FieldStatus::Warning, message: message.into() }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        FieldResult { status: FieldStatus::Error, message: message.into() }
+    }
+
+    fn skipped(message: impl Into<String>) -> Self {
+        FieldResult { status: FieldStatus::Skipped, message: message.into() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    // False if any field has an error
+    pub valid: bool,
+    pub twitch_client_id: FieldResult,
+    pub twitch_client_secret: FieldResult,
+    pub twitch_broadcaster_id: FieldResult,
+    pub twitch_user_token: FieldResult,
+    pub youtube_channel_id: FieldResult,
+    pub youtube_api_key: FieldResult,
+}
+
+// Scopes the user token needs for these subscriptions
+pub fn required_scopes(subscriptions: &[Subscription]) -> Vec<String> {
+    let mut scopes = subscriptions.iter()
+        .flat_map(|subscription| subscription.required_scope()
+            .split('+')
+            .map(str::to_string)
+            .collect::<Vec<_>>())
+        .filter(|scope| !scope.is_empty())
+        .collect::<Vec<_>>();
+    scopes.sort();
+    scopes.dedup();
+    scopes
+}
+
+pub async fn validate(
+    client: &Client,
+    endpoints: &Endpoints,
+    credentials: &Credentials,
+    required_scopes: &[String],
+) -> ValidationReport {
+    let twitch = validate_twitch(client, endpoints, credentials, required_scopes);
+    let youtube = validate_youtube(client, endpoints, credentials);
+    let ((twitch_client_id, twitch_client_secret, twitch_broadcaster_id, twitch_user_token), (youtube_channel_id, youtube_api_key)) =
+        tokio::join!(twitch, youtube);
+
+    let valid = [&twitch_client_id, &twitch_client_secret, &twitch_broadcaster_id, &twitch_user_token, &youtube_channel_id, &youtube_api_key]
+        .iter()
+        .all(|field| field.status != FieldStatus::Error);
+
+    ValidationReport {
+        valid,
+        twitch_client_id,
+        twitch_client_secret,
+        twitch_broadcaster_id,
+        twitch_user_token,
+        youtube_channel_id,
+        youtube_api_key,
+    }
+}
+
+#[derive(Deserialize)]
+struct AppTokenResponse {
+    access_token: String,
+}
+
+// Twitch error bodies look like `{"status":403,"message":"invalid client secret"}`
+#[derive(Deserialize, Default)]
+struct TwitchErrorBody {
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct UsersResponse {
+    data: Vec<TwitchUser>,
+}
+
+#[derive(Deserialize)]
+struct TwitchUser {
+    login: String,
+}
+
+async fn validate_twitch(
+    client: &Client,
+    endpoints: &Endpoints,
+    credentials: &Credentials,
+    required_scopes: &[String],
+) -> (FieldResult, FieldResult, FieldResult, FieldResult) {
+    let client_id = credentials.twitch_client_id.trim();
+    let client_secret = credentials.twitch_client_secret.trim();
+    let broadcaster_id = credentials.twitch_broadcaster_id.trim();
+
+    // An app token proves the client ID and secret belong together
+    let (client_id_result, secret_result, app_token) = match (client_id.is_empty(), client_secret.is_empty()) {
+        (true, _) => (FieldResult::skipped("Not set"), FieldResult::skipped("Not set"), None),
+        (false, true) => (FieldResult::skipped("Needs the client secret to be checked"), FieldResult::skipped("Not set"), None),
+        (false, false) => check_client(client, endpoints, client_id, client_secret).await,
+    };
+
+    let broadcaster_result = if broadcaster_id.is_empty() {
+        FieldResult::skipped("Not set")
+    } else if !broadcaster_id.bytes().all(|b| b.is_ascii_digit()) {
+        FieldResult::error("Broadcaster IDs are numeric, this looks like a login name")
+    } else if let Some(app_token) = &app_token {
+        check_broadcaster(client, endpoints, client_id, app_token, broadcaster_id).await
+    } else {
+        FieldResult::skipped("Needs a valid client ID and secret to be checked")
+    };
+
+    let token_result = if credentials.twitch_user_token.trim().is_empty() {
+        FieldResult::skipped("No token yet, start the Twitch listener to log in")
+    } else {
+        check_user_token(client, endpoints, credentials, required_scopes).await
+    };
+
+    (client_id_result, secret_result, broadcaster_result, token_result)
+}
+
+async fn check_client(
+    client: &Client,
+    endpoints: &Endpoints,
+    client_id: &str,
+    client_secret: &str,
+) -> (FieldResult, FieldResult, Option<String>) {
+    let response = client.post(format!("{}/oauth2/token", endpoints.twitch_auth))
+        .form(&[
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("grant_type", "client_credentials"),
+        ])
+        .send()
+        .await;
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            let unreachable = FieldResult::warning(format!("Couldn't reach Twitch: {}", e));
+            return (unreachable.clone(), unreachable, None);
+        },
+    };
+
+    let status = response.status();
+    if status.is_success() {
+        return match response.json::<AppTokenResponse>().await {
+            Ok(token) => (FieldResult::ok("Valid"), FieldResult::ok("Valid"), Some(token.access_token)),
+            Err(e) => {
+                let unreadable = FieldResult::warning(format!("Unexpected response from Twitch: {}", e));
+                (unreadable.clone(), unreadable, None)
+            },
+        };
+    }
+
+    let body: TwitchErrorBody = response.json().await.unwrap_or_default();
+    let message = body.message.to_lowercase();
+
+    if message.contains("invalid client secret") {
+        (FieldResult::ok("Valid"), FieldResult::error("Twitch rejected the client secret"), None)
+    } else if message.contains("invalid client") || status == StatusCode::BAD_REQUEST {
+        (FieldResult::error("Twitch doesn't know this client ID"), FieldResult::skipped("Needs a valid client ID to be checked"), None)
+    } else {
+        let failed = FieldResult::warning(format!("Twitch answered {}: {}", status, body.message));
+        (failed.clone(), failed, None)
+    }
+}
+
+async fn check_broadcaster(
+    client: &Client,
+    endpoints: &Endpoints,
+    client_id: &str,
+    app_token: &str,
+    broadcaster_id: &str,
+) -> FieldResult {
+    let response = client.get(format!("{}/helix/users", endpoints.twitch_api))
+        .header("Client-Id", client_id)
+        .bearer_auth(app_token)
+        .query(&[("id", broadcaster_id)])
+        .send()
+        .await;
+
+    let response = match response {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => return FieldResult::warning(format!("Twitch answered {}", response.status())),
+        Err(e) => return FieldResult::warning(format!("Couldn't reach Twitch: {}", e)),
+    };
+
+    match response.json::<UsersResponse>().await {
+        Ok(users) => match users.data.first() {
+            Some(user) => FieldResult::ok(format!("Channel: {}", user.login)),
+            None => FieldResult::error("No Twitch user has this ID"),
+        },
+        Err(e) => FieldResult::warning(format!("Unexpected response from Twitch: {}", e)),
+    }
+}
+
+async fn check_user_token(
+    client: &Client,
+    endpoints: &Endpoints,
+    credentials: &Credentials,
+    required_scopes: &[String],
+) -> FieldResult {
+    let response = client.get(format!("{}/oauth2/validate", endpoints.twitch_auth))
+        .header(AUTHORIZATION, format!("OAuth {}", credentials.twitch_user_token.trim()))
+        .send()
+        .await;
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => return FieldResult::warning(format!("Couldn't reach Twitch: {}", e)),
+    };
+
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return if credentials.twitch_refresh_token.trim().is_empty() {
+            FieldResult::error("Token is expired or revoked, log in again")
+        } else {
+            FieldResult::warning("Token is expired, it will be refreshed when the listener starts")
+        };
+    }
+    if !response.status().is_success() {
+        return FieldResult::warning(format!("Twitch answered {}", response.status()));
+    }
+
+    let info = match response.json::<TokenInfo>().await {
+        Ok(info) => info,
+        Err(e) => return FieldResult::warning(format!("Unexpected response from Twitch: {}", e)),
+    };
+
+    let client_id = credentials.twitch_client_id.trim();
+    if !client_id.is_empty() && info.client_id != client_id {
+        return FieldResult::error("Token was issued for a different client ID, log in again");
+    }
+
+    let missing = required_scopes.iter()
+        .filter(|scope| !eventsub::has_scope(&info.scopes, scope))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return FieldResult::error(format!("Token is missing scopes: {}", missing.join(", ")));
+    }
+
+    let login = info.login.unwrap_or_default();
+    let broadcaster_id = credentials.twitch_broadcaster_id.trim();
+    // Chat is read as the broadcaster, so the token has to be theirs
+    if let Some(user_id) = info.user_id.filter(|id| !broadcaster_id.is_empty() && id != broadcaster_id) {
+        return FieldResult::error(format!("Token belongs to {} ({}), not the broadcaster ID", login, user_id));
+    }
+
+    if info.expires_in > 0 && info.expires_in < 60 * 60 {
+        FieldResult::warning(format!("Logged in as {}, token expires in {} minutes", login, info.expires_in / 60))
+    } else {
+        FieldResult::ok(format!("Logged in as {}", login))
+    }
+}
+
+#[derive(Deserialize)]
+struct ChannelsResponse {
+    #[serde(default)]
+    items: Vec<YoutubeChannel>,
+}
+
+#[derive(Deserialize)]
+struct YoutubeChannel {
+    snippet: YoutubeChannelSnippet,
+}
+
+#[derive(Deserialize)]
+struct YoutubeChannelSnippet {
+    title: String,
+}
+
+async fn validate_youtube(client: &Client, endpoints: &Endpoints, credentials: &Credentials) -> (FieldResult, FieldResult) {
+    let channel_id = credentials.youtube_channel_id.trim();
+    let api_key = credentials.youtube_api_key.trim();
+
+    let channel_format = if channel_id.is_empty() {
+        Some(FieldResult::skipped("Not set"))
+    } else if !channel_id.starts_with("UC") || channel_id.len() != 24 {
+        Some(FieldResult::error("Channel IDs start with UC and are 24 characters long, handles and URLs don't work"))
+    } else {
+        None
+    };
+
+    if api_key.is_empty() {
+        let channel = channel_format.unwrap_or_else(|| FieldResult::skipped("Needs the API key to be checked"));
+        return (channel, FieldResult::skipped("Not set"));
+    }
+
+    // Without a usable channel ID the key is checked with the cheapest request there is
+    let request = match &channel_format {
+        None => client.get(format!("{}/youtube/v3/channels", endpoints.youtube_api))
+            .query(&[("part", "snippet"), ("id", channel_id), ("key", api_key)]),
+        Some(_) => client.get(format!("{}/youtube/v3/i18nLanguages", endpoints.youtube_api))
+            .query(&[("part", "snippet"), ("key", api_key)]),
+    };
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            let unreachable = FieldResult::warning(format!("Couldn't reach YouTube: {}", e));
+            return (channel_format.unwrap_or_else(|| unreachable.clone()), unreachable);
+        },
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        let key_result = youtube_key_error(status, &body);
+        let channel = channel_format.unwrap_or_else(|| FieldResult::skipped("Needs a working API key to be checked"));
+        return (channel, key_result);
+    }
+
+    let key_result = FieldResult::ok("Valid");
+    if let Some(channel) = channel_format {
+        return (channel, key_result);
+    }
+
+    let channel = match response.json::<ChannelsResponse>().await {
+        Ok(channels) => match channels.items.first() {
+            Some(channel) => FieldResult::ok(format!("Channel: {}", channel.snippet.title)),
+            None => FieldResult::error("No YouTube channel has this ID"),
+        },
+        Err(e) => FieldResult::warning(format!("Unexpected response from YouTube: {}", e)),
+    };
+    (channel, key_result)
+}
+
+// YouTube explains failures with reasons in the error body
+fn youtube_key_error(status: StatusCode, body: &str) -> FieldResult {
+    if body.contains("API_KEY_INVALID") || body.contains("keyInvalid") || body.contains("API key not valid") {
+        FieldResult::error("YouTube rejected the API key")
+    } else if body.contains("accessNotConfigured") || body.contains("SERVICE_DISABLED") {
+        FieldResult::error("The YouTube Data API v3 isn't enabled for this key's project")
+    } else if body.contains("quotaExceeded") || body.contains("rateLimitExceeded") {
+        FieldResult::warning("The key works but its daily quota is used up")
+    } else if status == StatusCode::FORBIDDEN {
+        FieldResult::error("YouTube refused the API key, check its restrictions")
+    } else {
+        FieldResult::warning(format!("YouTube answered {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server, ServerGuard};
+
+    const CLIENT_ID: &str = "abcdefghijklmnop";
+    const BROADCASTER_ID: &str = "123456";
+    const CHANNEL_ID: &str = "UCabcdefghijklmnopqrstuv";
+
+    fn credentials() -> Credentials {
+        Credentials {
+            twitch_client_id: CLIENT_ID.to_string(),
+            twitch_client_secret: "secret-secret".to_string(),
+            twitch_broadcaster_id: BROADCASTER_ID.to_string(),
+            twitch_user_token: "user-token".to_string(),
+            twitch_refresh_token: String::new(),
+            youtube_channel_id: CHANNEL_ID.to_string(),
+            youtube_api_key: "youtube-key".to_string(),
+        }
+    }
+
+    fn endpoints(server: &ServerGuard) -> Endpoints {
+        Endpoints {
+            twitch_auth: server.url(),
+            twitch_api: server.url(),
+            youtube_api: server.url(),
+        }
+    }
+
+    fn scopes() -> Vec<String> {
+        required_scopes(&[Subscription::ChatMessage, Subscription::ChannelFollow])
+    }
+
+    async fn mock_app_token(server: &mut ServerGuard) {
+        server.mock("POST", "/oauth2/token")
+            .with_status(200)
+            .with_body(r#"{"access_token":"app-token","expires_in":5000,"token_type":"bearer"}"#)
+            .create_async()
+            .await;
+    }
+
+    async fn mock_users(server: &mut ServerGuard, body: &str) {
+        server.mock("GET", "/helix/users")
+            .match_query(Matcher::UrlEncoded("id".into(), BROADCASTER_ID.into()))
+            .with_status(200)
+            .with_body(body)
+            .create_async()
+            .await;
+    }
+
+    async fn mock_validate(server: &mut ServerGuard, status: usize, body: &str) {
+        server.mock("GET", "/oauth2/validate")
+            .match_header("authorization", "OAuth user-token")
+            .with_status(status)
+            .with_body(body)
+            .create_async()
+            .await;
+    }
+
+    async fn mock_channels(server: &mut ServerGuard, status: usize, body: &str) {
+        server.mock("GET", "/youtube/v3/channels")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("id".into(), CHANNEL_ID.into()),
+                Matcher::UrlEncoded("key".into(), "youtube-key".into()),
+            ]))
+            .with_status(status)
+            .with_body(body)
+            .create_async()
+            .await;
+    }
+
+    fn token_info(client_id: &str, user_id: &str, scopes: &[&str], expires_in: i64) -> String {
+        serde_json::json!({
+            "client_id": client_id,
+            "login": "streamer",
+            "user_id": user_id,
+            "scopes": scopes,
+            "expires_in": expires_in,
+        }).to_string()
+    }
+
+    const ALL_SCOPES: &[&str] = &["moderator:read:followers", "user:read:chat", "user:write:chat"];
+
+    #[tokio::test]
+    async fn valid_credentials_pass() {
+        let mut server = Server::new_async().await;
+        mock_app_token(&mut server).await;
+        mock_users(&mut server, r#"{"data":[{"id":"123456","login":"streamer"}]}"#).await;
+        mock_validate(&mut server, 200, &token_info(CLIENT_ID, BROADCASTER_ID, ALL_SCOPES, 14000)).await;
+        mock_channels(&mut server, 200, r#"{"items":[{"snippet":{"title":"Streamer"}}]}"#).await;
+
+        let report = validate(&Client::new(), &endpoints(&server), &credentials(), &scopes()).await;
+
+        assert!(report.valid);
+        assert_eq!(report.twitch_client_id.status, FieldStatus::Ok);
+        assert_eq!(report.twitch_client_secret.status, FieldStatus::Ok);
+        assert_eq!(report.twitch_broadcaster_id.message, "Channel: streamer");
+        assert_eq!(report.twitch_user_token.status, FieldStatus::Ok);
+        assert_eq!(report.youtube_channel_id.message, "Channel: Streamer");
+        assert_eq!(report.youtube_api_key.status, FieldStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn wrong_client_secret_is_reported_on_the_secret() {
+        let mut server = Server::new_async().await;
+        server.mock("POST", "/oauth2/token")
+            .with_status(403)
+            .with_body(r#"{"status":403,"message":"invalid client secret"}"#)
+            .create_async()
+            .await;
+        mock_validate(&mut server, 200, &token_info(CLIENT_ID, BROADCASTER_ID, ALL_SCOPES, 14000)).await;
+        mock_channels(&mut server, 200, r#"{"items":[{"snippet":{"title":"Streamer"}}]}"#).await;
+
+        let report = validate(&Client::new(), &endpoints(&server), &credentials(), &scopes()).await;
+
+        assert!(!report.valid);
+        assert_eq!(report.twitch_client_id.status, FieldStatus::Ok);
+        assert_eq!(report.twitch_client_secret.status, FieldStatus::Error);
+        assert_eq!(report.twitch_broadcaster_id.status, FieldStatus::Skipped);
+    }
+
+    #[tokio::test]
+    async fn unknown_client_id_is_reported_on_the_id() {
+        let mut server = Server::new_async().await;
+        server.mock("POST", "/oauth2/token")
+            .with_status(400)
+            .with_body(r#"{"status":400,"message":"invalid client"}"#)
+            .create_async()
+            .await;
+
+        let mut credentials = credentials();
+        credentials.twitch_user_token.clear();
+        credentials.youtube_api_key.clear();
+        let report = validate(&Client::new(), &endpoints(&server), &credentials, &scopes()).await;
+
+        assert_eq!(report.twitch_client_id.status, FieldStatus::Error);
+        assert_eq!(report.twitch_client_secret.status, FieldStatus::Skipped);
+        assert_eq!(report.twitch_user_token.status, FieldStatus::Skipped);
+        assert_eq!(report.youtube_api_key.status, FieldStatus::Skipped);
+    }
+
+    #[tokio::test]
+    async fn unknown_broadcaster_id_fails() {
+        let mut server = Server::new_async().await;
+        mock_app_token(&mut server).await;
+        mock_users(&mut server, r#"{"data":[]}"#).await;
+
+        let mut credentials = credentials();
+        credentials.twitch_user_token.clear();
+        let report = validate(&Client::new(), &endpoints(&server), &credentials, &scopes()).await;
+
+        assert_eq!(report.twitch_broadcaster_id.status, FieldStatus::Error);
+    }
+
+    #[tokio::test]
+    async fn login_name_as_broadcaster_id_fails_without_a_request() {
+        let mut server = Server::new_async().await;
+        mock_app_token(&mut server).await;
+
+        let mut credentials = credentials();
+        credentials.twitch_broadcaster_id = "streamer".to_string();
+        credentials.twitch_user_token.clear();
+        let report = validate(&Client::new(), &endpoints(&server), &credentials, &scopes()).await;
+
+        assert_eq!(report.twitch_broadcaster_id.status, FieldStatus::Error);
+    }
+
+    #[tokio::test]
+    async fn token_problems_are_reported() {
+        let cases = [
+            (token_info("someone-else", BROADCASTER_ID, ALL_SCOPES, 14000), "different client ID"),
+            (token_info(CLIENT_ID, BROADCASTER_ID, &["user:read:chat"], 14000), "missing scopes: moderator:read:followers, user:write:chat"),
+            (token_info(CLIENT_ID, "999", ALL_SCOPES, 14000), "not the broadcaster ID"),
+        ];
+
+        for (body, expected) in cases {
+            let mut server = Server::new_async().await;
+            mock_app_token(&mut server).await;
+            mock_users(&mut server, r#"{"data":[{"id":"123456","login":"streamer"}]}"#).await;
+            mock_validate(&mut server, 200, &body).await;
+
+            let report = validate(&Client::new(), &endpoints(&server), &credentials(), &scopes()).await;
+
+            assert_eq!(report.twitch_user_token.status, FieldStatus::Error, "{}", expected);
+            assert!(report.twitch_user_token.message.contains(expected), "{}", report.twitch_user_token.message);
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_token_depends_on_refresh_token() {
+        let mut server = Server::new_async().await;
+        mock_validate(&mut server, 401, r#"{"status":401,"message":"invalid access token"}"#).await;
+
+        let mut credentials = credentials();
+        credentials.twitch_client_secret.clear();
+        credentials.youtube_api_key.clear();
+
+        let report = validate(&Client::new(), &endpoints(&server), &credentials, &scopes()).await;
+        assert_eq!(report.twitch_user_token.status, FieldStatus::Error);
+
+        credentials.twitch_refresh_token = "refresh-token".to_string();
+        let report = validate(&Client::new(), &endpoints(&server), &credentials, &scopes()).await;
+        assert_eq!(report.twitch_user_token.status, FieldStatus::Warning);
+    }
+
+    #[tokio::test]
+    async fn invalid_youtube_key_fails() {
+        let mut server = Server::new_async().await;
+        mock_channels(&mut server, 400, r#"{"error":{"code":400,"message":"API key not valid. Please pass a valid API key.","details":[{"reason":"API_KEY_INVALID"}]}}"#).await;
+
+        let mut credentials = credentials();
+        credentials.twitch_client_id.clear();
+        credentials.twitch_user_token.clear();
+        let report = validate(&Client::new(), &endpoints(&server), &credentials, &scopes()).await;
+
+        assert_eq!(report.youtube_api_key.status, FieldStatus::Error);
+        assert_eq!(report.youtube_channel_id.status, FieldStatus::Skipped);
+    }
+
+    #[tokio::test]
+    async fn youtube_quota_is_a_warning() {
+        let mut server = Server::new_async().await;
+        mock_channels(&mut server, 403, r#"{"error":{"code":403,"errors":[{"reason":"quotaExceeded"}]}}"#).await;
+
+        let mut credentials = credentials();
+        credentials.twitch_client_id.clear();
+        credentials.twitch_user_token.clear();
+        let report = validate(&Client::new(), &endpoints(&server), &credentials, &scopes()).await;
+
+        assert!(report.valid);
+        assert_eq!(report.youtube_api_key.status, FieldStatus::Warning);
+    }
+
+    #[tokio::test]
+    async fn unknown_youtube_channel_fails() {
+        let mut server = Server::new_async().await;
+        mock_channels(&mut server, 200, r#"{"kind":"youtube#channelListResponse","items":[]}"#).await;
+
+        let mut credentials = credentials();
+        credentials.twitch_client_id.clear();
+        credentials.twitch_user_token.clear();
+        let report = validate(&Client::new(), &endpoints(&server), &credentials, &scopes()).await;
+
+        assert_eq!(report.youtube_api_key.status, FieldStatus::Ok);
+        assert_eq!(report.youtube_channel_id.status, FieldStatus::Error);
+    }
+
+    #[tokio::test]
+    async fn youtube_handle_checks_key_separately() {
+        let mut server = Server::new_async().await;
+        server.mock("GET", "/youtube/v3/i18nLanguages")
+            .match_query(Matcher::UrlEncoded("key".into(), "youtube-key".into()))
+            .with_status(200)
+            .with_body(r#"{"items":[]}"#)
+            .create_async()
+            .await;
+
+        let mut credentials = credentials();
+        credentials.twitch_client_id.clear();
+        credentials.twitch_user_token.clear();
+        credentials.youtube_channel_id = "@streamer".to_string();
+        let report = validate(&Client::new(), &endpoints(&server), &credentials, &scopes()).await;
+
+        assert_eq!(report.youtube_api_key.status, FieldStatus::Ok);
+        assert_eq!(report.youtube_channel_id.status, FieldStatus::Error);
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_is_a_warning() {
+        // A port that was free a moment ago, so nothing is listening on it
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = format!("http://127.0.0.1:{}", port);
+        let endpoints = Endpoints {
+            twitch_auth: url.clone(),
+            twitch_api: url.clone(),
+            youtube_api: url,
+        };
+
+        let report = validate(&Client::new(), &endpoints, &credentials(), &scopes()).await;
+
+        assert!(report.valid);
+        assert_eq!(report.twitch_client_id.status, FieldStatus::Warning);
+        assert_eq!(report.twitch_user_token.status, FieldStatus::Warning);
+        assert_eq!(report.youtube_api_key.status, FieldStatus::Warning);
+    }
+}
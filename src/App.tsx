@@ -1,18 +1,27 @@
 import { useState, useEffect } from "react";
 import { v4 as uuidv4 } from "uuid";
-import ChatBox, { Message } from "./components/ChatBox";
+import ChatBox, { HypeChat, Message } from "./components/ChatBox";
 import { invoke } from '@tauri-apps/api/tauri';
 import { listen } from '@tauri-apps/api/event';
 import { appWindow } from '@tauri-apps/api/window';
 import { LogicalSize } from '@tauri-apps/api/window';
 import "./App.css";
 import ActivityFeed from "./components/ActivityFeed";
-import { Activity } from "./components/ActivityItem";
+import { Activity, SubscriptionEvent, subscriptionUsername } from "./components/ActivityItem";
 import DockableLayout from "./components/DockableLayout";
 import Toolbar from "./components/Toolbar";
 
 type DockPosition = 'left' | 'right' | 'top' | 'bottom' | 'none';
 
+// Errors from the backend, as returned by commands and sent in `error` events
+interface AppError {
+  kind: 'auth' | 'network' | 'quota' | 'config' | 'platform_api' | 'internal';
+  message: string;
+  platform?: string;
+  status?: number;
+  source?: string;
+}
+
 // Sample messages for demonstration
 // const sampleMessages: Message[] = [
 //   {
@@ -60,6 +69,7 @@ function App() {
   const [dockPosition, setDockPosition] = useState<DockPosition>('right');
   const [dockSize, setDockSize] = useState(30);
   const [originalWindowSize, setOriginalWindowSize] = useState<{width: number, height: number} | null>(null);
+  const [lastError, setLastError] = useState<AppError | null>(null);
 
   // Store original window size and adjust window when activity feed visibility changes
   useEffect(() => {
@@ -150,39 +160,97 @@ function App() {
 
     initBadges();
 
-    // Listen for chat messages
-    const unlistenChat = listen("twitch-chat-message", (event) => {
-      const { user, color, message, badges } = event.payload as any;
+    // Convert chat payloads from the backend into messages
+    const twitchMessage = (payload: any): Message => {
+      const { user, user_id, avatar, color, message, badges, fragments, message_id, bits, hype_chat } = payload;
 
-      const newMessage: Message = {
+      return {
         id: uuidv4(),
         author: user,
+        userId: user_id,
+        avatar: avatar ?? undefined,
         source: "twitch",
         content: message,
         timestamp: new Date(),
         color: color,
-        badges: badges
+        badges: badges,
+        fragments: fragments,
+        messageId: message_id,
+        bits: bits ?? undefined,
+        hypeChat: hype_chat ?? undefined
       };
+    };
 
-      setMessages(prev => [...prev, newMessage]);
-    });
-
-    // Listen for youtube chat messages
-    const unlistenYoutubeChat = listen("youtube-chat-message", (event) => {
-      const { user, color, message, timestamp } = event.payload as any;
+    const youtubeMessage = (payload: any): Message => {
+      const { user, user_id, avatar, color, message, badges } = payload;
 
-      const newMessage: Message = {
+      return {
         id: uuidv4(),
         author: user,
+        userId: user_id,
+        avatar: avatar ?? undefined,
         source: "youtube",
         content: message,
         timestamp: new Date(),
-        color: color
+        color: color,
+        badges: badges
       };
+    };
 
+    // Listen for chat messages
+    const unlistenChat = listen("twitch-chat-message", (event) => {
+      const newMessage = twitchMessage(event.payload);
       setMessages(prev => [...prev, newMessage]);
     });
 
+    // Listen for youtube chat messages
+    const unlistenYoutubeChat = listen("youtube-chat-message", (event) => {
+      const newMessage = youtubeMessage(event.payload);
+      setMessages(prev => [...prev, newMessage]);
+    });
+
+    // Under load the backend coalesces chat messages into batches
+    const unlistenChatBatch = listen("chat-batch", (event) => {
+      const { messages, dropped, deferred } = event.payload as any;
+
+      const newMessages = (messages as any[]).map(({ event, payload }) =>
+        event === "youtube-chat-message" ? youtubeMessage(payload) : twitchMessage(payload)
+      );
+
+      if (dropped > 0 || deferred > 0) {
+        console.debug(`Chat batch: ${dropped} dropped, ${deferred} deferred`);
+      }
+
+      setMessages(prev => [...prev, ...newMessages]);
+    });
+
+    // Show why a listener stopped or a request failed
+    const unlistenError = listen("error", (event) => {
+      setLastError(event.payload as AppError);
+    });
+
+    // Twitch avatars are looked up after the first message, so fill them in when they arrive
+    const unlistenAvatar = listen("avatar-update", (event) => {
+      const { platform, user_id, avatar } = event.payload as any;
+
+      setMessages(prev => prev.map(message =>
+        message.source === platform && message.userId === user_id && !message.avatar
+          ? { ...message, avatar: avatar }
+          : message
+      ));
+    });
+
+    // Hype Chats the IRC side saw after the message was shown
+    const unlistenHypeChat = listen("twitch-hype-chat", (event) => {
+      const hypeChat = event.payload as HypeChat;
+
+      setMessages(prev => prev.map(message =>
+        message.source === "twitch" && message.messageId === hypeChat.message_id
+          ? { ...message, hypeChat: hypeChat }
+          : message
+      ));
+    });
+
     // Listen for follow events
     const unlistenFollow = listen("twitch-follow", (event) => {
       const { user } = event.payload as any;
@@ -219,14 +287,70 @@ function App() {
 
     // Listen for subscription events
     const unlistenSubscription = listen("twitch-subscription", (event) => {
-      const { username, tier, is_gift } = event.payload as any;
+      const subscription = event.payload as SubscriptionEvent;
 
       const newActivity: Activity = {
         id: uuidv4(),
         type: "subscription",
+        username: subscriptionUsername(subscription),
+        source: "twitch",
+        message: subscription.kind === "resubscribe" && subscription.message ? subscription.message : undefined,
+        subscription: subscription,
+        timestamp: new Date()
+      };
+
+      // Add to activities
+      setActivities(prev => [...prev, newActivity]);
+    });
+
+    // Listen for cheer events
+    const unlistenCheer = listen("twitch-cheer", (event) => {
+      const { username, bits, message, fragments } = event.payload as any;
+
+      const newActivity: Activity = {
+        id: uuidv4(),
+        type: "cheer",
+        username: username ?? "An anonymous cheerer",
+        source: "twitch",
+        amount: bits,
+        message: message || undefined,
+        fragments: fragments,
+        timestamp: new Date()
+      };
+
+      // Add to activities
+      setActivities(prev => [...prev, newActivity]);
+    });
+
+    // Listen for raid events
+    const unlistenRaid = listen("twitch-raid", (event) => {
+      const { from, viewers } = event.payload as any;
+
+      const newActivity: Activity = {
+        id: uuidv4(),
+        type: "raid",
+        username: from,
+        source: "twitch",
+        amount: viewers,
+        timestamp: new Date()
+      };
+
+      // Add to activities
+      setActivities(prev => [...prev, newActivity]);
+    });
+
+    // Listen for channel point redemptions
+    const unlistenRedemption = listen("twitch-redemption", (event) => {
+      const { username, reward, cost, user_input } = event.payload as any;
+
+      const newActivity: Activity = {
+        id: uuidv4(),
+        type: "redemption",
         username: username,
         source: "twitch",
-        message: `Tier ${tier} ${is_gift ? '(gifted)' : ''}`,
+        amount: cost,
+        reward: reward,
+        message: user_input ?? undefined,
         timestamp: new Date()
       };
 
@@ -237,9 +361,16 @@ function App() {
     return () => {
       unlistenChat.then(unlisten => unlisten());
       unlistenYoutubeChat.then(unlisten => unlisten());
+      unlistenChatBatch.then(unlisten => unlisten());
+      unlistenAvatar.then(unlisten => unlisten());
+      unlistenHypeChat.then(unlisten => unlisten());
+      unlistenError.then(unlisten => unlisten());
       unlistenFollow.then(unlisten => unlisten());
       unlistenDonation.then(unlisten => unlisten());
       unlistenSubscription.then(unlisten => unlisten());
+      unlistenCheer.then(unlisten => unlisten());
+      unlistenRaid.then(unlisten => unlisten());
+      unlistenRedemption.then(unlisten => unlisten());
     };
   }, []);
 
@@ -258,6 +389,15 @@ function App() {
           setAutoScroll={setAutoScroll}
         />
       </div>
+      {lastError && (
+        <div className={`error-banner ${lastError.kind}`}>
+          <span>
+            {lastError.platform && `${lastError.platform}: `}
+            {lastError.message}
+          </span>
+          <button onClick={() => setLastError(null)} aria-label="Dismiss">×</button>
+        </div>
+      )}
       <ChatBox 
         messages={messages} 
         autoScroll={autoScroll} 
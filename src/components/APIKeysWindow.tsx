@@ -1,6 +1,9 @@
 import React, { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
 import './APIKeysWindow.css';
+import TwitchAccount from './TwitchAccount';
+import TwitchSubscriptions from './TwitchSubscriptions';
+import RaidWelcome from './RaidWelcome';
 
 interface APIKey {
   name: string;
@@ -9,6 +12,29 @@ interface APIKey {
   visible: boolean;
 }
 
+interface CredentialsStatus {
+  locked: boolean;
+  key_source: 'keyring' | 'key_file' | 'passphrase' | null;
+  plaintext_files: string[];
+}
+
+interface FieldResult {
+  status: 'ok' | 'warning' | 'error' | 'skipped';
+  message: string;
+}
+
+// Results of validate_credentials, keyed by the lowercased field name
+type ValidationReport = { valid: boolean } & Record<string, FieldResult>;
+
+const KEY_SOURCE_LABELS: Record<string, string> = {
+  keyring: 'encrypted with a key in the system keyring',
+  key_file: 'encrypted with a local key file',
+  passphrase: 'encrypted with your passphrase',
+};
+
+const errorMessage = (error: unknown) =>
+  typeof error === 'object' && error !== null && 'message' in error ? String((error as { message: string }).message) : String(error);
+
 const APIKeysWindow: React.FC = () => {
   const [apiKeys, setApiKeys] = useState<APIKey[]>([
     { name: 'TWITCH_CLIENT_ID', key: '', placeholder: 'Enter Twitch Client ID', visible: false },
@@ -21,6 +47,10 @@ const APIKeysWindow: React.FC = () => {
   const [isSaving, setIsSaving] = useState(false);
   const [isLoading, setIsLoading] = useState(true);
   const [saveMessage, setSaveMessage] = useState('');
+  const [status, setStatus] = useState<CredentialsStatus | null>(null);
+  const [passphrase, setPassphrase] = useState('');
+  const [validation, setValidation] = useState<ValidationReport | null>(null);
+  const [isValidating, setIsValidating] = useState(false);
 
   useEffect(() => {
     loadApiKeys();
@@ -29,6 +59,13 @@ const APIKeysWindow: React.FC = () => {
   const loadApiKeys = async () => {
     setIsLoading(true);
     try {
+      const credentialsStatus = await invoke<CredentialsStatus>('credentials_status');
+      setStatus(credentialsStatus);
+      if (credentialsStatus.locked) {
+        return;
+      }
+
+
       const result = await invoke<Record<string, string>>('read_api_keys');
       
       const updatedKeys = [...apiKeys];
@@ -56,6 +93,7 @@ const APIKeysWindow: React.FC = () => {
     const newApiKeys = [...apiKeys];
     newApiKeys[index].key = value;
     setApiKeys(newApiKeys);
+    setValidation(null);
   };
 
   const saveApiKeys = async () => {
@@ -74,12 +112,72 @@ const APIKeysWindow: React.FC = () => {
       setSaveMessage('API keys saved successfully!');
       setTimeout(() => setSaveMessage(''), 3000);
     } catch (error) {
-      setSaveMessage(`Error saving API keys: ${error}`);
+      setSaveMessage(`Error saving API keys: ${errorMessage(error)}`);
     } finally {
       setIsSaving(false);
     }
   };
 
+  // Check what's typed against Twitch and YouTube without saving
+  const validateApiKeys = async () => {
+    setIsValidating(true);
+    try {
+      const report = await invoke<ValidationReport>('validate_credentials', {
+        twitchClientId: apiKeys[0].key,
+        twitchClientSecret: apiKeys[1].key,
+        twitchBroadcasterId: apiKeys[2].key,
+        youtubeChannelId: apiKeys[3].key,
+        youtubeApiKey: apiKeys[4].key,
+      });
+      setValidation(report);
+    } catch (error) {
+      setSaveMessage(`Error validating API keys: ${errorMessage(error)}`);
+    } finally {
+      setIsValidating(false);
+    }
+  };
+
+  const fieldResult = (name: string) => {
+    const result = validation?.[name.toLowerCase()];
+    if (!result || typeof result !== 'object') {
+      return null;
+    }
+    return <div className={`field-result ${result.status}`}>{result.message}</div>;
+  };
+
+  const unlock = async () => {
+    try {
+      await invoke('unlock_credentials', { passphrase });
+      setPassphrase('');
+      setSaveMessage('');
+      await loadApiKeys();
+    } catch (error) {
+      setSaveMessage(`Error unlocking: ${errorMessage(error)}`);
+    }
+  };
+
+  // An empty passphrase goes back to the system keyring
+  const changePassphrase = async () => {
+    try {
+      await invoke('set_credentials_passphrase', { passphrase: passphrase || null });
+      setPassphrase('');
+      setSaveMessage(passphrase ? 'Passphrase set!' : 'Passphrase removed!');
+      setStatus(await invoke<CredentialsStatus>('credentials_status'));
+    } catch (error) {
+      setSaveMessage(`Error changing passphrase: ${errorMessage(error)}`);
+    }
+  };
+
+  const removePlaintextFiles = async () => {
+    try {
+      const removed = await invoke<string[]>('remove_plaintext_credentials');
+      setSaveMessage(`Removed ${removed.length} plaintext file(s)`);
+      setStatus(await invoke<CredentialsStatus>('credentials_status'));
+    } catch (error) {
+      setSaveMessage(`Error removing files: ${errorMessage(error)}`);
+    }
+  };
+
   if (isLoading) {
     return (
       <div className="api-keys-window loading">
@@ -88,9 +186,56 @@ const APIKeysWindow: React.FC = () => {
     );
   }
 
+  // The store isn't protected by a passphrase but couldn't be decrypted, e.g. because
+  // the system keyring wasn't available yet
+  if (status?.locked && status.key_source !== 'passphrase') {
+    return (
+      <div className="api-keys-window">
+        <h2>API Keys Configuration</h2>
+        <p>Your API keys couldn't be decrypted. The file was left as it is.</p>
+        <div className="api-keys-actions">
+          <button className="save-button" onClick={unlock}>Try again</button>
+          {saveMessage && <div className="save-message error">{saveMessage}</div>}
+        </div>
+      </div>
+    );
+  }
+
+  if (status?.locked) {
+    return (
+      <div className="api-keys-window">
+        <h2>API Keys Configuration</h2>
+        <p>Your API keys are protected by a passphrase.</p>
+        <div className="api-key-input">
+          <label>PASSPHRASE</label>
+          <input
+            type="password"
+            value={passphrase}
+            onChange={(e) => setPassphrase(e.target.value)}
+            onKeyDown={(e) => e.key === 'Enter' && unlock()}
+          />
+        </div>
+        <div className="api-keys-actions">
+          <button className="save-button" onClick={unlock}>Unlock</button>
+          {saveMessage && <div className="save-message error">{saveMessage}</div>}
+        </div>
+      </div>
+    );
+  }
+
   return (
     <div className="api-keys-window">
       <h2>API Keys Configuration</h2>
+
+      {status && status.plaintext_files.length > 0 && (
+        <div className="plaintext-warning">
+          <p>These old files still contain your keys as plain text:</p>
+          <ul>
+            {status.plaintext_files.map((file) => <li key={file}>{file}</li>)}
+          </ul>
+          <button onClick={removePlaintextFiles}>Delete them</button>
+        </div>
+      )}
       
       <div className="api-keys-container">
         {apiKeys.map((apiKey, index) => (
@@ -111,11 +256,26 @@ const APIKeysWindow: React.FC = () => {
                 {apiKey.visible ? '👁️' : '👁️‍🗨️'}
               </button>
             </div>
+            {fieldResult(apiKey.name)}
           </div>
         ))}
+        {validation && (
+          <div className="api-key-input">
+            <label>TWITCH_USER_TOKEN</label>
+            {fieldResult('TWITCH_USER_TOKEN')}
+          </div>
+        )}
       </div>
       
       <div className="api-keys-actions">
+        <button
+          className="validate-button"
+          onClick={validateApiKeys}
+          disabled={isValidating}
+        >
+          {isValidating ? 'Checking...' : 'Validate'}
+        </button>
+
         <button 
           className="save-button" 
           onClick={saveApiKeys} 
@@ -133,8 +293,28 @@ const APIKeysWindow: React.FC = () => {
       
       <div className="api-keys-help">
         <p>These API keys are used to connect to Twitch and YouTube APIs.</p>
-        <p>All keys are stored locally and securely on your device.</p>
+        <p>
+          All keys are stored locally on your device
+          {status?.key_source ? `, ${KEY_SOURCE_LABELS[status.key_source]}` : ''}.
+        </p>
       </div>
+
+      <div className="api-key-input">
+        <label>PASSPHRASE (optional)</label>
+        <input
+          type="password"
+          value={passphrase}
+          placeholder="Leave empty to use the system keyring"
+          onChange={(e) => setPassphrase(e.target.value)}
+        />
+        <button onClick={changePassphrase}>
+          {passphrase ? 'Set passphrase' : 'Remove passphrase'}
+        </button>
+      </div>
+
+      <TwitchAccount />
+      <TwitchSubscriptions />
+      <RaidWelcome />
     </div>
   );
 };
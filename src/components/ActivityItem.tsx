@@ -1,14 +1,67 @@
 import React from 'react';
 import twitchIcon from '../assets/twitch32.png';
+import { MessageFragment } from './ChatBox';
+import MessageContent from './MessageContent';
 import './ActivityItem.css';
 
+// Payload of the `twitch-subscription` event
+export type SubscriptionEvent =
+  | { kind: 'subscribe'; username: string; tier: number }
+  | { kind: 'gifted'; recipient: string; tier: number }
+  | {
+      kind: 'resubscribe';
+      username: string;
+      tier: number;
+      cumulative_months: number;
+      streak_months: number | null;
+      duration_months: number;
+      message: string;
+    }
+  | { kind: 'gift'; gifter: string | null; tier: number; count: number; cumulative_total: number | null };
+
+// Who the feed entry is about; anonymous gifts have no gifter
+export const subscriptionUsername = (subscription: SubscriptionEvent) => {
+  switch (subscription.kind) {
+    case 'gifted':
+      return subscription.recipient;
+    case 'gift':
+      return subscription.gifter ?? 'An anonymous gifter';
+    default:
+      return subscription.username;
+  }
+};
+
+const plural = (count: number, word: string) => `${count} ${word}${count === 1 ? '' : 's'}`;
+
+const subscriptionMessage = (username: string, subscription: SubscriptionEvent) => {
+  switch (subscription.kind) {
+    case 'subscribe':
+      return `${username} subscribed at Tier ${subscription.tier}!`;
+    case 'gifted':
+      return `${username} received a gifted Tier ${subscription.tier} sub!`;
+    case 'resubscribe': {
+      const streak = subscription.streak_months ? `, ${subscription.streak_months} in a row` : '';
+      return `${username} resubscribed at Tier ${subscription.tier} for ${plural(subscription.cumulative_months, 'month')}${streak}!`;
+    }
+    case 'gift': {
+      const total = subscription.cumulative_total ? ` (${subscription.cumulative_total} in the channel)` : '';
+      return `${username} gifted ${plural(subscription.count, 'Tier ' + subscription.tier + ' sub')}${total}!`;
+    }
+  }
+};
+
 export interface Activity {
   id: string;
-  type: 'follow' | 'donation' | 'subscription';
+  type: 'follow' | 'donation' | 'subscription' | 'cheer' | 'raid' | 'redemption';
   username: string;
   source: 'twitch' | 'youtube';
   amount?: number;
   message?: string;
+  subscription?: SubscriptionEvent;
+  // Title of a redeemed channel point reward
+  reward?: string;
+  // Cheer messages with their cheermotes
+  fragments?: MessageFragment[];
   timestamp: Date;
 }
 
@@ -28,7 +81,15 @@ const ActivityItem: React.FC<ActivityItemProps> = ({ activity }) => {
       case 'donation':
         return `${activity.username} donated $${activity.amount?.toFixed(2)}!`;
       case 'subscription':
-        return `${activity.username} subscribed!`;
+        return activity.subscription
+          ? subscriptionMessage(activity.username, activity.subscription)
+          : `${activity.username} subscribed!`;
+      case 'cheer':
+        return `${activity.username} cheered ${plural(activity.amount ?? 0, 'bit')}!`;
+      case 'raid':
+        return `${activity.username} is raiding with ${plural(activity.amount ?? 0, 'viewer')}!`;
+      case 'redemption':
+        return `${activity.username} redeemed ${activity.reward} (${plural(activity.amount ?? 0, 'point')})`;
       default:
         return '';
     }
@@ -49,7 +110,9 @@ const ActivityItem: React.FC<ActivityItemProps> = ({ activity }) => {
       </div>
       
       {activity.message && (
-        <div className="activity-user-message">{activity.message}</div>
+        <div className="activity-user-message">
+          <MessageContent content={activity.message} fragments={activity.fragments} />
+        </div>
       )}
     </div>
   );
@@ -5,22 +5,59 @@ import MessageContent from './MessageContent';
 import './ChatBox.css';
 import SendBox from './SendMessageBox';
 
-// Interface for Twitch badges
+// Interface for chat badges
 export interface Badge {
   id: string;
   version: string;
   image_url?: string;
+  srcset?: string;
   title?: string;
 }
 
+// Part of a Twitch message: plain text, an emote image or a cheermote
+export interface MessageFragment {
+  type: 'text' | 'emote' | 'cheermote';
+  text: string;
+  id?: string;
+  url?: string;
+  srcset?: string;
+  // Cheermotes only
+  bits?: number;
+  color?: string;
+}
+
+// A paid, pinned Twitch message
+export interface HypeChat {
+  message_id: string;
+  amount: number;
+  currency: string;
+  level: string;
+}
+
+const formatAmount = ({ amount, currency }: HypeChat) => {
+  try {
+    return new Intl.NumberFormat(undefined, { style: 'currency', currency }).format(amount);
+  } catch {
+    return `${amount.toFixed(2)} ${currency}`;
+  }
+};
+
 export interface Message {
   id: string;
   author: string;
+  userId?: string;
+  avatar?: string;
   source: 'youtube' | 'twitch';
   content: string;
   timestamp: Date;
   color: string;
   badges?: Badge[]; // Optional array of Badge objects
+  fragments?: MessageFragment[];
+  // Twitch message ID, for attaching Hype Chat info that arrives later
+  messageId?: string;
+  // Bits cheered with the message
+  bits?: number;
+  hypeChat?: HypeChat;
 }
 
 // This is where all the settings for the chatbox go
@@ -100,20 +137,28 @@ const ChatBox: React.FC<ChatBoxProps> = ({
         ref={containerRef}
       >
         {messages.map((message) => (
-          <div key={message.id} className={`message-item ${message.source}`}>
+          <div key={message.id} className={`message-item ${message.source}${message.bits ? ' cheer' : ''}${message.hypeChat ? ' hype-chat' : ''}`}>
+            {message.hypeChat && (
+              <div className="hype-chat-label">
+                Hype Chat · {formatAmount(message.hypeChat)} · Level {message.hypeChat.level}
+              </div>
+            )}
             <div className="message-header">
               <img
                 src={getSourceIcon(message.source)}
                 alt={`${message.source}`}
                 className="source-icon"
               />  
+              {message.avatar && (
+                <img src={message.avatar} alt="" className="author-avatar" />
+              )}
               <span style={{ color: message.color }} className="author-name">{message.author}</span>           
               <span className="message-time">
                 {message.timestamp.toLocaleTimeString()}
               </span>
             </div>
             <div className="message-content">
-              <MessageContent content={message.content} badges={message.badges} />
+              <MessageContent content={message.content} badges={message.badges} fragments={message.fragments} />
             </div>
           </div>
         ))}
@@ -0,0 +1,72 @@
+import React, { useState } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import './ConfigBackup.css';
+
+interface ImportResult {
+  profile: string;
+  credentials: boolean;
+}
+
+const errorMessage = (error: unknown) =>
+  typeof error === 'object' && error !== null && 'message' in error ? String((error as { message: string }).message) : String(error);
+
+// Export the current profile's settings to a file, or load an exported file
+const ConfigBackup: React.FC = () => {
+  const [includeSecrets, setIncludeSecrets] = useState(false);
+  const [importPath, setImportPath] = useState('');
+  const [message, setMessage] = useState('');
+  const [isError, setIsError] = useState(false);
+
+  const report = (text: string, error = false) => {
+    setMessage(text);
+    setIsError(error);
+  };
+
+  const exportConfig = async () => {
+    try {
+      const path = await invoke<string>('export_config', { includeSecrets });
+      report(`Exported to ${path}`);
+    } catch (error) {
+      report(errorMessage(error), true);
+    }
+  };
+
+  const importConfig = async () => {
+    try {
+      const result = await invoke<ImportResult>('import_config', { path: importPath.trim() });
+      report(`Imported settings${result.credentials ? ' and keys' : ''} from profile ${result.profile}`);
+      setImportPath('');
+    } catch (error) {
+      report(errorMessage(error), true);
+    }
+  };
+
+  return (
+    <div className="config-backup">
+      <label className="settings-label">Backup:</label>
+      <label className="settings-label">
+        <input
+          type="checkbox"
+          checked={includeSecrets}
+          onChange={() => setIncludeSecrets(!includeSecrets)}
+        />
+        Include API keys and tokens (saved unencrypted)
+      </label>
+      <button onClick={exportConfig}>Export settings</button>
+
+      <div className="config-backup-row">
+        <input
+          type="text"
+          value={importPath}
+          onChange={(e) => setImportPath(e.target.value)}
+          placeholder="Path to an exported file"
+        />
+        <button onClick={importConfig} disabled={!importPath.trim()}>Import</button>
+      </div>
+
+      {message && <div className={`config-backup-message ${isError ? 'error' : ''}`}>{message}</div>}
+    </div>
+  );
+};
+
+export default ConfigBackup;
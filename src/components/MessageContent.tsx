@@ -1,27 +1,40 @@
 import React from 'react';
 import './MessageContent.css';
-import { Badge } from './ChatBox';
+import { Badge, MessageFragment } from './ChatBox';
 
 interface MessageContentProps {
   content: string;
   badges?: Badge[];
+  fragments?: MessageFragment[];
 }
 
 // Simple regex to match common emoji patterns
 const emojiRegex = /(\p{Emoji_Presentation}|\p{Extended_Pictographic})/gu;
 
-const MessageContent: React.FC<MessageContentProps> = ({ content, badges }) => {
-  // Split content into segments of text and emojis
-  const parts = content.split(emojiRegex);
+const MessageContent: React.FC<MessageContentProps> = ({ content, badges, fragments }) => {
+  // Split text into segments of text and emojis
+  const renderText = (text: string, key: string | number) =>
+    text.split(emojiRegex).map((part, index) => {
+      // Check if this part is an emoji
+      if (part.match(emojiRegex)) {
+        return (
+          <span key={`${key}-${index}`} className="emoji">
+            {part}
+          </span>
+        );
+      }
+      return <span key={`${key}-${index}`}>{part}</span>;
+    });
   
   return (
     <div className="message-text">
       {badges && badges.length > 0 && (
         <div className="message-badges">
-          {badges.map((badge, i) => (
+          {badges.filter(badge => badge.image_url).map((badge, i) => (
             <span key={i} className="badge" title={badge.title || badge.id}>
               <img 
-                src={badge.image_url || `https://static-cdn.jtvnw.net/badges/v1/${badge.id}/${badge.version}/1`} 
+                src={badge.image_url} 
+                srcSet={badge.srcset}
                 alt={badge.title || badge.id} 
                 className="badge-icon" 
               />
@@ -29,17 +42,27 @@ const MessageContent: React.FC<MessageContentProps> = ({ content, badges }) => {
           ))}
         </div>
       )}
-      {parts.map((part, index) => {
-        // Check if this part is an emoji
-        if (part.match(emojiRegex)) {
-          return (
-            <span key={index} className="emoji">
-              {part}
-            </span>
-          );
-        }
-        return <span key={index}>{part}</span>;
-      })}
+      {fragments && fragments.length > 0
+        ? fragments.map((fragment, i) =>
+            fragment.type === 'emote' && fragment.url ? (
+              <img
+                key={i}
+                src={fragment.url}
+                srcSet={fragment.srcset}
+                alt={fragment.text}
+                title={fragment.text}
+                className="emote"
+              />
+            ) : fragment.type === 'cheermote' && fragment.url ? (
+              <span key={i} className="cheermote" title={fragment.text}>
+                <img src={fragment.url} srcSet={fragment.srcset} alt={fragment.text} className="emote" />
+                <span className="cheermote-bits" style={{ color: fragment.color }}>{fragment.bits}</span>
+              </span>
+            ) : (
+              renderText(fragment.text, i)
+            )
+          )
+        : renderText(content, 'text')}
     </div>
   );
 };
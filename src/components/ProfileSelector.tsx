@@ -0,0 +1,98 @@
+import React, { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
+import './ProfileSelector.css';
+
+interface ProfileList {
+  active: string;
+  profiles: string[];
+}
+
+const errorMessage = (error: unknown) =>
+  typeof error === 'object' && error !== null && 'message' in error ? String((error as { message: string }).message) : String(error);
+
+// Pick the profile (channel and account) the app runs as, or add and remove profiles
+const ProfileSelector: React.FC = () => {
+  const [list, setList] = useState<ProfileList>({ active: 'default', profiles: ['default'] });
+  const [newName, setNewName] = useState('');
+  const [toDelete, setToDelete] = useState('');
+  const [message, setMessage] = useState('');
+  const [busy, setBusy] = useState(false);
+
+  useEffect(() => {
+    invoke<ProfileList>('list_profiles').then(setList);
+
+    const unlistenProfile = listen<ProfileList>('profile-changed', (event) => setList(event.payload));
+    return () => {
+      unlistenProfile.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  const run = async (action: () => Promise<ProfileList>) => {
+    setBusy(true);
+    try {
+      setList(await action());
+      setMessage('');
+    } catch (error) {
+      setMessage(errorMessage(error));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const createProfile = () =>
+    run(async () => {
+      const result = await invoke<ProfileList>('create_profile', { name: newName.trim(), copySettings: true });
+      setNewName('');
+      return result;
+    });
+
+  const deletable = list.profiles.filter((profile) => profile !== list.active && profile !== 'default');
+
+  const deleteProfile = () =>
+    run(async () => {
+      const result = await invoke<ProfileList>('delete_profile', { name: toDelete });
+      setToDelete('');
+      return result;
+    });
+
+  return (
+    <div className="profile-selector">
+      <label className="settings-label">Profile:</label>
+      <div className="profile-row">
+        <select
+          value={list.active}
+          onChange={(e) => run(() => invoke<ProfileList>('switch_profile', { name: e.target.value }))}
+          className="settings-select"
+          disabled={busy}
+        >
+          {list.profiles.map((profile) => <option key={profile} value={profile}>{profile}</option>)}
+        </select>
+      </div>
+
+      <div className="profile-row">
+        <input
+          type="text"
+          value={newName}
+          onChange={(e) => setNewName(e.target.value)}
+          placeholder="New profile name"
+        />
+        <button onClick={createProfile} disabled={busy || !newName.trim()}>Add</button>
+      </div>
+
+      {deletable.length > 0 && (
+        <div className="profile-row">
+          <select value={toDelete} onChange={(e) => setToDelete(e.target.value)} className="settings-select">
+            <option value="" disabled>Delete a profile…</option>
+            {deletable.map((profile) => <option key={profile} value={profile}>{profile}</option>)}
+          </select>
+          <button onClick={deleteProfile} disabled={busy || !toDelete}>Delete</button>
+        </div>
+      )}
+
+      {message && <div className="profile-message">{message}</div>}
+    </div>
+  );
+};
+
+export default ProfileSelector;
@@ -0,0 +1,129 @@
+import React, { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
+import './RaidWelcome.css';
+
+type RaidAction = 'none' | 'shoutout' | 'thank_you' | 'slow_mode';
+
+interface RaidWelcomeSettings {
+  action: RaidAction;
+  min_viewers: number;
+  message: string;
+  slow_mode_wait_secs: number;
+  slow_mode_duration_secs: number;
+}
+
+interface Settings {
+  twitch: {
+    raid_welcome: RaidWelcomeSettings;
+  };
+}
+
+const ACTIONS: { value: RaidAction; label: string }[] = [
+  { value: 'none', label: 'Only show it in the activity feed' },
+  { value: 'shoutout', label: 'Shout out the raider' },
+  { value: 'thank_you', label: 'Send a thank-you message' },
+  { value: 'slow_mode', label: 'Turn on slow mode for a while' },
+];
+
+const errorMessage = (error: unknown) =>
+  typeof error === 'object' && error !== null && 'message' in error ? String((error as { message: string }).message) : String(error);
+
+// Choose what the app does when another channel raids
+const RaidWelcome: React.FC = () => {
+  const [welcome, setWelcome] = useState<RaidWelcomeSettings | null>(null);
+  const [message, setMessage] = useState('');
+
+  useEffect(() => {
+    invoke<Settings>('get_settings').then((settings) => setWelcome(settings.twitch.raid_welcome));
+
+    const unlistenSettings = listen<Settings>('settings-changed', (event) => setWelcome(event.payload.twitch.raid_welcome));
+    return () => {
+      unlistenSettings.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  const save = async (changes: Partial<RaidWelcomeSettings>) => {
+    try {
+      const settings = await invoke<Settings>('update_settings', { changes: { twitch: { raid_welcome: changes } } });
+      setWelcome(settings.twitch.raid_welcome);
+      setMessage('');
+    } catch (error) {
+      setMessage(errorMessage(error));
+    }
+  };
+
+  if (!welcome) {
+    return null;
+  }
+
+  return (
+    <div className="raid-welcome">
+      <h3>Raids</h3>
+      <p className="raid-welcome-hint">Shoutouts and slow mode need moderator access; log in again after choosing one.</p>
+
+      <select
+        value={welcome.action}
+        onChange={(e) => save({ action: e.target.value as RaidAction })}
+        className="settings-select"
+      >
+        {ACTIONS.map((action) => (
+          <option key={action.value} value={action.value}>{action.label}</option>
+        ))}
+      </select>
+
+      {welcome.action !== 'none' && (
+        <label className="raid-welcome-field">
+          Only for raids with at least
+          <input
+            type="number"
+            min={1}
+            value={welcome.min_viewers}
+            onChange={(e) => save({ min_viewers: parseInt(e.target.value) || 1 })}
+          />
+          viewers
+        </label>
+      )}
+
+      {welcome.action === 'thank_you' && (
+        <label className="raid-welcome-field">
+          Message ({'{raider}'} and {'{viewers}'} are filled in)
+          <textarea
+            defaultValue={welcome.message}
+            onBlur={(e) => save({ message: e.target.value })}
+            rows={2}
+          />
+        </label>
+      )}
+
+      {welcome.action === 'slow_mode' && (
+        <>
+          <label className="raid-welcome-field">
+            Seconds between messages
+            <input
+              type="number"
+              min={3}
+              max={120}
+              value={welcome.slow_mode_wait_secs}
+              onChange={(e) => save({ slow_mode_wait_secs: parseInt(e.target.value) || 3 })}
+            />
+          </label>
+          <label className="raid-welcome-field">
+            Keep slow mode on for (seconds)
+            <input
+              type="number"
+              min={1}
+              max={3600}
+              value={welcome.slow_mode_duration_secs}
+              onChange={(e) => save({ slow_mode_duration_secs: parseInt(e.target.value) || 1 })}
+            />
+          </label>
+        </>
+      )}
+
+      {message && <div className="save-message error">{message}</div>}
+    </div>
+  );
+};
+
+export default RaidWelcome;
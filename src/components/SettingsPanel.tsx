@@ -1,6 +1,8 @@
 import React, { useState } from 'react';
 import './SettingsPanel.css';
 import APIKeysWindow from './APIKeysWindow';
+import ProfileSelector from './ProfileSelector';
+import ConfigBackup from './ConfigBackup';
 
 type DockPosition = 'left' | 'right' | 'top' | 'bottom' | 'none';
 
@@ -37,6 +39,10 @@ const SettingsPanel: React.FC<SettingsPanelProps> = ({
 
   return (
     <div className="settings-panel">
+      <div className="settings-section">
+        <ProfileSelector />
+      </div>
+
       <div className="settings-section">
         <label className="settings-label">
           <input
@@ -94,6 +100,10 @@ const SettingsPanel: React.FC<SettingsPanelProps> = ({
         </>
       )}
 
+      <div className="settings-section">
+        <ConfigBackup />
+      </div>
+
       <div className="settings-section api-keys-section">
         <button 
           className="api-keys-button"
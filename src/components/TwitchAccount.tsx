@@ -0,0 +1,175 @@
+import React, { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
+import './TwitchAccount.css';
+
+interface TokenStatus {
+  login: string | null;
+  user_id: string | null;
+  scopes: string[];
+  expires_at: string | null;
+  can_refresh: boolean;
+}
+
+interface DeviceLogin {
+  user_code: string;
+  verification_uri: string;
+  expires_at: string;
+}
+
+type LoginEvent =
+  | { status: 'success'; token: TokenStatus }
+  | { status: 'failed'; error: unknown };
+
+type Permission = 'moderation' | 'channel_points';
+
+const errorMessage = (error: unknown) =>
+  typeof error === 'object' && error !== null && 'message' in error ? String((error as { message: string }).message) : String(error);
+
+// The logged in Twitch account: who it is, what the token may do and when it expires
+const TwitchAccount: React.FC = () => {
+  const [status, setStatus] = useState<TokenStatus | null>(null);
+  const [message, setMessage] = useState('');
+  const [busy, setBusy] = useState(false);
+  const [deviceLogin, setDeviceLogin] = useState<DeviceLogin | null>(null);
+  const [redirectPort, setRedirectPort] = useState('');
+
+  useEffect(() => {
+    loadStatus();
+    invoke<number>('get_twitch_redirect_port').then((port) => setRedirectPort(String(port)));
+
+    // Device code logins finish in the background
+    const unlistenLogin = listen<LoginEvent>('twitch-login', (event) => {
+      setDeviceLogin(null);
+      if (event.payload.status === 'success') {
+        setStatus(event.payload.token);
+        setMessage('');
+      } else {
+        setMessage(errorMessage(event.payload.error));
+      }
+    });
+
+    return () => {
+      unlistenLogin.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  const loadStatus = async () => {
+    try {
+      setStatus(await invoke<TokenStatus>('twitch_token_status'));
+      setMessage('');
+    } catch (error) {
+      setStatus(null);
+      setMessage(errorMessage(error));
+    }
+  };
+
+  const run = async (action: () => Promise<TokenStatus | void>) => {
+    setBusy(true);
+    try {
+      const result = await action();
+      if (result) {
+        setStatus(result);
+        setMessage('');
+      } else {
+        await loadStatus();
+      }
+    } catch (error) {
+      setMessage(errorMessage(error));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const authorize = (permissions: Permission[]) =>
+    run(() => invoke<TokenStatus>('authorize_twitch', { permissions }));
+
+  const startDeviceLogin = async () => {
+    setBusy(true);
+    try {
+      setDeviceLogin(await invoke<DeviceLogin>('start_twitch_device_login', { permissions: [] }));
+      setMessage('');
+    } catch (error) {
+      setMessage(errorMessage(error));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const cancelDeviceLogin = async () => {
+    await invoke('cancel_twitch_device_login');
+    setDeviceLogin(null);
+  };
+
+  const saveRedirectPort = async () => {
+    try {
+      await invoke('set_twitch_redirect_port', { port: Number(redirectPort) });
+      setMessage('');
+    } catch (error) {
+      setMessage(errorMessage(error));
+    }
+  };
+
+  return (
+    <div className="twitch-account">
+      <h3>Twitch Account</h3>
+
+      {status ? (
+        <>
+          <p>
+            Logged in as <strong>{status.login}</strong>
+            {status.expires_at && `, token expires ${new Date(status.expires_at).toLocaleString()}`}
+          </p>
+          <div className="token-scopes">
+            {status.scopes.map((scope) => <span key={scope} className="token-scope">{scope}</span>)}
+          </div>
+        </>
+      ) : (
+        <p>Not logged in{message && `: ${message}`}</p>
+      )}
+
+      <div className="twitch-account-actions">
+        <button onClick={() => authorize([])} disabled={busy}>{status ? 'Log in again' : 'Log in'}</button>
+        <button onClick={() => run(() => invoke<TokenStatus>('refresh_twitch_token'))} disabled={busy || !status?.can_refresh}>Refresh token</button>
+        <button onClick={() => run(() => invoke<void>('logout_twitch'))} disabled={busy || !status}>Log out</button>
+      </div>
+      <div className="twitch-account-actions">
+        <button onClick={startDeviceLogin} disabled={busy || !!deviceLogin}>Log in with a code</button>
+      </div>
+
+      {deviceLogin && (
+        <div className="device-login">
+          <p>
+            Go to <a href={deviceLogin.verification_uri} target="_blank" rel="noreferrer">{deviceLogin.verification_uri}</a> and enter
+          </p>
+          <div className="device-code">{deviceLogin.user_code}</div>
+          <p>Waiting for you to finish, the code expires {new Date(deviceLogin.expires_at).toLocaleTimeString()}</p>
+          <button onClick={cancelDeviceLogin}>Cancel</button>
+        </div>
+      )}
+
+      <div className="twitch-account-actions">
+        <button onClick={() => authorize(['moderation'])} disabled={busy}>Allow moderation</button>
+        <button onClick={() => authorize(['channel_points'])} disabled={busy}>Allow channel points</button>
+      </div>
+
+      <div className="redirect-port">
+        <label htmlFor="redirectPort">Browser login port</label>
+        <input
+          id="redirectPort"
+          type="number"
+          min={1}
+          max={65535}
+          value={redirectPort}
+          onChange={(e) => setRedirectPort(e.target.value)}
+          onBlur={saveRedirectPort}
+        />
+        <small>Add http://localhost:{redirectPort} as a redirect URL of your Twitch app</small>
+      </div>
+
+      {status && message && <div className="save-message error">{message}</div>}
+    </div>
+  );
+};
+
+export default TwitchAccount;
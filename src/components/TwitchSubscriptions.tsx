@@ -0,0 +1,94 @@
+import React, { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
+import './TwitchSubscriptions.css';
+
+interface AvailableSubscription {
+  kind: string;
+  name: string;
+  required_scopes: string[];
+}
+
+interface SubscriptionStatus {
+  kind: string;
+  state: 'pending' | 'active' | 'rejected';
+  reason: string | null;
+}
+
+interface Settings {
+  twitch: {
+    subscriptions: string[];
+  };
+}
+
+const errorMessage = (error: unknown) =>
+  typeof error === 'object' && error !== null && 'message' in error ? String((error as { message: string }).message) : String(error);
+
+// Pick the Twitch events to receive and see which ones Twitch accepted
+const TwitchSubscriptions: React.FC = () => {
+  const [available, setAvailable] = useState<AvailableSubscription[]>([]);
+  const [selected, setSelected] = useState<string[]>([]);
+  const [statuses, setStatuses] = useState<SubscriptionStatus[]>([]);
+  const [message, setMessage] = useState('');
+
+  useEffect(() => {
+    invoke<AvailableSubscription[]>('available_twitch_subscriptions').then(setAvailable);
+    invoke<Settings>('get_settings').then((settings) => setSelected(settings.twitch.subscriptions));
+    invoke<SubscriptionStatus[]>('twitch_subscription_status').then(setStatuses);
+
+    const unlistenStatus = listen<SubscriptionStatus[]>('twitch-subscriptions', (event) => setStatuses(event.payload));
+    const unlistenSettings = listen<Settings>('settings-changed', (event) => setSelected(event.payload.twitch.subscriptions));
+    return () => {
+      unlistenStatus.then((unlisten) => unlisten());
+      unlistenSettings.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  const toggle = async (kind: string) => {
+    const subscriptions = selected.includes(kind) ? selected.filter((s) => s !== kind) : [...selected, kind];
+    try {
+      const settings = await invoke<Settings>('update_settings', { changes: { twitch: { subscriptions } } });
+      setSelected(settings.twitch.subscriptions);
+      setMessage('');
+    } catch (error) {
+      setMessage(errorMessage(error));
+    }
+  };
+
+  const statusOf = (kind: string) => statuses.find((status) => status.kind === kind);
+
+  return (
+    <div className="twitch-subscriptions">
+      <h3>Twitch Events</h3>
+      <p className="subscriptions-hint">Changes reconnect the Twitch listener. New events may need you to log in again to grant access.</p>
+
+      {available.map((subscription) => {
+        const status = selected.includes(subscription.kind) ? statusOf(subscription.kind) : undefined;
+        return (
+          <div key={subscription.kind} className="subscription-row">
+            <label className="settings-label" title={subscription.required_scopes.join(', ')}>
+              <input
+                type="checkbox"
+                checked={selected.includes(subscription.kind)}
+                onChange={() => toggle(subscription.kind)}
+              />
+              {subscription.name}
+            </label>
+            {status && (
+              <span className={`subscription-state ${status.state}`} title={status.reason ?? ''}>
+                {status.state}
+              </span>
+            )}
+          </div>
+        );
+      })}
+
+      {statuses.filter((status) => status.state === 'rejected' && status.reason).map((status) => (
+        <div key={status.kind} className="subscription-reason">{status.kind}: {status.reason}</div>
+      ))}
+      {message && <div className="save-message error">{message}</div>}
+    </div>
+  );
+};
+
+export default TwitchSubscriptions;